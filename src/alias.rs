@@ -0,0 +1,76 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use crate::stdio::Io;
+
+// Alias names and the text they stand for, kept sorted for listing
+pub type Aliases = BTreeMap<String, String>;
+
+// Anything the lexer would not split up or treat specially can name an alias
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "=/$`\\'\"|&;()<>".contains(c))
+}
+
+// Always single quoted, the way bash prints aliases
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// alias NAME=value... defines aliases, alias NAME... shows them and plain alias (or -p)
+// lists them all in a form that can be read back in
+pub fn define_aliases(aliases: &mut Aliases, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let args = args.strip_prefix(&["-p"]).unwrap_or(args);
+    if args.is_empty() {
+        for (name, value) in aliases.iter() {
+            writeln!(io.stdout, "alias {}={}", name, quote(value))?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) if is_valid_name(name) => {
+                aliases.insert(name.to_string(), value.to_string());
+            }
+            Some(_) => {
+                writeln!(io.stderr, "alias: `{}': invalid alias name", arg)?;
+                status = 1;
+            }
+            None => match aliases.get(*arg) {
+                Some(value) => writeln!(io.stdout, "alias {}={}", arg, quote(value))?,
+                None => {
+                    writeln!(io.stderr, "alias: {}: not found", arg)?;
+                    status = 1;
+                }
+            },
+        }
+    }
+    Ok(status)
+}
+
+// unalias NAME... removes aliases, unalias -a all of them
+pub fn remove_aliases(aliases: &mut Aliases, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    if args.first() == Some(&"-a") {
+        aliases.clear();
+        return Ok(0);
+    }
+    if args.is_empty() {
+        writeln!(io.stderr, "unalias: usage: unalias [-a] name [name ...]")?;
+        return Ok(2);
+    }
+
+    let mut status = 0;
+    for name in args {
+        if aliases.remove(*name).is_none() {
+            writeln!(io.stderr, "unalias: {}: not found", name)?;
+            status = 1;
+        }
+    }
+    Ok(status)
+}
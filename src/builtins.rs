@@ -0,0 +1,367 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+
+use crate::{signals, stdio::Io};
+
+// Resolves a path argument against the shell's current directory, expanding `~`
+pub fn resolve_path(current_dir: &Path, name: &str) -> Option<PathBuf> {
+    if name == "~" || name.starts_with("~/") {
+        let home_dir = dirs::home_dir()?;
+        if name == "~" {
+            Some(home_dir)
+        } else {
+            // Remove the ~ and join with home
+            Some(home_dir.join(&name[2..]))
+        }
+    } else if Path::new(name).is_absolute() {
+        Some(Path::new(name).to_path_buf())
+    } else {
+        // On Windows this keeps the drive of current_dir for `\dir`, and a path with
+        // another drive such as `D:dir` replaces current_dir altogether
+        Some(current_dir.join(name))
+    }
+}
+
+// Opens a file operand for reading, `-` being the command's stdin
+pub fn open_input(current_dir: &Path, name: &str, io: &Io) -> io::Result<Box<dyn Read + Send>> {
+    if name == "-" {
+        return Ok(Box::new(io.stdin.try_clone()?));
+    }
+    let path = resolve_path(current_dir, name)
+        .ok_or_else(|| io::Error::other("Could not determine home directory"))?;
+    let file = File::open(path)?;
+    // Reading a directory only fails at the first read, say so up front
+    if file.metadata()?.is_dir() {
+        return Err(io::ErrorKind::IsADirectory.into());
+    }
+    Ok(Box::new(file))
+}
+
+// Like BufRead::read_until with a newline, but fails with Interrupted on Ctrl-C, even in
+// the middle of a line that never ends
+pub fn read_line(reader: &mut impl BufRead, line: &mut Vec<u8>) -> io::Result<usize> {
+    let mut read = 0;
+    loop {
+        if signals::interrupt_pending() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            return Ok(read);
+        }
+        let (used, done) = match available.iter().position(|byte| *byte == b'\n') {
+            Some(end) => (end + 1, true),
+            None => (available.len(), false),
+        };
+        line.extend_from_slice(&available[..used]);
+        reader.consume(used);
+        read += used;
+        if done {
+            return Ok(read);
+        }
+    }
+}
+
+// The last part of a path argument, `a/b/` gives `b`
+pub fn file_name(name: &str) -> String {
+    let trimmed = name.trim_end_matches(['/', '\\']);
+    match trimmed.rsplit(['/', '\\']).next() {
+        Some(last) if !last.is_empty() => last.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+// Asks on stderr and reads the answer from the command's stdin, yes starts with y
+pub fn confirm(question: &str, io: &mut Io) -> io::Result<bool> {
+    write!(io.stderr, "{}", question)?;
+    io.stderr.flush()?;
+
+    let mut answer = Vec::new();
+    let mut byte = [0];
+    while io.stdin.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        answer.push(byte[0]);
+    }
+    Ok(matches!(answer.first(), Some(b'y' | b'Y')))
+}
+
+pub fn display_help(io: &mut Io) -> io::Result<i32> {
+    writeln!(io.stdout, "Available commands:")?;
+    writeln!(io.stdout, "  cd <dir>       - Change directory")?;
+    writeln!(io.stdout, "  pwd            - Print working directory")?;
+    writeln!(
+        io.stdout,
+        "  ls [-alhRtSr] [path] - List directory contents (-l long, -h sizes, -R recursive)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  mkdir [-pv] [-m mode] <dir> - Create directories (-p with missing parents)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  rm [-rfiIv] <path> - Remove files (--dry-run to preview, --trash to move to the trash)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  cp [-rfinvpa] <src>... <dest> - Copy files (-r directories, -p keep mode and times)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  mv [-finvu] <src>... <dest> - Move or rename files"
+    )?;
+    writeln!(
+        io.stdout,
+        "  ln [-sfrv] <target> [name] - Make a hard link, or a symbolic one with -s"
+    )?;
+    writeln!(
+        io.stdout,
+        "  cat [-nbsAv] [file] - Display files or stdin (-n numbers lines, -A shows non-printing)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  head [-n lines|-c bytes] [file] - Print the first lines of files or stdin"
+    )?;
+    writeln!(
+        io.stdout,
+        "  tail [-n lines|-c bytes] [-f] [file] - Print the last lines (-f follows growing files)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  wc [-lwmc] [file] - Count lines, words and bytes"
+    )?;
+    writeln!(
+        io.stdout,
+        "  grep [-ivnrclowx] <pattern> [file] - Print lines matching a regular expression"
+    )?;
+    writeln!(
+        io.stdout,
+        "  sort [-nrfu] [-k key] [-t sep] [file] - Sort lines (-n numbers, -k fields)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  uniq [-cdu] [input [output]] - Fold repeated adjacent lines (-c counts them)"
+    )?;
+    writeln!(io.stdout, "  echo <text>    - Display text")?;
+    writeln!(
+        io.stdout,
+        "  touch [-acm] [-d date|-r file] <file> - Update file times, creating missing files"
+    )?;
+    writeln!(io.stdout, "  clear          - Clear screen")?;
+    writeln!(
+        io.stdout,
+        "  jobs           - List background and stopped jobs"
+    )?;
+    writeln!(
+        io.stdout,
+        "  fg [%job]      - Bring a job to the foreground"
+    )?;
+    writeln!(
+        io.stdout,
+        "  bg [%job]      - Resume a stopped job in the background"
+    )?;
+    writeln!(
+        io.stdout,
+        "  wait [%job]    - Wait for background jobs to finish"
+    )?;
+    writeln!(
+        io.stdout,
+        "  kill <%job|pid> - Send a signal to a job or process"
+    )?;
+    writeln!(
+        io.stdout,
+        "  set -o option  - Turn on vi, emacs, nullglob or failglob (+o turns off)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  history [n]    - List, clear (-c), delete (-d n) or write (-w) history"
+    )?;
+    writeln!(
+        io.stdout,
+        "  export NAME[=value] - Export a variable to commands (-n to stop)"
+    )?;
+    writeln!(io.stdout, "  unset NAME     - Remove a variable")?;
+    writeln!(
+        io.stdout,
+        "  env [NAME=value]... [cmd] - Show the environment or run cmd with changes"
+    )?;
+    writeln!(io.stdout, "  set            - List all variables")?;
+    writeln!(
+        io.stdout,
+        "  source FILE    - Run the commands of a file in this shell (also `. FILE`)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  local NAME[=value] - Make a variable local to the running function"
+    )?;
+    writeln!(
+        io.stdout,
+        "  return [n]     - Leave a function or sourced file with status n"
+    )?;
+    writeln!(
+        io.stdout,
+        "  alias [NAME=cmd] - Define or list aliases (unalias NAME removes one)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  command CMD    - Run CMD without alias or function lookup (also \\CMD)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  shift [n]      - Drop the first n positional parameters ($1, $2, ...)"
+    )?;
+    writeln!(
+        io.stdout,
+        "  break [n]      - Leave the n innermost loops (continue [n] skips ahead)"
+    )?;
+    writeln!(io.stdout, "  pkg            - Package management commands:")?;
+    writeln!(io.stdout, "     pkg install <package>  - Install a package")?;
+    writeln!(
+        io.stdout,
+        "     pkg search <query>     - Search for packages"
+    )?;
+    writeln!(io.stdout, "     pkg update [package]   - Update packages")?;
+    writeln!(
+        io.stdout,
+        "     pkg list               - List available package managers"
+    )?;
+    writeln!(io.stdout, "  help           - Display this help")?;
+    writeln!(io.stdout, "  exit [n]       - Exit the shell with status n")?;
+    writeln!(io.stdout)?;
+    writeln!(
+        io.stdout,
+        "Scripting: if/elif/else/fi, while/until ... do ... done, for x in ...; do ... done,"
+    )?;
+    writeln!(
+        io.stdout,
+        "case word in pattern) ... ;; esac and functions: name() {{ ...; }}"
+    )?;
+    writeln!(
+        io.stdout,
+        "time [-p] pipeline reports how long the pipeline took"
+    )?;
+    writeln!(io.stdout)?;
+    writeln!(io.stdout, "You can also execute any system command")?;
+
+    Ok(0)
+}
+
+pub fn change_directory(current_dir: &mut PathBuf, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    if args.is_empty() {
+        // Go to home directory if no args
+        if let Some(home_dir) = dirs::home_dir() {
+            *current_dir = home_dir;
+        } else {
+            writeln!(io.stderr, "{}", "Could not determine home directory".red())?;
+            return Ok(1);
+        }
+        return Ok(0);
+    }
+
+    let Some(path) = resolve_path(current_dir, args[0]) else {
+        writeln!(io.stderr, "{}", "Could not determine home directory".red())?;
+        return Ok(1);
+    };
+
+    // Only the shell's own directory changes: cd also runs in subshell and pipeline
+    // threads, which must not move the whole process
+    let resolved = fs::metadata(&path)
+        .and_then(|metadata| match metadata.is_dir() {
+            true => fs::canonicalize(&path),
+            false => Err(io::ErrorKind::NotADirectory.into()),
+        })
+        .map(without_verbatim_prefix);
+    match resolved {
+        Ok(resolved) => {
+            *current_dir = resolved;
+            Ok(0)
+        }
+        Err(e) => {
+            writeln!(
+                io.stderr,
+                "cd: {}: {}",
+                args[0].red(),
+                e.to_string().bright_red()
+            )?;
+            Ok(1)
+        }
+    }
+}
+
+// canonicalize gives `\\?\C:\dir` on Windows, the working directory is shown as `C:\dir`
+fn without_verbatim_prefix(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|path| path.strip_prefix(r"\\?\")) {
+        Some(plain) if !plain.starts_with("UNC") => PathBuf::from(plain),
+        _ => path,
+    }
+}
+
+pub fn echo(args: &[&str], io: &mut Io) -> io::Result<i32> {
+    writeln!(io.stdout, "{}", args.join(" "))?;
+    Ok(0)
+}
+
+pub fn print_working_directory(current_dir: &Path, io: &mut Io) -> io::Result<i32> {
+    writeln!(io.stdout, "{}", current_dir.display())?;
+    Ok(0)
+}
+
+pub fn clear_screen(io: &mut Io) -> io::Result<i32> {
+    if cfg!(target_os = "windows") {
+        // On Windows
+        let _ = Command::new("cmd")
+            .args(["/C", "cls"])
+            .stdout(io.stdout.to_stdio()?)
+            .status();
+    } else {
+        // On Unix, use ANSI escape codes
+        write!(io.stdout, "\x1B[2J\x1B[1;1H")?;
+        io.stdout.flush()?;
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    #[test]
+    fn resolves_paths_against_the_current_directory() {
+        let dir = Path::new("/work");
+        assert_eq!(resolve_path(dir, "a/b"), Some(PathBuf::from("/work/a/b")));
+        // A colon is an ordinary character in a file name
+        assert_eq!(resolve_path(dir, "a:b"), Some(PathBuf::from("/work/a:b")));
+        #[cfg(unix)]
+        assert_eq!(resolve_path(dir, "/etc"), Some(PathBuf::from("/etc")));
+    }
+
+    // Windows does not allow a colon in a file name
+    #[cfg(unix)]
+    #[test]
+    fn changes_only_to_directories() {
+        let dir = TempDir::new("cd");
+        dir.write("x:y/file", "");
+        let mut current_dir = fs::canonicalize(dir.path()).unwrap();
+        let start = current_dir.clone();
+
+        let run = testing::run("", |io| change_directory(&mut current_dir, &["x:y"], io));
+        assert_eq!(run.status, 0);
+        assert_eq!(current_dir, start.join("x:y"));
+
+        let run = testing::run("", |io| {
+            change_directory(&mut current_dir, &["file/.."], io)
+        });
+        assert_eq!(run.status, 1);
+        assert_eq!(current_dir, start.join("x:y"));
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{builtins, signals, stdio::Io};
+
+#[derive(Default)]
+struct Options {
+    // -n numbers every line, -b only the ones that are not empty
+    number: bool,
+    number_nonblank: bool,
+    squeeze_blank: bool,
+    show_nonprinting: bool,
+    show_ends: bool,
+    show_tabs: bool,
+}
+
+impl Options {
+    // Without these the bytes are copied as they are
+    fn formats(&self) -> bool {
+        self.number
+            || self.number_nonblank
+            || self.squeeze_blank
+            || self.show_nonprinting
+            || self.show_ends
+            || self.show_tabs
+    }
+}
+
+// Line numbering and blank line squeezing go on from one file to the next
+struct State {
+    line: u64,
+    at_line_start: bool,
+    blank_lines: usize,
+}
+
+const USAGE: &str = "Usage: cat [-nbsAvETet] [FILE]...";
+const BUFFER_SIZE: usize = 64 * 1024;
+
+// cat [OPTION]... [FILE]... copies the files, or stdin for `-` and when none are given,
+// to stdout a chunk at a time. Binary files are not dumped on a terminal without asking.
+pub fn concatenate(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut options = Options::default();
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--" => {
+                files.extend(args.by_ref());
+                break;
+            }
+            "--number" => options.number = true,
+            "--number-nonblank" => options.number_nonblank = true,
+            "--squeeze-blank" => options.squeeze_blank = true,
+            "--show-nonprinting" => options.show_nonprinting = true,
+            "--show-ends" => options.show_ends = true,
+            "--show-tabs" => options.show_tabs = true,
+            "--show-all" => {
+                options.show_nonprinting = true;
+                options.show_ends = true;
+                options.show_tabs = true;
+            }
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "cat: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'n' => options.number = true,
+                        'b' => options.number_nonblank = true,
+                        's' => options.squeeze_blank = true,
+                        'v' => options.show_nonprinting = true,
+                        'E' => options.show_ends = true,
+                        'T' => options.show_tabs = true,
+                        'A' => {
+                            options.show_nonprinting = true;
+                            options.show_ends = true;
+                            options.show_tabs = true;
+                        }
+                        'e' => {
+                            options.show_nonprinting = true;
+                            options.show_ends = true;
+                        }
+                        't' => {
+                            options.show_nonprinting = true;
+                            options.show_tabs = true;
+                        }
+                        // Output is never buffered across reads anyway
+                        'u' => {}
+                        _ => {
+                            writeln!(io.stderr, "cat: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => files.push(*arg),
+        }
+    }
+    if files.is_empty() {
+        files.push("-");
+    }
+
+    let mut state = State {
+        line: 0,
+        at_line_start: true,
+        blank_lines: 0,
+    };
+    let mut status = 0;
+    for name in files {
+        let result = if name == "-" {
+            let mut stdin = io.stdin.try_clone()?;
+            copy(&mut stdin, name, true, &options, &mut state, io)
+        } else {
+            let Some(path) = builtins::resolve_path(current_dir, name) else {
+                writeln!(io.stderr, "cat: Could not determine home directory")?;
+                status = 1;
+                continue;
+            };
+            match File::open(&path) {
+                Ok(mut file) => copy(&mut file, name, false, &options, &mut state, io),
+                Err(e) => Err(e),
+            }
+        };
+
+        match result {
+            Ok(true) => {}
+            Ok(false) => status = 1,
+            // Writing failed, the reader of a pipeline went away
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Err(e),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(128 + signals::SIGINT),
+            Err(e) => {
+                writeln!(io.stderr, "cat: {}: {}", name, e)?;
+                status = 1;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+// Copies one file to stdout, false when a binary file was held back
+fn copy(
+    reader: &mut impl Read,
+    name: &str,
+    from_stdin: bool,
+    options: &Options,
+    state: &mut State,
+    io: &mut Io,
+) -> io::Result<bool> {
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut output = Vec::new();
+    let mut first = true;
+
+    loop {
+        // Reads of files are not interrupted by the signal, so Ctrl-C is checked here
+        if signals::interrupt_pending() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let count = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let chunk = &buffer[..count];
+
+        // Like git and grep, a NUL byte near the start means binary
+        if first && !options.show_nonprinting && io.stdout.is_terminal() && chunk.contains(&0) {
+            if from_stdin || !io.stdin.is_terminal() {
+                writeln!(
+                    io.stderr,
+                    "cat: {}: binary file not shown on a terminal, use -v or redirect the output",
+                    name
+                )?;
+                return Ok(false);
+            }
+            let question = format!("cat: {}: binary file, show it anyway? [y/N] ", name);
+            if !builtins::confirm(&question, io)? {
+                return Ok(true);
+            }
+        }
+        first = false;
+
+        if !options.formats() {
+            io.stdout.write_all(chunk)?;
+            continue;
+        }
+        output.clear();
+        format_chunk(chunk, options, state, &mut output);
+        io.stdout.write_all(&output)?;
+    }
+
+    io.stdout.flush()?;
+    Ok(true)
+}
+
+fn format_chunk(chunk: &[u8], options: &Options, state: &mut State, output: &mut Vec<u8>) {
+    for &byte in chunk {
+        if state.at_line_start {
+            if byte == b'\n' {
+                state.blank_lines += 1;
+                if options.squeeze_blank && state.blank_lines > 1 {
+                    continue;
+                }
+                if options.number && !options.number_nonblank {
+                    state.line += 1;
+                    output.extend_from_slice(format!("{:6}\t", state.line).as_bytes());
+                }
+                if options.show_ends {
+                    output.push(b'$');
+                }
+                output.push(b'\n');
+                continue;
+            }
+            state.blank_lines = 0;
+            state.at_line_start = false;
+            if options.number || options.number_nonblank {
+                state.line += 1;
+                output.extend_from_slice(format!("{:6}\t", state.line).as_bytes());
+            }
+        }
+
+        if byte == b'\n' {
+            if options.show_ends {
+                output.push(b'$');
+            }
+            output.push(b'\n');
+            state.at_line_start = true;
+        } else if byte == b'\t' {
+            match options.show_tabs {
+                true => output.extend_from_slice(b"^I"),
+                false => output.push(b'\t'),
+            }
+        } else if options.show_nonprinting {
+            push_visible(byte, output);
+        } else {
+            output.push(byte);
+        }
+    }
+}
+
+// The -v notation: ^X for control characters, ^? for DEL and M- for the high half
+fn push_visible(byte: u8, output: &mut Vec<u8>) {
+    let low = if byte >= 0x80 {
+        output.extend_from_slice(b"M-");
+        byte - 0x80
+    } else {
+        byte
+    };
+    match low {
+        0x7f => output.extend_from_slice(b"^?"),
+        0..0x20 => output.extend_from_slice(&[b'^', low + 0x40]),
+        _ => output.push(low),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    // Formats the chunks one after the other, like the reads of one file
+    fn format(chunks: &[&[u8]], options: &Options) -> String {
+        let mut state = State {
+            line: 0,
+            at_line_start: true,
+            blank_lines: 0,
+        };
+        let mut output = Vec::new();
+        for chunk in chunks {
+            format_chunk(chunk, options, &mut state, &mut output);
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn numbers_lines() {
+        let number = Options {
+            number: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            format(&[b"a\n\nb"], &number),
+            "     1\ta\n     2\t\n     3\tb"
+        );
+        let nonblank = Options {
+            number: true,
+            number_nonblank: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            format(&[b"a\n", b"\nb\n"], &nonblank),
+            "     1\ta\n\n     2\tb\n"
+        );
+    }
+
+    #[test]
+    fn squeezes_blank_lines_across_chunks() {
+        let squeeze = Options {
+            squeeze_blank: true,
+            ..Options::default()
+        };
+        assert_eq!(format(&[b"a\n\n", b"\n\nb\n\n"], &squeeze), "a\n\nb\n\n");
+    }
+
+    #[test]
+    fn shows_nonprinting_characters() {
+        let all = Options {
+            show_nonprinting: true,
+            show_ends: true,
+            show_tabs: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            format(&[b"\x01\t\x7f\x81\xe9 \n"], &all),
+            "^A^I^?M-^AM-i $\n"
+        );
+        // Without -v only tabs and ends change
+        let ends = Options {
+            show_ends: true,
+            ..Options::default()
+        };
+        assert_eq!(format(&[b"\x01\t\n"], &ends), "\x01\t$\n");
+    }
+
+    #[test]
+    fn numbering_goes_on_across_files() {
+        let dir = TempDir::new("cat-files");
+        dir.write("a", "one\ntwo\n");
+        let run = testing::run("three\n", |io| {
+            concatenate(dir.path(), &["-n", "a", "-", "missing"], io)
+        });
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stdout, "     1\tone\n     2\ttwo\n     3\tthree\n");
+        assert!(run.stderr.starts_with("cat: missing: "));
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        let run = testing::run("", |io| concatenate(Path::new("."), &["-nq"], io));
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("cat: invalid option -- 'q'\n{}\n", USAGE)
+        );
+    }
+}
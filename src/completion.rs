@@ -0,0 +1,337 @@
+use std::{env, fs, path::Path};
+
+use crate::{builtins, lexer, pkg, shell, shell::Shell};
+
+pub struct Candidate {
+    // Completed word, before quoting
+    pub text: String,
+    // What the menu shows, the name without the directory part
+    pub display: String,
+    // Directories are completed without a trailing space so the path can go on
+    pub is_dir: bool,
+}
+
+pub struct Completion {
+    // Character offset in the line where the replaced text starts
+    pub start: usize,
+    // The word as typed, after quote removal. Candidates that are no longer than this
+    // add nothing to it.
+    pub word: String,
+    // Quote the word was opened with, None for variables which are inserted as they are
+    quote: Quoting,
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Quoting {
+    Backslash,
+    Single,
+    Double,
+    None,
+}
+
+impl Completion {
+    // The text that replaces the word. finished closes an open quote and adds a space
+    // so the next argument can be typed right away.
+    pub fn replacement(&self, text: &str, finished: bool) -> String {
+        let mut result = match self.quote {
+            Quoting::None => text.to_string(),
+            Quoting::Single => format!("'{}", text.replace('\'', r"'\''")),
+            Quoting::Double => {
+                let mut quoted = String::from('"');
+                for c in text.chars() {
+                    if matches!(c, '"' | '\\' | '$' | '`') {
+                        quoted.push('\\');
+                    }
+                    quoted.push(c);
+                }
+                quoted
+            }
+            Quoting::Backslash => {
+                let mut quoted = String::new();
+                for c in text.chars() {
+                    if c.is_whitespace() || "'\"\\|&;<>()$`*?[]{}!#".contains(c) {
+                        quoted.push('\\');
+                    }
+                    quoted.push(c);
+                }
+                quoted
+            }
+        };
+
+        if finished {
+            match self.quote {
+                Quoting::Single => result.push('\''),
+                Quoting::Double => result.push('"'),
+                _ => {}
+            }
+            result.push(' ');
+        }
+        result
+    }
+
+    // Longest prefix shared by every candidate
+    pub fn common_prefix(&self) -> String {
+        let mut candidates = self.candidates.iter().map(|c| c.text.as_str());
+        let Some(first) = candidates.next() else {
+            return String::new();
+        };
+
+        let mut prefix = first.to_string();
+        for text in candidates {
+            let common = prefix
+                .chars()
+                .zip(text.chars())
+                .take_while(|(a, b)| a == b)
+                .count();
+            prefix = prefix.chars().take(common).collect();
+        }
+        prefix
+    }
+}
+
+// Finds candidates for the word that ends at the end of line, which is the text of
+// the current line up to the cursor
+pub fn complete(shell: &Shell, line: &[char]) -> Completion {
+    let context = scan(line);
+    let raw: String = line[context.start..].iter().collect();
+
+    if let Some(dollar) = context.variable {
+        let braced = line.get(dollar + 1) == Some(&'{');
+        let name: String = line[dollar + 1 + usize::from(braced)..].iter().collect();
+        return Completion {
+            start: dollar,
+            word: line[dollar..].iter().collect(),
+            quote: Quoting::None,
+            candidates: variable_candidates(shell, &name, braced),
+        };
+    }
+
+    let word = lexer::unquote(&raw);
+    let quote = match raw.chars().next() {
+        Some('\'') => Quoting::Single,
+        Some('"') => Quoting::Double,
+        _ => Quoting::Backslash,
+    };
+
+    let candidates = if context.words.is_empty() && !word.contains('/') && !word.starts_with('~') {
+        command_candidates(&word)
+    } else if context.words.len() == 1 && matches!(context.words[0].as_str(), "pkg" | "package") {
+        pkg::SUBCOMMANDS
+            .iter()
+            .filter(|name| name.starts_with(&word))
+            .map(|name| Candidate {
+                text: name.to_string(),
+                display: name.to_string(),
+                is_dir: false,
+            })
+            .collect()
+    } else {
+        path_candidates(&shell.current_dir, &word, context.words.is_empty())
+    };
+
+    Completion {
+        start: context.start,
+        word,
+        quote,
+        candidates,
+    }
+}
+
+struct Context {
+    // Where the word under the cursor starts
+    start: usize,
+    // Words of the current command before that one, unquoted
+    words: Vec<String>,
+    // Position of a `$` starting a variable name that runs up to the cursor
+    variable: Option<usize>,
+}
+
+// Splits the line the same way the lexer does, just far enough to know which word
+// the cursor is in and what comes before it in the same command
+fn scan(line: &[char]) -> Context {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    // The word after `<` or `>` is a file name, not an argument
+    let mut redirect = false;
+
+    for (i, &c) in line.iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else if c == '\\' && q == '"' {
+                escaped = true;
+            }
+        } else if c.is_whitespace() || "|&;()<>".contains(c) {
+            if let Some(word_start) = start.take() {
+                if !redirect {
+                    let raw: String = line[word_start..i].iter().collect();
+                    words.push(lexer::unquote(&raw));
+                }
+                redirect = false;
+            }
+            match c {
+                '<' | '>' => redirect = true,
+                '|' | '&' | ';' | '(' | ')' => {
+                    words.clear();
+                    redirect = false;
+                }
+                _ => {}
+            }
+        } else {
+            start.get_or_insert(i);
+            match c {
+                '\\' => escaped = true,
+                '\'' | '"' => quote = Some(c),
+                _ => {}
+            }
+        }
+    }
+
+    if redirect {
+        // Completing a file name, never a command
+        words.push(String::new());
+    }
+
+    let start = start.unwrap_or(line.len());
+    Context {
+        start,
+        words,
+        variable: variable_start(line, start),
+    }
+}
+
+// Finds a `$NAME` or `${NAME` that the word ends with, outside single quotes
+fn variable_start(line: &[char], start: usize) -> Option<usize> {
+    let mut dollar = None;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, &c) in line.iter().enumerate().skip(start) {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if quote != Some('\'') => escaped = true,
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            '$' if quote != Some('\'') => dollar = Some(i),
+            _ => {}
+        }
+    }
+
+    let dollar = dollar?;
+    let name = &line[dollar + 1..];
+    let name = name.strip_prefix(&['{']).unwrap_or(name);
+    name.iter().all(|c| is_name_char(*c)).then_some(dollar)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn variable_candidates(shell: &Shell, prefix: &str, braced: bool) -> Vec<Candidate> {
+    // Variables are kept sorted already
+    shell
+        .variables
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| Candidate {
+            text: if braced {
+                format!("${{{}}}", name)
+            } else {
+                format!("${}", name)
+            },
+            display: name,
+            is_dir: false,
+        })
+        .collect()
+}
+
+// Builtins and every executable found on PATH
+fn command_candidates(prefix: &str) -> Vec<Candidate> {
+    let mut names: Vec<String> = shell::BUILTINS
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect();
+
+    if let Some(path) = env::var_os("PATH") {
+        for dir in env::split_paths(&path) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.starts_with(prefix) && is_executable::is_executable(entry.path()) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| Candidate {
+            text: name.clone(),
+            display: name,
+            is_dir: false,
+        })
+        .collect()
+}
+
+// Entries of the directory named by word, matching the part after its last slash.
+// Dotfiles only show up when the name being completed starts with a dot.
+fn path_candidates(current_dir: &Path, word: &str, executables_only: bool) -> Vec<Candidate> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(slash) => word.split_at(slash + 1),
+        None if word == "~" => ("~/", ""),
+        None => ("", word),
+    };
+    let Some(path) = (if dir.is_empty() {
+        Some(current_dir.to_path_buf())
+    } else {
+        builtins::resolve_path(current_dir, dir)
+    }) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<Candidate> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            // Follows symlinks so a link to a directory completes like one
+            let is_dir = entry.path().is_dir();
+            if executables_only && !is_dir && !is_executable::is_executable(entry.path()) {
+                return None;
+            }
+
+            let suffix = if is_dir { "/" } else { "" };
+            Some(Candidate {
+                text: format!("{}{}{}", dir, name, suffix),
+                display: format!("{}{}", name, suffix),
+                is_dir,
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| a.text.cmp(&b.text));
+    candidates
+}
@@ -0,0 +1,882 @@
+use std::{
+    fs::{self, File, FileTimes, Metadata, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    builtins, ln, ls, signals,
+    stdio::{Io, Output},
+};
+
+// What to do when the destination is already there
+#[derive(Clone, Copy, PartialEq)]
+pub enum Overwrite {
+    Always,
+    // -f: replace it, deleting it first when it cannot be opened
+    Force,
+    // -i: ask first
+    Ask,
+    // -n: leave it alone
+    Never,
+}
+
+struct Options {
+    recursive: bool,
+    overwrite: Overwrite,
+    verbose: bool,
+    // -p keeps mode, owner and times
+    preserve: bool,
+    // Copy what symlinks point to instead of the links themselves
+    follow: bool,
+    // -u only replaces files that are older than the source
+    update: bool,
+}
+
+const USAGE: &str = "Usage: cp [-rRfinvpauLP] SOURCE... DEST | -t DIRECTORY SOURCE...";
+
+// cp [OPTION]... SOURCE... DEST copies files, and directories with -r. Symlinks given as
+// operands are followed unless copying recursively, where links are copied as links
+// (-L and -P choose either way). Large copies draw a progress bar on a terminal.
+pub fn copy(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut options = Options {
+        recursive: false,
+        overwrite: Overwrite::Always,
+        verbose: false,
+        preserve: false,
+        follow: false,
+        update: false,
+    };
+    let mut dereference = None;
+    let mut no_target_directory = false;
+    let mut target_directory = None;
+    let mut operands = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--" => {
+                operands.extend(args.by_ref().copied());
+                break;
+            }
+            "--recursive" => options.recursive = true,
+            "--force" => options.overwrite = Overwrite::Force,
+            "--interactive" => options.overwrite = Overwrite::Ask,
+            "--no-clobber" => options.overwrite = Overwrite::Never,
+            "--verbose" => options.verbose = true,
+            "--preserve" => options.preserve = true,
+            "--archive" => {
+                options.recursive = true;
+                options.preserve = true;
+                dereference = Some(false);
+            }
+            "--update" => options.update = true,
+            "--dereference" => dereference = Some(true),
+            "--no-dereference" => dereference = Some(false),
+            "--no-target-directory" => no_target_directory = true,
+            _ if arg.starts_with("--target-directory=") => {
+                target_directory = Some(arg["--target-directory=".len()..].to_string());
+            }
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "cp: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'r' | 'R' => options.recursive = true,
+                        // The last of -f, -i and -n wins
+                        'f' => options.overwrite = Overwrite::Force,
+                        'i' => options.overwrite = Overwrite::Ask,
+                        'n' => options.overwrite = Overwrite::Never,
+                        'v' => options.verbose = true,
+                        'p' => options.preserve = true,
+                        'a' => {
+                            options.recursive = true;
+                            options.preserve = true;
+                            dereference = Some(false);
+                        }
+                        'u' => options.update = true,
+                        'L' => dereference = Some(true),
+                        'P' | 'd' => dereference = Some(false),
+                        'T' => no_target_directory = true,
+                        't' => {
+                            let rest = &arg[2 + i..];
+                            let value = match rest.is_empty() {
+                                true => args.next().map(|value| value.to_string()),
+                                false => Some(rest.to_string()),
+                            };
+                            let Some(value) = value else {
+                                writeln!(io.stderr, "cp: option requires an argument -- 't'")?;
+                                writeln!(io.stderr, "{}", USAGE)?;
+                                return Ok(2);
+                            };
+                            target_directory = Some(value);
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "cp: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => operands.push(*arg),
+        }
+    }
+    options.follow = dereference.unwrap_or(!options.recursive);
+
+    let pairs = match destinations(
+        "cp",
+        current_dir,
+        operands,
+        target_directory.as_deref(),
+        no_target_directory,
+        io,
+    )? {
+        Ok(pairs) => pairs,
+        Err(status) => return Ok(status),
+    };
+
+    let sources: Vec<_> = pairs
+        .iter()
+        .filter_map(|(from, _)| builtins::resolve_path(current_dir, from))
+        .collect();
+    let mut progress = Progress::new(&io.stderr, || {
+        sources
+            .iter()
+            .map(|source| tree_size(source, options.follow))
+            .sum()
+    });
+
+    let mut status = 0;
+    for (from_name, to_name) in pairs {
+        let (Some(from), Some(to)) = (
+            builtins::resolve_path(current_dir, &from_name),
+            builtins::resolve_path(current_dir, &to_name),
+        ) else {
+            writeln!(io.stderr, "cp: Could not determine home directory")?;
+            status = 1;
+            continue;
+        };
+
+        if from.is_dir() && options.recursive && is_inside(&from, &to) {
+            writeln!(
+                io.stderr,
+                "cp: cannot copy a directory, '{}', into itself, '{}'",
+                from_name, to_name
+            )?;
+            status = 1;
+            continue;
+        }
+
+        match copy_path(
+            &from,
+            &to,
+            &from_name,
+            &to_name,
+            &options,
+            &mut progress,
+            io,
+        ) {
+            Ok(true) => {}
+            Ok(false) => status = 1,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                progress.clear();
+                return Ok(128 + signals::SIGINT);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    progress.clear();
+
+    Ok(status)
+}
+
+// Pairs each source operand with where it goes: into the last operand (or -t) when that
+// is a directory, otherwise onto it. The status to return after a usage error.
+pub fn destinations(
+    command: &str,
+    current_dir: &Path,
+    mut operands: Vec<&str>,
+    target_directory: Option<&str>,
+    no_target_directory: bool,
+    io: &mut Io,
+) -> io::Result<Result<Vec<(String, String)>, i32>> {
+    let is_directory =
+        |name: &str| builtins::resolve_path(current_dir, name).is_some_and(|path| path.is_dir());
+
+    let directory = match target_directory {
+        Some(directory) if !is_directory(directory) => {
+            writeln!(
+                io.stderr,
+                "{}: target '{}' is not a directory",
+                command, directory
+            )?;
+            return Ok(Err(1));
+        }
+        Some(directory) => directory.to_string(),
+        None if operands.len() < 2 => {
+            match operands.first() {
+                Some(operand) => writeln!(
+                    io.stderr,
+                    "{}: missing destination file operand after '{}'",
+                    command, operand
+                )?,
+                None => writeln!(io.stderr, "{}: missing file operand", command)?,
+            }
+            return Ok(Err(2));
+        }
+        None => {
+            let last = operands.pop().unwrap_or_default();
+            if no_target_directory || !is_directory(last) {
+                if operands.len() > 1 {
+                    writeln!(
+                        io.stderr,
+                        "{}: target '{}' is not a directory",
+                        command, last
+                    )?;
+                    return Ok(Err(1));
+                }
+                return Ok(Ok(vec![(operands[0].to_string(), last.to_string())]));
+            }
+            last.to_string()
+        }
+    };
+
+    if operands.is_empty() {
+        writeln!(io.stderr, "{}: missing file operand", command)?;
+        return Ok(Err(2));
+    }
+    Ok(Ok(operands
+        .iter()
+        .map(|operand| {
+            let name = builtins::file_name(operand);
+            let to = format!("{}/{}", directory.trim_end_matches('/'), name);
+            (operand.to_string(), to)
+        })
+        .collect()))
+}
+
+// Copies one operand, going through directories. False when something failed, which
+// has been reported already; Err only for Ctrl-C and write errors on stdout.
+fn copy_path(
+    from: &Path,
+    to: &Path,
+    from_name: &str,
+    to_name: &str,
+    options: &Options,
+    progress: &mut Progress,
+    io: &mut Io,
+) -> io::Result<bool> {
+    let metadata = match options.follow {
+        true => fs::metadata(from),
+        false => fs::symlink_metadata(from),
+    };
+    let metadata = match metadata {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            progress.clear();
+            writeln!(io.stderr, "cp: cannot stat '{}': {}", from_name, e)?;
+            return Ok(false);
+        }
+    };
+
+    if metadata.is_dir() {
+        if !options.recursive {
+            progress.clear();
+            writeln!(
+                io.stderr,
+                "cp: -r not specified; omitting directory '{}'",
+                from_name
+            )?;
+            return Ok(false);
+        }
+        // A directory made here gets its final mode once its contents are copied
+        let mut created = false;
+        match fs::metadata(to) {
+            Ok(existing) if existing.is_dir() => {}
+            Ok(_) => {
+                progress.clear();
+                writeln!(
+                    io.stderr,
+                    "cp: cannot overwrite non-directory '{}' with directory '{}'",
+                    to_name, from_name
+                )?;
+                return Ok(false);
+            }
+            Err(_) => {
+                if let Err(e) = create_dir(to, &metadata) {
+                    progress.clear();
+                    writeln!(
+                        io.stderr,
+                        "cp: cannot create directory '{}': {}",
+                        to_name, e
+                    )?;
+                    return Ok(false);
+                }
+                created = true;
+                if options.verbose {
+                    writeln!(io.stdout, "'{}' -> '{}'", from_name, to_name)?;
+                }
+            }
+        }
+
+        let entries = match fs::read_dir(from) {
+            Ok(entries) => entries,
+            Err(e) => {
+                progress.clear();
+                writeln!(
+                    io.stderr,
+                    "cp: cannot open directory '{}': {}",
+                    from_name, e
+                )?;
+                return Ok(false);
+            }
+        };
+        let mut ok = true;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            ok &= copy_path(
+                &entry.path(),
+                &to.join(entry.file_name()),
+                &format!("{}/{}", from_name.trim_end_matches('/'), name),
+                &format!("{}/{}", to_name.trim_end_matches('/'), name),
+                options,
+                progress,
+                io,
+            )?;
+        }
+        // Last, so the times are not changed again by the copies inside
+        if options.preserve
+            && let Err(e) = copy_metadata(&metadata, to)
+        {
+            progress.clear();
+            writeln!(
+                io.stderr,
+                "cp: preserving attributes of '{}': {}",
+                to_name, e
+            )?;
+            ok = false;
+        } else if !options.preserve
+            && created
+            && let Err(e) = finish_dir(to, &metadata)
+        {
+            progress.clear();
+            writeln!(
+                io.stderr,
+                "cp: setting permissions for '{}': {}",
+                to_name, e
+            )?;
+            ok = false;
+        }
+        return Ok(ok);
+    }
+
+    if let Ok(existing) = fs::symlink_metadata(to) {
+        if same_file(from, to, true) {
+            progress.clear();
+            writeln!(
+                io.stderr,
+                "cp: '{}' and '{}' are the same file",
+                from_name, to_name
+            )?;
+            return Ok(false);
+        }
+        if fs::metadata(to).is_ok_and(|target| target.is_dir()) {
+            progress.clear();
+            writeln!(
+                io.stderr,
+                "cp: cannot overwrite directory '{}' with non-directory",
+                to_name
+            )?;
+            return Ok(false);
+        }
+        if options.update && is_newer(&existing, &metadata) {
+            return Ok(true);
+        }
+        match options.overwrite {
+            Overwrite::Never => return Ok(true),
+            Overwrite::Ask => {
+                progress.clear();
+                if !builtins::confirm(&format!("cp: overwrite '{}'? ", to_name), io)? {
+                    return Ok(true);
+                }
+            }
+            Overwrite::Always | Overwrite::Force => {}
+        }
+    }
+
+    let result = if metadata.file_type().is_symlink() {
+        // A link replaces whatever is there, writing through it would change its target
+        let _ = fs::remove_file(to);
+        fs::read_link(from).and_then(|target| ln::symlink(&target, to))
+    } else if metadata.is_file() {
+        copy_file(
+            from,
+            to,
+            &metadata,
+            options.overwrite == Overwrite::Force,
+            progress,
+        )
+    } else {
+        progress.clear();
+        writeln!(io.stderr, "cp: cannot copy special file '{}'", from_name)?;
+        return Ok(false);
+    };
+    let result = result.and_then(|()| match options.preserve {
+        true => copy_metadata(&metadata, to),
+        false => Ok(()),
+    });
+
+    match result {
+        Ok(()) => {
+            if options.verbose {
+                writeln!(io.stdout, "'{}' -> '{}'", from_name, to_name)?;
+            }
+            Ok(true)
+        }
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => Err(e),
+        Err(e) => {
+            progress.clear();
+            writeln!(
+                io.stderr,
+                "cp: cannot copy '{}' to '{}': {}",
+                from_name, to_name, e
+            )?;
+            Ok(false)
+        }
+    }
+}
+
+// Copies a file, symlink or whole tree with everything -p keeps, for moving between
+// file systems
+pub fn copy_all(from: &Path, to: &Path, progress: &mut Progress) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_dir() {
+        create_dir(to, &metadata)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()), progress)?;
+        }
+    } else if metadata.file_type().is_symlink() {
+        ln::symlink(&fs::read_link(from)?, to)?;
+    } else if metadata.is_file() {
+        copy_file(from, to, &metadata, false, progress)?;
+    } else {
+        return Err(io::Error::other("cannot copy special file"));
+    }
+    copy_metadata(&metadata, to)
+}
+
+// Copies the contents of a regular file. A new file gets the permissions of the source
+// (less the umask), an existing one keeps its own. Ctrl-C stops it, and a file the copy
+// created is removed rather than left half copied.
+fn copy_file(
+    from: &Path,
+    to: &Path,
+    metadata: &Metadata,
+    force: bool,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    let mut source = File::open(from)?;
+    let mut created = fs::symlink_metadata(to).is_err();
+    let mut destination = match create(to, metadata) {
+        Err(e) if force && e.kind() != io::ErrorKind::NotFound => {
+            fs::remove_file(to)?;
+            created = true;
+            create(to, metadata)?
+        }
+        result => result?,
+    };
+
+    let mut buffer = vec![0; 128 * 1024];
+    loop {
+        if signals::interrupt_pending() {
+            drop(destination);
+            if created {
+                let _ = fs::remove_file(to);
+            }
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let count = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        destination.write_all(&buffer[..count])?;
+        progress.advance(count as u64);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create(path: &Path, metadata: &Metadata) -> io::Result<File> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(metadata.permissions().mode() & 0o777)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create(path: &Path, _metadata: &Metadata) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
+
+// Makes a directory for the copy of one: it gets the permissions of the source less the
+// umask, like a new file, but its owner may write to it until finish_dir, so the copies
+// inside can be made even when the source is read-only
+#[cfg(unix)]
+fn create_dir(path: &Path, metadata: &Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    fs::DirBuilder::new()
+        .mode(metadata.permissions().mode() & 0o777 | 0o700)
+        .create(path)
+}
+
+#[cfg(not(unix))]
+fn create_dir(path: &Path, _metadata: &Metadata) -> io::Result<()> {
+    fs::create_dir(path)
+}
+
+// Takes back the owner permissions create_dir added
+#[cfg(unix)]
+fn finish_dir(path: &Path, metadata: &Metadata) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let added = 0o700 & !metadata.permissions().mode();
+    if added == 0 {
+        return Ok(());
+    }
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() & !added);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn finish_dir(_path: &Path, _metadata: &Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+// Gives to the permissions, times and, where allowed, the owner of the source
+fn copy_metadata(metadata: &Metadata, to: &Path) -> io::Result<()> {
+    // Only root may give files away, anyone else stays the owner of the copy
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let _ = std::os::unix::fs::lchown(to, Some(metadata.uid()), Some(metadata.gid()));
+    }
+    // The link's own mode and times cannot be set without following it
+    if metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    let file = File::open(to).or_else(|_| OpenOptions::new().write(true).open(to))?;
+    file.set_times(times)?;
+    fs::set_permissions(to, metadata.permissions())
+}
+
+// Whether both paths lead to the same file, looking at symlinks themselves unless follow
+pub fn same_file(a: &Path, b: &Path, follow: bool) -> bool {
+    let metadata = |path: &Path| match follow {
+        true => fs::metadata(path),
+        false => fs::symlink_metadata(path),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (metadata(a), metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let canonical = |path: &Path| match metadata(path) {
+            Ok(found) if found.file_type().is_symlink() => Ok(path.to_path_buf()),
+            _ => fs::canonicalize(path),
+        };
+        matches!((canonical(a), canonical(b)), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+// Whether to is from itself or somewhere inside it, so copying would never end
+pub fn is_inside(from: &Path, to: &Path) -> bool {
+    let Ok(from) = fs::canonicalize(from) else {
+        return false;
+    };
+    // The destination usually does not exist yet, its parent does
+    let to = match fs::canonicalize(to) {
+        Ok(to) => to,
+        Err(_) => match (to.parent().map(fs::canonicalize), to.file_name()) {
+            (Some(Ok(parent)), Some(name)) => parent.join(name),
+            _ => return false,
+        },
+    };
+    to.starts_with(&from)
+}
+
+// -u: whether the existing file was modified at the same time as the source or later
+pub fn is_newer(existing: &Metadata, source: &Metadata) -> bool {
+    matches!((existing.modified(), source.modified()), (Ok(a), Ok(b)) if a >= b)
+}
+
+// Bytes in the regular files at path and below it
+pub fn tree_size(path: &Path, follow: bool) -> u64 {
+    let metadata = match follow {
+        true => fs::metadata(path),
+        false => fs::symlink_metadata(path),
+    };
+    match metadata {
+        Ok(metadata) if metadata.is_dir() => fs::read_dir(path).map_or(0, |entries| {
+            entries
+                .flatten()
+                .map(|entry| tree_size(&entry.path(), follow))
+                .sum()
+        }),
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => 0,
+    }
+}
+
+// Copies of at least this many bytes show a progress bar
+const PROGRESS_THRESHOLD: u64 = 32 * 1024 * 1024;
+
+// A progress bar on stderr for a copy:
+// [##########                    ]  33% 120M/360M 85M/s
+pub struct Progress {
+    output: Option<Output>,
+    total: u64,
+    done: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    pub fn none() -> Self {
+        Progress {
+            output: None,
+            total: 0,
+            done: 0,
+            started: Instant::now(),
+            drawn: None,
+        }
+    }
+
+    // Draws on stderr when it is a terminal and the copy, whose size total works out,
+    // is large enough to take a while
+    pub fn new(stderr: &Output, total: impl FnOnce() -> u64) -> Self {
+        if !stderr.is_terminal() {
+            return Progress::none();
+        }
+        let total = total();
+        if total < PROGRESS_THRESHOLD {
+            return Progress::none();
+        }
+        Progress {
+            output: stderr.try_clone().ok(),
+            total,
+            ..Progress::none()
+        }
+    }
+
+    fn advance(&mut self, bytes: u64) {
+        let Some(output) = &mut self.output else {
+            return;
+        };
+        self.done += bytes;
+        // Redrawing ten times a second is plenty
+        if self
+            .drawn
+            .is_some_and(|drawn| drawn.elapsed() < Duration::from_millis(100))
+        {
+            return;
+        }
+        self.drawn = Some(Instant::now());
+
+        let percent = (self.done * 100 / self.total.max(1)).min(100);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = match elapsed > 0.0 {
+            true => (self.done as f64 / elapsed) as u64,
+            false => 0,
+        };
+        let text = format!(
+            " {:>3}% {}/{} {}/s",
+            percent,
+            ls::human_size(self.done),
+            ls::human_size(self.total),
+            ls::human_size(rate)
+        );
+        let width = match crossterm::terminal::size() {
+            Ok((columns, _)) if columns > 0 => columns as usize,
+            _ => 80,
+        };
+        let bar = width.saturating_sub(text.len() + 3).min(40);
+        let filled = bar * percent as usize / 100;
+        let _ = write!(
+            output,
+            "\r[{}{}]{}",
+            "#".repeat(filled),
+            " ".repeat(bar - filled),
+            text
+        );
+        let _ = output.flush();
+    }
+
+    // Removes the bar, before a message or once the copy is done
+    pub fn clear(&mut self) {
+        if let Some(output) = &mut self.output
+            && self.drawn.take().is_some()
+        {
+            let _ = write!(output, "\r\x1b[K");
+            let _ = output.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn cp(dir: &TempDir, input: &str, args: &[&str]) -> testing::Run {
+        testing::run(input, |io| copy(dir.path(), args, io))
+    }
+
+    #[test]
+    fn rejects_bad_options_and_operands() {
+        let dir = TempDir::new("cp-options");
+        let run = cp(&dir, "", &["-rx", "a", "b"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("cp: invalid option -- 'x'\n{}\n", USAGE)
+        );
+        assert_eq!(cp(&dir, "", &["--recursiv", "a", "b"]).status, 2);
+
+        let run = cp(&dir, "", &[]);
+        assert_eq!(run.status, 2);
+        assert!(run.stderr.starts_with("cp: missing file operand\n"));
+        let run = cp(&dir, "", &["a"]);
+        assert_eq!(run.status, 2);
+        assert!(
+            run.stderr
+                .starts_with("cp: missing destination file operand after 'a'\n")
+        );
+
+        dir.write("a", "a");
+        dir.write("b", "b");
+        let run = cp(&dir, "", &["a", "b", "c"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stderr, "cp: target 'c' is not a directory\n");
+    }
+
+    #[test]
+    fn copies_files() {
+        let dir = TempDir::new("cp-files");
+        dir.write("a", "new");
+        dir.write("b", "old");
+
+        let run = cp(&dir, "", &["-n", "a", "b"]);
+        assert_eq!(run.status, 0);
+        assert_eq!(dir.read("b"), "old");
+
+        let run = cp(&dir, "", &["-v", "a", "b"]);
+        assert_eq!((run.status, run.stdout.as_str()), (0, "'a' -> 'b'\n"));
+        assert_eq!(dir.read("b"), "new");
+
+        let run = cp(&dir, "n\n", &["-i", "b", "a"]);
+        assert_eq!(run.status, 0);
+        assert_eq!(run.stderr, "cp: overwrite 'a'? ");
+
+        fs::create_dir(dir.join("d")).unwrap();
+        assert_eq!(cp(&dir, "", &["a", "b", "d"]).status, 0);
+        assert_eq!(cp(&dir, "", &["-t", "d", "a"]).status, 0);
+        assert_eq!(dir.read("d/a"), "new");
+        assert_eq!(dir.read("d/b"), "new");
+
+        let run = cp(&dir, "", &["a", "a"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stderr, "cp: 'a' and 'a' are the same file\n");
+    }
+
+    #[test]
+    fn copies_directories_only_with_r() {
+        let dir = TempDir::new("cp-dirs");
+        dir.write("d/e/f", "f");
+
+        let run = cp(&dir, "", &["d", "copy"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stderr, "cp: -r not specified; omitting directory 'd'\n");
+        assert!(!dir.join("copy").exists());
+
+        assert_eq!(cp(&dir, "", &["-r", "d", "copy"]).status, 0);
+        assert_eq!(dir.read("copy/e/f"), "f");
+
+        let run = cp(&dir, "", &["-r", "d", "d/e"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(
+            run.stderr,
+            "cp: cannot copy a directory, 'd', into itself, 'd/e/d'\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gives_new_directories_the_mode_of_the_source() {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        let dir = TempDir::new("cp-modes");
+        let mode = |name: &str| fs::metadata(dir.join(name)).unwrap().permissions().mode() & 0o777;
+        // What the umask leaves of a mode
+        fs::DirBuilder::new()
+            .mode(0o777)
+            .create(dir.join("umask"))
+            .unwrap();
+        let umask = mode("umask");
+
+        dir.write("shared/f", "f");
+        dir.write("read-only/f", "f");
+        fs::set_permissions(dir.join("shared"), fs::Permissions::from_mode(0o775)).unwrap();
+        fs::set_permissions(dir.join("read-only"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        assert_eq!(
+            cp(&dir, "", &["-r", "shared", "read-only", "umask/"]).status,
+            0
+        );
+        assert_eq!(mode("umask/shared"), 0o775 & umask);
+        assert_eq!(mode("umask/read-only"), 0o555 & umask);
+        assert_eq!(dir.read("umask/read-only/f"), "f");
+
+        // The directories can be removed again
+        for name in ["read-only", "umask/read-only"] {
+            fs::set_permissions(dir.join(name), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn knows_when_a_path_is_inside_another() {
+        let dir = TempDir::new("cp-inside");
+        dir.write("d/e/f", "f");
+        assert!(is_inside(&dir.join("d"), &dir.join("d")));
+        assert!(is_inside(&dir.join("d"), &dir.join("d/e/new")));
+        assert!(is_inside(&dir.join("d/e/.."), &dir.join("d/e")));
+        assert!(!is_inside(&dir.join("d/e"), &dir.join("d")));
+        assert!(!is_inside(&dir.join("d"), &dir.join("dd")));
+        assert!(!is_inside(&dir.join("missing"), &dir.join("missing/x")));
+    }
+}
@@ -0,0 +1,1165 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    Line,
+    completion::{self, Completion},
+    parser,
+    shell::Shell,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditMode {
+    Emacs,
+    Vi,
+}
+
+// State of a Ctrl-R incremental search
+struct Search {
+    query: String,
+    // History entry currently shown
+    index: Option<usize>,
+    failed: bool,
+    // Line to go back to when the search is cancelled
+    original: Vec<char>,
+    original_cursor: usize,
+}
+
+// Candidates listed below the line after an ambiguous Tab, further Tabs cycle through them
+struct Menu {
+    completion: Completion,
+    selected: Option<usize>,
+    // Part of the buffer holding the word being completed
+    start: usize,
+    end: usize,
+}
+
+enum Action {
+    Continue,
+    Accept,
+    Interrupt,
+    Eof,
+}
+
+// Raw-mode line editor used for the interactive prompt
+pub struct Editor {
+    buffer: Vec<char>,
+    // Index into buffer, the cursor sits right before this character
+    cursor: usize,
+    prompt: String,
+    continuation: String,
+    // Shown at the right edge of the first line while the text leaves room for it
+    right_prompt: String,
+    // Terminal row of the cursor at the last redraw, counted from the first prompt row
+    cursor_row: usize,
+    mode: EditMode,
+    // Vi starts every line in insert mode, Escape switches to command mode
+    vi_insert: bool,
+    // Repeat count and operator (d, c, y, r) typed so far in vi command mode
+    vi_count: Option<usize>,
+    vi_pending: Option<char>,
+    // f, F, t or T waiting for the character to look for, and the last search for ; and ,
+    vi_find: Option<char>,
+    last_find: Option<(char, char)>,
+    // Text removed by the last kill, put back by Ctrl-Y (or p/P in vi)
+    kill_buffer: String,
+    // Whether the previous key killed text, consecutive kills are joined
+    last_kill: bool,
+    this_kill: bool,
+    // Buffer and cursor before each change, typed runs of characters count as one change
+    undo: Vec<(Vec<char>, usize)>,
+    inserting: bool,
+    // History entry shown by Up/Down, None while editing a new line
+    history_index: Option<usize>,
+    // The new line, kept while browsing history
+    saved_line: Vec<char>,
+    search: Option<Search>,
+    menu: Option<Menu>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Editor {
+            buffer: Vec::new(),
+            cursor: 0,
+            prompt: String::new(),
+            continuation: String::new(),
+            right_prompt: String::new(),
+            cursor_row: 0,
+            mode: EditMode::Emacs,
+            vi_insert: true,
+            vi_count: None,
+            vi_pending: None,
+            vi_find: None,
+            last_find: None,
+            kill_buffer: String::new(),
+            last_kill: false,
+            this_kill: false,
+            undo: Vec::new(),
+            inserting: false,
+            history_index: None,
+            saved_line: Vec::new(),
+            search: None,
+            menu: None,
+        }
+    }
+
+    // Reads one command, which may span several lines when the shell needs more input
+    // (trailing backslash, here-document). continuation is shown before those lines.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        continuation: &str,
+        right_prompt: &str,
+        shell: &Shell,
+    ) -> io::Result<Line> {
+        self.buffer.clear();
+        self.cursor = 0;
+        self.prompt = prompt.to_string();
+        self.continuation = continuation.to_string();
+        self.right_prompt = right_prompt.to_string();
+        self.cursor_row = 0;
+        self.mode = shell.edit_mode;
+        self.vi_insert = true;
+        self.vi_count = None;
+        self.vi_pending = None;
+        self.vi_find = None;
+        self.last_kill = false;
+        self.undo.clear();
+        self.inserting = false;
+        self.history_index = None;
+        self.search = None;
+        self.menu = None;
+
+        terminal::enable_raw_mode()?;
+        let result = self.edit(shell);
+        terminal::disable_raw_mode()?;
+        result
+    }
+
+    fn edit(&mut self, shell: &Shell) -> io::Result<Line> {
+        self.refresh()?;
+
+        loop {
+            let action = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => self.handle_key(key, shell),
+                Event::Resize(..) => Action::Continue,
+                _ => continue,
+            };
+
+            match action {
+                Action::Continue => self.refresh()?,
+                Action::Accept => {
+                    self.finish("\r\n")?;
+                    let mut line: String = self.buffer.iter().collect();
+                    line.push('\n');
+                    return Ok(Line::Text(line));
+                }
+                Action::Interrupt => {
+                    self.finish("^C")?;
+                    return Ok(Line::Interrupted);
+                }
+                Action::Eof => {
+                    self.finish("\r\n")?;
+                    return Ok(Line::Eof);
+                }
+            }
+        }
+    }
+
+    // Moves past the end of the input so whatever comes next starts below it
+    fn finish(&mut self, text: &str) -> io::Result<()> {
+        self.cursor = self.buffer.len();
+        self.refresh()?;
+        let mut stdout = io::stdout();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    // Redraws the prompt and the whole buffer, then puts the cursor back in place
+    fn refresh(&mut self) -> io::Result<()> {
+        // Some terminals (serial consoles, bare ptys) report no size at all
+        let (width, height) = match terminal::size() {
+            Ok((columns, rows)) if columns > 0 && rows > 0 => (columns as usize, rows as usize),
+            _ => (80, 24),
+        };
+        let mut screen = Screen {
+            out: String::new(),
+            width,
+            row: 0,
+            column: 0,
+        };
+
+        if self.cursor_row > 0 {
+            screen.out.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        screen.out.push_str("\r\x1b[J");
+
+        match &self.search {
+            Some(search) => screen.put_styled(&format!(
+                "({}reverse-i-search)`{}': ",
+                if search.failed { "failed " } else { "" },
+                search.query
+            )),
+            None => screen.put_styled(&self.prompt),
+        }
+        let prompt_row = screen.row;
+        let mut cursor = screen.position(self.buffer.first().copied());
+        for (i, &c) in self.buffer.iter().enumerate() {
+            screen.put(c);
+            if c == '\n' {
+                screen.put_styled(&self.continuation);
+            }
+            if i + 1 == self.cursor {
+                cursor = screen.position(self.buffer.get(i + 1).copied());
+            }
+        }
+        // The right prompt goes away once the line grows into it
+        let right_width = styled_width(&self.right_prompt);
+        if self.search.is_none()
+            && right_width > 0
+            && screen.row == prompt_row
+            && screen.column + right_width < screen.width
+        {
+            // The last column stays empty, writing there makes some terminals wrap
+            let padding = screen.width - right_width - screen.column - 1;
+            screen.out.push_str(&" ".repeat(padding));
+            screen.column += padding;
+            screen.put_styled(&self.right_prompt);
+            // So a color left on by the right prompt does not spill into the menu
+            screen.out.push_str("\x1b[0m");
+        }
+        if screen.column >= screen.width {
+            screen.newline();
+        }
+        if let Some(menu) = &self.menu {
+            screen.put_menu(menu, height);
+        }
+
+        if screen.row > cursor.0 {
+            screen
+                .out
+                .push_str(&format!("\x1b[{}A", screen.row - cursor.0));
+        }
+        screen.out.push('\r');
+        if cursor.1 > 0 {
+            screen.out.push_str(&format!("\x1b[{}C", cursor.1));
+        }
+        self.cursor_row = cursor.0;
+
+        let mut stdout = io::stdout();
+        stdout.write_all(screen.out.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, shell: &Shell) -> Action {
+        self.last_kill = std::mem::take(&mut self.this_kill);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        if self.search.is_some() && self.search_key(key, shell) {
+            return Action::Continue;
+        }
+        if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+            self.menu = None;
+        }
+
+        match key.code {
+            KeyCode::Char('c') if ctrl => return Action::Interrupt,
+            KeyCode::Char('d') if ctrl && self.buffer.is_empty() => return Action::Eof,
+            KeyCode::Char('j' | 'm') if ctrl => return self.accept(),
+            KeyCode::Enter => return self.accept(),
+            KeyCode::Char('l') if ctrl => {
+                // Clear the screen, the prompt is redrawn at the top
+                let _ = io::stdout().write_all(b"\x1b[H\x1b[2J");
+                self.cursor_row = 0;
+                return Action::Continue;
+            }
+            KeyCode::Char('r') if ctrl => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    index: None,
+                    failed: false,
+                    original: self.buffer.clone(),
+                    original_cursor: self.cursor,
+                });
+                return Action::Continue;
+            }
+            _ => {}
+        }
+
+        match self.mode {
+            EditMode::Emacs => self.emacs_key(key, shell),
+            EditMode::Vi if self.vi_insert => self.vi_insert_key(key, shell),
+            EditMode::Vi => self.vi_command_key(key, shell),
+        }
+        Action::Continue
+    }
+
+    fn accept(&mut self) -> Action {
+        let line: String = self.buffer.iter().collect();
+        if parser::is_incomplete(&line) {
+            // Not a full command yet, keep editing on a new line
+            self.cursor = self.buffer.len();
+            self.insert('\n');
+            return Action::Continue;
+        }
+        Action::Accept
+    }
+
+    // Handles a key while searching. Returns false when the key ends the search and
+    // should then be handled as usual.
+    fn search_key(&mut self, key: KeyEvent, shell: &Shell) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let entries = shell.history.entries();
+        let Some(search) = self.search.as_mut() else {
+            return false;
+        };
+
+        // Where to look next, the shown entry is tried again after the query changes
+        let from = match key.code {
+            KeyCode::Char('r') if ctrl => search.index.unwrap_or(entries.len()),
+            KeyCode::Char(c) if !ctrl && !alt => {
+                search.query.push(c);
+                search.index.map_or(entries.len(), |index| index + 1)
+            }
+            KeyCode::Backspace => {
+                search.query.pop();
+                entries.len()
+            }
+            KeyCode::Char('g') if ctrl => {
+                self.buffer = std::mem::take(&mut search.original);
+                self.cursor = search.original_cursor;
+                self.search = None;
+                return true;
+            }
+            _ => {
+                // Keep the entry that was found and leave the search
+                if let Some(search) = self.search.take()
+                    && search.index.is_some()
+                {
+                    self.saved_line = search.original;
+                    self.history_index = search.index;
+                }
+                return false;
+            }
+        };
+
+        let found = (0..from)
+            .rev()
+            .find(|&i| !search.query.is_empty() && entries[i].contains(&search.query));
+        search.failed = found.is_none() && !search.query.is_empty();
+        if let Some(index) = found {
+            let entry = &entries[index];
+            let offset = entry.find(&search.query).unwrap_or(0);
+            search.index = Some(index);
+            self.buffer = entry.chars().collect();
+            self.cursor = entry[..offset].chars().count();
+        }
+        true
+    }
+
+    fn emacs_key(&mut self, key: KeyEvent, shell: &Shell) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.cursor = self.line_start(self.cursor),
+            KeyCode::Char('e') if ctrl => self.cursor = self.line_end(self.cursor),
+            KeyCode::Char('b') if ctrl => self.move_left(),
+            KeyCode::Char('f') if ctrl => self.move_right(),
+            KeyCode::Char('b') if alt => self.cursor = self.word_start(self.cursor),
+            KeyCode::Char('f') if alt => self.cursor = self.word_end(self.cursor),
+            KeyCode::Left if ctrl || alt => self.cursor = self.word_start(self.cursor),
+            KeyCode::Right if ctrl || alt => self.cursor = self.word_end(self.cursor),
+            KeyCode::Char('p') if ctrl => self.previous_line(shell),
+            KeyCode::Char('n') if ctrl => self.next_line(shell),
+            KeyCode::Char('d') if ctrl => self.delete_char(),
+            KeyCode::Char('h') if ctrl => self.backspace(),
+            KeyCode::Char('k') if ctrl => {
+                let end = self.line_end(self.cursor);
+                // At the end of a line Ctrl-K joins it with the next one
+                let end = if end == self.cursor {
+                    (end + 1).min(self.buffer.len())
+                } else {
+                    end
+                };
+                self.kill(self.cursor, end, false);
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.kill(self.line_start(self.cursor), self.cursor, true)
+            }
+            KeyCode::Char('w') if ctrl => {
+                self.kill(self.big_word_start(self.cursor), self.cursor, true)
+            }
+            KeyCode::Char('d') if alt => self.kill(self.cursor, self.word_end(self.cursor), false),
+            KeyCode::Backspace if alt => self.kill(self.word_start(self.cursor), self.cursor, true),
+            KeyCode::Char('y') if ctrl => self.yank(self.cursor),
+            KeyCode::Char('t') if ctrl => self.transpose(),
+            KeyCode::Char('_' | '/' | '7') if ctrl => self.undo(),
+            KeyCode::Char(c) if !ctrl && !alt => self.insert(c),
+            _ => self.common_key(key, shell),
+        }
+    }
+
+    fn vi_insert_key(&mut self, key: KeyEvent, shell: &Shell) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => self.vi_leave_insert(),
+            // Escape followed quickly by a command key arrives as Alt plus that key
+            KeyCode::Char(_) if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.vi_leave_insert();
+                self.vi_command_key(key, shell);
+            }
+            KeyCode::Char('d') if ctrl => self.delete_char(),
+            KeyCode::Char('h') if ctrl => self.backspace(),
+            KeyCode::Char('u') if ctrl => {
+                self.kill(self.line_start(self.cursor), self.cursor, true)
+            }
+            KeyCode::Char('w') if ctrl => {
+                self.kill(self.big_word_start(self.cursor), self.cursor, true)
+            }
+            KeyCode::Char('y') if ctrl => self.yank(self.cursor),
+            KeyCode::Char(c) if !ctrl => self.insert(c),
+            _ => self.common_key(key, shell),
+        }
+    }
+
+    // Arrow and editing keys that behave the same in every keymap
+    fn common_key(&mut self, key: KeyEvent, shell: &Shell) {
+        match key.code {
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Home => self.cursor = self.line_start(self.cursor),
+            KeyCode::End => self.cursor = self.line_end(self.cursor),
+            KeyCode::Up => self.previous_line(shell),
+            KeyCode::Down => self.next_line(shell),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete_char(),
+            KeyCode::Tab => self.complete(shell, true),
+            KeyCode::BackTab => self.complete(shell, false),
+            _ => {}
+        }
+    }
+
+    // Completes the word before the cursor as far as it is unambiguous. When nothing
+    // more can be added the candidates are listed, and Tab/Shift-Tab step through them.
+    fn complete(&mut self, shell: &Shell, forward: bool) {
+        if let Some(menu) = self.menu.as_mut() {
+            let count = menu.completion.candidates.len();
+            let selected = match (menu.selected, forward) {
+                (None, true) => 0,
+                (None, false) => count - 1,
+                (Some(i), true) => (i + 1) % count,
+                (Some(i), false) => (i + count - 1) % count,
+            };
+            menu.selected = Some(selected);
+
+            let text = menu
+                .completion
+                .replacement(&menu.completion.candidates[selected].text, false);
+            let (start, end) = (menu.start, menu.end);
+            menu.end = start + text.chars().count();
+            self.replace_range(start, end, &text);
+            return;
+        }
+
+        let line_start = self.line_start(self.cursor);
+        let completion = completion::complete(shell, &self.buffer[line_start..self.cursor]);
+        let start = line_start + completion.start;
+
+        let text = match completion.candidates.as_slice() {
+            [] => {
+                let _ = io::stdout().write_all(b"\x07");
+                return;
+            }
+            [only] => completion.replacement(&only.text, !only.is_dir),
+            _ => {
+                let prefix = completion.common_prefix();
+                if prefix.chars().count() > completion.word.chars().count() {
+                    completion.replacement(&prefix, false)
+                } else {
+                    self.menu = Some(Menu {
+                        completion,
+                        selected: None,
+                        start,
+                        end: self.cursor,
+                    });
+                    return;
+                }
+            }
+        };
+        self.replace_range(start, self.cursor, &text);
+    }
+
+    fn replace_range(&mut self, start: usize, end: usize, text: &str) {
+        self.save_undo();
+        self.inserting = false;
+
+        let text: Vec<char> = text.chars().collect();
+        self.cursor = start + text.len();
+        self.buffer.splice(start..end, text);
+    }
+
+    fn vi_command_key(&mut self, key: KeyEvent, shell: &Shell) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let c = match key.code {
+            KeyCode::Char(c) if !ctrl => c,
+            KeyCode::Esc => {
+                self.vi_count = None;
+                self.vi_pending = None;
+                self.vi_find = None;
+                return;
+            }
+            KeyCode::Left | KeyCode::Backspace => 'h',
+            KeyCode::Right => 'l',
+            KeyCode::Home => '0',
+            KeyCode::End => '$',
+            KeyCode::Up => 'k',
+            KeyCode::Down => 'j',
+            KeyCode::Delete => 'x',
+            _ => return,
+        };
+
+        if let Some('r') = self.vi_pending {
+            // Replace count characters under the cursor
+            self.vi_pending = None;
+            let count = self.vi_count.take().unwrap_or(1);
+            let end = self.cursor + count;
+            if end <= self.line_end(self.cursor) {
+                self.save_undo();
+                self.buffer[self.cursor..end].fill(c);
+                self.cursor = end - 1;
+            }
+            return;
+        }
+
+        let c = match self.vi_find.take() {
+            Some(kind) => {
+                // The searched character was just typed, run it as a repeat of that search
+                self.last_find = Some((kind, c));
+                ';'
+            }
+            None => c,
+        };
+
+        if c.is_ascii_digit() && (c != '0' || self.vi_count.is_some()) {
+            let digit = c.to_digit(10).unwrap_or(0) as usize;
+            self.vi_count = Some(self.vi_count.unwrap_or(0).saturating_mul(10) + digit);
+            return;
+        }
+        let count = self.vi_count.take().unwrap_or(1);
+
+        if matches!(c, 'f' | 'F' | 't' | 'T') {
+            self.vi_find = Some(c);
+            self.vi_count = Some(count);
+            return;
+        }
+
+        if let Some(op) = self.vi_pending.take() {
+            self.vi_operator(op, c, count);
+            self.vi_clamp();
+            return;
+        }
+
+        match c {
+            'i' => self.vi_enter_insert(),
+            'a' => {
+                if self.cursor < self.line_end(self.cursor) {
+                    self.cursor += 1;
+                }
+                self.vi_enter_insert();
+            }
+            'I' => {
+                self.cursor = self.first_non_blank(self.cursor);
+                self.vi_enter_insert();
+            }
+            'A' => {
+                self.cursor = self.line_end(self.cursor);
+                self.vi_enter_insert();
+            }
+            'x' => {
+                let end = (self.cursor + count).min(self.line_end(self.cursor));
+                self.kill(self.cursor, end, false);
+            }
+            'X' => {
+                let start = self
+                    .cursor
+                    .saturating_sub(count)
+                    .max(self.line_start(self.cursor));
+                self.kill(start, self.cursor, true);
+            }
+            'D' => self.kill(self.cursor, self.line_end(self.cursor), false),
+            'C' => {
+                self.kill(self.cursor, self.line_end(self.cursor), false);
+                self.vi_enter_insert();
+            }
+            's' => {
+                let end = (self.cursor + count).min(self.line_end(self.cursor));
+                self.kill(self.cursor, end, false);
+                self.vi_enter_insert();
+            }
+            'S' => self.vi_operator('c', 'c', count),
+            'p' => {
+                for _ in 0..count {
+                    let at = (self.cursor + 1).min(self.line_end(self.cursor));
+                    self.yank(at);
+                }
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            'P' => {
+                for _ in 0..count {
+                    self.yank(self.cursor);
+                }
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            '~' => {
+                self.save_undo();
+                let end = (self.cursor + count).min(self.line_end(self.cursor));
+                for c in &mut self.buffer[self.cursor..end] {
+                    *c = if c.is_lowercase() {
+                        c.to_uppercase().next().unwrap_or(*c)
+                    } else {
+                        c.to_lowercase().next().unwrap_or(*c)
+                    };
+                }
+                self.cursor = end;
+            }
+            'u' => self.undo(),
+            'd' | 'c' | 'y' | 'r' => {
+                self.vi_pending = Some(c);
+                // Keep the count for the motion that follows (2dw)
+                self.vi_count = Some(count);
+            }
+            'j' | '+' => (0..count).for_each(|_| self.next_line(shell)),
+            'k' | '-' => (0..count).for_each(|_| self.previous_line(shell)),
+            motion => {
+                if let Some(target) = self.vi_motion(motion, count) {
+                    self.cursor = target;
+                }
+            }
+        }
+
+        self.vi_clamp();
+    }
+
+    // Applies d, c or y to the text between the cursor and where the motion ends
+    fn vi_operator(&mut self, op: char, motion: char, count: usize) {
+        let (start, end) = if motion == op {
+            // dd, cc, yy work on the whole line
+            let start = self.line_start(self.cursor);
+            (start, self.line_end(self.cursor))
+        } else {
+            // cw changes up to the end of the word, like ce
+            let motion = if op == 'c' && motion == 'w' {
+                'e'
+            } else {
+                motion
+            };
+            let motion = if op == 'c' && motion == 'W' {
+                'E'
+            } else {
+                motion
+            };
+            let Some(target) = self.vi_motion(motion, count) else {
+                return;
+            };
+            // These motions include the character they land on
+            let inclusive = matches!(motion, 'e' | 'E')
+                || (matches!(motion, ';' | ',') && target > self.cursor);
+            let target = if inclusive {
+                (target + 1).min(self.buffer.len())
+            } else {
+                target
+            };
+            (self.cursor.min(target), self.cursor.max(target))
+        };
+
+        match op {
+            'y' => {
+                self.kill_buffer = self.buffer[start..end].iter().collect();
+                self.cursor = start;
+            }
+            'd' => self.kill(start, end, false),
+            _ => {
+                self.kill(start, end, false);
+                self.vi_enter_insert();
+            }
+        }
+    }
+
+    fn vi_motion(&self, motion: char, count: usize) -> Option<usize> {
+        let mut pos = self.cursor;
+        for _ in 0..count {
+            pos = match motion {
+                'h' => pos.saturating_sub(1).max(self.line_start(pos)),
+                'l' | ' ' => (pos + 1).min(self.line_end(pos)),
+                '0' => self.line_start(pos),
+                '^' => self.first_non_blank(pos),
+                '$' => self.line_end(pos),
+                'w' | 'W' => self.vi_word_forward(pos, motion == 'W'),
+                'b' | 'B' => self.vi_word_backward(pos, motion == 'B'),
+                'e' | 'E' => self.vi_word_end(pos, motion == 'E'),
+                ';' | ',' => self.vi_find_char(pos, motion == ',')?,
+                _ => return None,
+            };
+        }
+        Some(pos)
+    }
+
+    // Looks for the character of the last f, F, t or T on the current line.
+    // reverse searches the other way, for `,`.
+    fn vi_find_char(&self, pos: usize, reverse: bool) -> Option<usize> {
+        let (kind, target) = self.last_find?;
+        let till = matches!(kind, 't' | 'T');
+
+        if matches!(kind, 'f' | 't') != reverse {
+            let end = self.line_end(pos);
+            let found = (pos + 1..end).find(|&i| self.buffer[i] == target)?;
+            Some(if till { found - 1 } else { found })
+        } else {
+            let start = self.line_start(pos);
+            let found = (start..pos).rev().find(|&i| self.buffer[i] == target)?;
+            Some(if till { found + 1 } else { found })
+        }
+    }
+
+    fn vi_enter_insert(&mut self) {
+        self.save_undo();
+        self.vi_insert = true;
+        self.inserting = true;
+    }
+
+    fn vi_leave_insert(&mut self) {
+        self.vi_insert = false;
+        self.inserting = false;
+        // The cursor moves back onto the last inserted character
+        if self.cursor > self.line_start(self.cursor) {
+            self.move_left();
+        }
+    }
+
+    // In command mode the cursor stays on a character, never past the end of the line
+    fn vi_clamp(&mut self) {
+        if !self.vi_insert
+            && self.cursor > self.line_start(self.cursor)
+            && self.cursor == self.line_end(self.cursor)
+        {
+            self.move_left();
+        }
+    }
+
+    fn save_undo(&mut self) {
+        self.undo.push((self.buffer.clone(), self.cursor));
+    }
+
+    fn undo(&mut self) {
+        if let Some((buffer, cursor)) = self.undo.pop() {
+            self.buffer = buffer;
+            self.cursor = cursor;
+        }
+        self.inserting = false;
+    }
+
+    fn insert(&mut self, c: char) {
+        if !self.inserting {
+            self.save_undo();
+            self.inserting = true;
+        }
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            let end = self.cursor;
+            self.move_left();
+            self.save_undo();
+            self.buffer.drain(self.cursor..end);
+            self.inserting = false;
+        }
+    }
+
+    fn delete_char(&mut self) {
+        if self.cursor < self.buffer.len() {
+            let start = self.cursor;
+            self.move_right();
+            self.save_undo();
+            self.buffer.drain(start..self.cursor);
+            self.cursor = start;
+            self.inserting = false;
+        }
+    }
+
+    // Removes start..end into the kill buffer. backward kills are prepended when
+    // joined with the previous kill so repeated Ctrl-W keeps the text in order.
+    fn kill(&mut self, start: usize, end: usize, backward: bool) {
+        if start >= end {
+            return;
+        }
+        self.save_undo();
+        self.inserting = false;
+
+        let text: String = self.buffer.drain(start..end).collect();
+        if !self.last_kill {
+            self.kill_buffer.clear();
+        }
+        if backward {
+            self.kill_buffer.insert_str(0, &text);
+        } else {
+            self.kill_buffer.push_str(&text);
+        }
+        self.this_kill = true;
+        self.cursor = start;
+    }
+
+    fn yank(&mut self, at: usize) {
+        if self.kill_buffer.is_empty() {
+            return;
+        }
+        self.save_undo();
+        self.inserting = false;
+
+        let text: Vec<char> = self.kill_buffer.chars().collect();
+        self.cursor = at + text.len();
+        self.buffer.splice(at..at, text);
+    }
+
+    fn transpose(&mut self) {
+        if self.cursor == 0 || self.buffer.len() < 2 {
+            return;
+        }
+        self.save_undo();
+        self.inserting = false;
+
+        // At the end of the line the last two characters are swapped
+        if self.cursor == self.buffer.len() {
+            self.cursor -= 1;
+        }
+        self.buffer.swap(self.cursor - 1, self.cursor);
+        self.cursor += 1;
+    }
+
+    // Moves by one character, skipping over zero-width combining marks
+    fn move_left(&mut self) {
+        while self.cursor > 0 {
+            self.cursor -= 1;
+            if !is_zero_width(self.buffer[self.cursor]) {
+                break;
+            }
+        }
+    }
+
+    fn move_right(&mut self) {
+        if self.cursor < self.buffer.len() {
+            self.cursor += 1;
+        }
+        while self.cursor < self.buffer.len() && is_zero_width(self.buffer[self.cursor]) {
+            self.cursor += 1;
+        }
+    }
+
+    // Up moves within a multiline command first, then back through history
+    fn previous_line(&mut self, shell: &Shell) {
+        if self.line_start(self.cursor) > 0 {
+            self.move_line(-1);
+            return;
+        }
+
+        let entries = shell.history.entries();
+        let index = match self.history_index {
+            Some(0) => return,
+            Some(index) => index - 1,
+            None if entries.is_empty() => return,
+            None => {
+                self.saved_line = self.buffer.clone();
+                entries.len() - 1
+            }
+        };
+        self.history_index = Some(index);
+        self.replace_buffer(entries[index].chars().collect());
+    }
+
+    fn next_line(&mut self, shell: &Shell) {
+        if self.line_end(self.cursor) < self.buffer.len() {
+            self.move_line(1);
+            return;
+        }
+
+        let entries = shell.history.entries();
+        match self.history_index {
+            None => {}
+            Some(index) if index + 1 < entries.len() => {
+                self.history_index = Some(index + 1);
+                self.replace_buffer(entries[index + 1].chars().collect());
+            }
+            Some(_) => {
+                self.history_index = None;
+                let line = std::mem::take(&mut self.saved_line);
+                self.replace_buffer(line);
+            }
+        }
+    }
+
+    fn replace_buffer(&mut self, buffer: Vec<char>) {
+        self.save_undo();
+        self.inserting = false;
+        self.buffer = buffer;
+        self.cursor = self.buffer.len();
+    }
+
+    // Moves to the same column on the line above or below in a multiline command
+    fn move_line(&mut self, direction: isize) {
+        let start = self.line_start(self.cursor);
+        let column = self.cursor - start;
+
+        let target = if direction < 0 {
+            if start == 0 {
+                return;
+            }
+            self.line_start(start - 1)
+        } else {
+            let end = self.line_end(self.cursor);
+            if end == self.buffer.len() {
+                return;
+            }
+            end + 1
+        };
+
+        self.cursor = (target + column).min(self.line_end(target));
+    }
+
+    fn line_start(&self, pos: usize) -> usize {
+        self.buffer[..pos]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, pos: usize) -> usize {
+        self.buffer[pos..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(self.buffer.len(), |i| pos + i)
+    }
+
+    fn first_non_blank(&self, pos: usize) -> usize {
+        let mut pos = self.line_start(pos);
+        while pos < self.line_end(pos) && matches!(self.buffer[pos], ' ' | '\t') {
+            pos += 1;
+        }
+        pos
+    }
+
+    // Emacs words are runs of letters and digits
+    fn word_start(&self, mut pos: usize) -> usize {
+        while pos > 0 && !self.buffer[pos - 1].is_alphanumeric() {
+            pos -= 1;
+        }
+        while pos > 0 && self.buffer[pos - 1].is_alphanumeric() {
+            pos -= 1;
+        }
+        pos
+    }
+
+    fn word_end(&self, mut pos: usize) -> usize {
+        while pos < self.buffer.len() && !self.buffer[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        while pos < self.buffer.len() && self.buffer[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        pos
+    }
+
+    // Start of the whitespace-separated word before pos, used by Ctrl-W
+    fn big_word_start(&self, mut pos: usize) -> usize {
+        while pos > 0 && self.buffer[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        while pos > 0 && !self.buffer[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        pos
+    }
+
+    fn vi_word_forward(&self, mut pos: usize, big: bool) -> usize {
+        let len = self.buffer.len();
+        if pos < len {
+            let class = char_class(self.buffer[pos], big);
+            while pos < len && class != 0 && char_class(self.buffer[pos], big) == class {
+                pos += 1;
+            }
+        }
+        while pos < len && self.buffer[pos].is_whitespace() {
+            pos += 1;
+        }
+        pos
+    }
+
+    fn vi_word_backward(&self, mut pos: usize, big: bool) -> usize {
+        while pos > 0 && self.buffer[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        if pos > 0 {
+            let class = char_class(self.buffer[pos - 1], big);
+            while pos > 0 && char_class(self.buffer[pos - 1], big) == class {
+                pos -= 1;
+            }
+        }
+        pos
+    }
+
+    fn vi_word_end(&self, mut pos: usize, big: bool) -> usize {
+        let len = self.buffer.len();
+        if pos + 1 >= len {
+            return pos;
+        }
+        pos += 1;
+        while pos + 1 < len && self.buffer[pos].is_whitespace() {
+            pos += 1;
+        }
+        let class = char_class(self.buffer[pos], big);
+        while pos + 1 < len && char_class(self.buffer[pos + 1], big) == class {
+            pos += 1;
+        }
+        pos
+    }
+}
+
+// Tracks where text lands on the terminal while the editor redraws, wrapping
+// explicitly so wide characters never get split across rows
+struct Screen {
+    out: String,
+    width: usize,
+    row: usize,
+    column: usize,
+}
+
+impl Screen {
+    fn put(&mut self, c: char) {
+        if self.column >= self.width {
+            self.newline();
+        }
+        if c == '\n' {
+            self.newline();
+            return;
+        }
+
+        let width = c.width().unwrap_or(0);
+        if self.column + width > self.width {
+            self.newline();
+        }
+        self.out.push(c);
+        self.column += width;
+    }
+
+    fn newline(&mut self) {
+        self.out.push_str("\r\n");
+        self.row += 1;
+        self.column = 0;
+    }
+
+    // Text that may carry color escapes, those take no space on screen
+    fn put_styled(&mut self, text: &str) {
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                self.out.push(c);
+                for next in chars.by_ref() {
+                    self.out.push(next);
+                    if next.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            } else {
+                self.put(c);
+            }
+        }
+    }
+
+    // Lays the candidates out in columns below the line, only as many rows as fit on
+    // the terminal, scrolled so the selected one stays visible
+    fn put_menu(&mut self, menu: &Menu, height: usize) {
+        let candidates = &menu.completion.candidates;
+        let column_width = candidates
+            .iter()
+            .map(|c| c.display.width())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let columns = (self.width / column_width).max(1);
+        let rows = candidates.len().div_ceil(columns);
+        let visible = rows.min(height.saturating_sub(self.row + 1).max(1));
+        let selected_row = menu.selected.map_or(0, |i| i / columns);
+        let first = (selected_row + 1).saturating_sub(visible);
+
+        for row in first..first + visible {
+            self.newline();
+            for column in 0..columns {
+                let index = row * columns + column;
+                let Some(candidate) = candidates.get(index) else {
+                    break;
+                };
+                let padding = " ".repeat(column_width - candidate.display.width());
+                if menu.selected == Some(index) {
+                    self.put_styled(&format!("\x1b[7m{}\x1b[0m", candidate.display));
+                } else {
+                    self.put_styled(&candidate.display);
+                }
+                if column + 1 < columns {
+                    self.put_styled(&padding);
+                }
+            }
+        }
+    }
+
+    // Where the cursor shows up when next is the character under it
+    fn position(&self, next: Option<char>) -> (usize, usize) {
+        let width = match next {
+            Some(c) if c != '\n' => c.width().unwrap_or(0),
+            _ => 0,
+        };
+        if self.column >= self.width || self.column + width > self.width {
+            (self.row + 1, 0)
+        } else {
+            (self.row, self.column)
+        }
+    }
+}
+
+// Columns taken by text that may carry color escapes
+fn styled_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|next| next.is_ascii_alphabetic());
+        } else {
+            width += c.width().unwrap_or(0);
+        }
+    }
+    width
+}
+
+fn is_zero_width(c: char) -> bool {
+    c != '\n' && c.width() == Some(0)
+}
+
+// Vi word classes: blanks, word characters and punctuation. Big words (W, B, E)
+// only split on blanks.
+fn char_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
@@ -0,0 +1,746 @@
+use crate::{pattern, shell::Shell, variables};
+
+// Where a character of an expanded word came from
+#[derive(Clone, Copy, PartialEq)]
+pub enum Origin {
+    // Typed outside of quotes
+    Literal,
+    // Quoted or escaped, taken exactly as it is
+    Quoted,
+    // Produced by an unquoted expansion, which field splitting applies to
+    Expanded,
+    // Separates the parameters of "$@", which always stay separate fields
+    Break,
+}
+
+pub type Text = Vec<(char, Origin)>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Word,
+    DoubleQuoted,
+    // Like double quotes, except that `"` is an ordinary character
+    HereDoc,
+}
+
+// Expands the words of a command into its arguments: brace, tilde and parameter
+// expansion, field splitting of unquoted expansions, pathname expansion, then quote
+// removal
+pub fn expand_words(shell: &mut Shell, words: &[String]) -> Result<Vec<String>, String> {
+    let ifs = shell.variables.get("IFS").unwrap_or(" \t\n").to_string();
+    let mut fields = Vec::new();
+
+    for word in words.iter().flat_map(|word| brace_expand(word)) {
+        let chars: Vec<char> = word.chars().collect();
+        let (text, quoted) = expand(shell, &chars, Mode::Word)?;
+        for field in split_fields(text, quoted, &ifs) {
+            fields.extend(glob_field(shell, &field)?);
+        }
+    }
+
+    Ok(fields)
+}
+
+// Expands a single word without splitting it, for assignments and redirection targets
+pub fn expand_word(shell: &mut Shell, raw: &str) -> Result<String, String> {
+    let chars: Vec<char> = raw.chars().collect();
+    let (text, _) = expand(shell, &chars, Mode::Word)?;
+    Ok(to_string(&text))
+}
+
+// Expands a case pattern, quoted characters in it only match themselves
+pub fn expand_pattern(shell: &mut Shell, raw: &str) -> Result<Vec<(char, bool)>, String> {
+    let chars: Vec<char> = raw.chars().collect();
+    let (text, _) = expand(shell, &chars, Mode::Word)?;
+    Ok(to_pattern(&text))
+}
+
+// Expands variables in the body of a here-document
+pub fn expand_heredoc(shell: &mut Shell, body: &str) -> Result<String, String> {
+    let chars: Vec<char> = body.chars().collect();
+    let (text, _) = expand(shell, &chars, Mode::HereDoc)?;
+    Ok(to_string(&text))
+}
+
+pub fn to_string(text: &[(char, Origin)]) -> String {
+    text.iter().map(|(c, _)| c).collect()
+}
+
+// Only unquoted characters can be wildcards
+fn to_pattern(text: &[(char, Origin)]) -> Vec<(char, bool)> {
+    text.iter()
+        .map(|(c, origin)| (*c, *origin != Origin::Quoted))
+        .collect()
+}
+
+// Brace expansion, done on the raw word before anything else: a{b,c}d becomes abd and
+// acd, {1..3} becomes 1, 2 and 3. Braces that are quoted or hold neither a comma nor
+// a range stay as they are.
+fn brace_expand(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' => i = closing_single_quote(&chars, i + 1),
+            '"' => i = closing_quote(&chars, i + 1),
+            '$' | '`' => {
+                if let Some(end) = expansion_end(&chars, i) {
+                    i = end;
+                }
+            }
+            '{' => {
+                if let Some((end, items)) = brace_items(&chars, i) {
+                    let prefix: String = chars[..i].iter().collect();
+                    let suffix: String = chars[end + 1..].iter().collect();
+                    return items
+                        .iter()
+                        .flat_map(|item| brace_expand(&format!("{}{}{}", prefix, item, suffix)))
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    vec![word.to_string()]
+}
+
+// The items of the brace expression opening at open and the index of its `}`,
+// None when it is not one
+fn brace_items(chars: &[char], open: usize) -> Option<(usize, Vec<String>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = open + 1;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' => i = closing_single_quote(chars, i + 1),
+            '"' => i = closing_quote(chars, i + 1),
+            '$' | '`' => {
+                if let Some(end) = expansion_end(chars, i) {
+                    i = end;
+                }
+            }
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '}' if commas.is_empty() => {
+                return range_items(&chars[open + 1..i]).map(|items| (i, items));
+            }
+            '}' => {
+                let mut items = Vec::new();
+                let mut start = open + 1;
+                for end in commas.into_iter().chain([i]) {
+                    items.push(chars[start..end].iter().collect());
+                    start = end + 1;
+                }
+                return Some((i, items));
+            }
+            ',' if depth == 0 => commas.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+// {1..10}, {a..e} and with a step {1..10..2}. A leading zero pads the numbers.
+fn range_items(inner: &[char]) -> Option<Vec<String>> {
+    let inner: String = inner.iter().collect();
+    let parts: Vec<&str> = inner.split("..").collect();
+    let (start, end, step) = match parts[..] {
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step.parse::<i64>().ok()?.abs().max(1)),
+        _ => return None,
+    };
+
+    let steps = |from: i64, to: i64| -> Vec<i64> {
+        if from <= to {
+            (from..=to).step_by(step as usize).collect()
+        } else {
+            (to..=from).rev().step_by(step as usize).collect()
+        }
+    };
+
+    if let (Ok(from), Ok(to)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = [start, end].iter().any(|n| {
+            let digits = n.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        });
+        let width = if padded {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+        return Some(
+            steps(from, to)
+                .into_iter()
+                .map(|n| format!("{:0width$}", n))
+                .collect(),
+        );
+    }
+
+    let letter = |text: &str| {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphabetic() => Some(c as i64),
+            _ => None,
+        }
+    };
+    let (from, to) = (letter(start)?, letter(end)?);
+    Some(
+        steps(from, to)
+            .into_iter()
+            .filter_map(|c| char::from_u32(c as u32))
+            .map(String::from)
+            .collect(),
+    )
+}
+
+// Pathname expansion of one field. A pattern that matches nothing is kept as it is,
+// unless nullglob drops it or failglob makes it an error.
+fn glob_field(shell: &Shell, field: &[(char, Origin)]) -> Result<Vec<String>, String> {
+    let pattern = to_pattern(field);
+    if !pattern::has_wildcards(&pattern) {
+        return Ok(vec![to_string(field)]);
+    }
+
+    let matches = pattern::glob(&shell.current_dir, &pattern);
+    if !matches.is_empty() {
+        Ok(matches)
+    } else if shell.failglob {
+        Err(format!("no match: {}", to_string(field)))
+    } else if shell.nullglob {
+        Ok(Vec::new())
+    } else {
+        Ok(vec![to_string(field)])
+    }
+}
+
+// Returns the expanded text and whether the word had quotes, which keeps an empty
+// word like "" as an argument
+fn expand(shell: &mut Shell, chars: &[char], mode: Mode) -> Result<(Text, bool), String> {
+    let plain = if mode == Mode::Word {
+        Origin::Literal
+    } else {
+        Origin::Quoted
+    };
+    let expanded = if mode == Mode::Word {
+        Origin::Expanded
+    } else {
+        Origin::Quoted
+    };
+
+    let mut text = Text::new();
+    let mut quoted = false;
+    let mut i = 0;
+
+    // `~` alone or before a slash at the start of a word is the home directory
+    if mode == Mode::Word
+        && chars.first() == Some(&'~')
+        && matches!(chars.get(1), None | Some('/'))
+        && let Some(home) = home_dir(shell)
+    {
+        text.extend(home.chars().map(|c| (c, Origin::Quoted)));
+        i = 1;
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' => match (mode, chars.get(i + 1).copied()) {
+                (_, None) => text.push(('\\', plain)),
+                (Mode::Word, Some(next)) => {
+                    text.push((next, Origin::Quoted));
+                    i += 1;
+                }
+                // Inside double quotes a backslash only escapes a few characters
+                (_, Some(next @ ('$' | '`' | '\\'))) | (Mode::DoubleQuoted, Some(next @ '"')) => {
+                    text.push((next, Origin::Quoted));
+                    i += 1;
+                }
+                _ => text.push(('\\', Origin::Quoted)),
+            },
+            '\'' if mode == Mode::Word => {
+                let end = closing_single_quote(chars, i + 1);
+                text.extend(chars[i + 1..end].iter().map(|c| (*c, Origin::Quoted)));
+                quoted = true;
+                i = end;
+            }
+            '"' if mode == Mode::Word => {
+                let end = closing_quote(chars, i + 1);
+                let inner = &chars[i + 1..end];
+                let (inner_text, _) = expand(shell, inner, Mode::DoubleQuoted)?;
+                text.extend(inner_text);
+                // "$@" without any parameters is no word at all, unlike ""
+                let all_parameters =
+                    all_parameters_end(inner, 0).is_some_and(|end| end + 1 == inner.len());
+                quoted |= !(all_parameters && shell.positional.is_empty());
+                i = end;
+            }
+            '$' => {
+                if mode == Mode::DoubleQuoted
+                    && let Some(end) = all_parameters_end(chars, i)
+                {
+                    for (n, parameter) in shell.positional.iter().enumerate() {
+                        if n > 0 {
+                            text.push((' ', Origin::Break));
+                        }
+                        text.extend(parameter.chars().map(|c| (c, Origin::Quoted)));
+                    }
+                    i = end;
+                } else {
+                    let (value, end) = expand_dollar(shell, chars, i)?;
+                    match value {
+                        Some(value) => text.extend(value.chars().map(|c| (c, expanded))),
+                        None => text.push(('$', plain)),
+                    }
+                    i = end;
+                }
+            }
+            '`' => {
+                let end = closing_backquote(chars, i + 1).unwrap_or(chars.len());
+                // Inside backquotes a backslash only escapes `$`, `` ` `` and itself
+                let mut command = String::new();
+                let mut j = i + 1;
+                while j < end {
+                    if chars[j] == '\\' && matches!(chars.get(j + 1), Some('$' | '`' | '\\')) {
+                        j += 1;
+                    }
+                    command.push(chars[j]);
+                    j += 1;
+                }
+
+                let output = shell.capture_output(&command)?;
+                text.extend(output.chars().map(|c| (c, expanded)));
+                i = end;
+            }
+            _ => text.push((c, plain)),
+        }
+        i += 1;
+    }
+
+    Ok((text, quoted))
+}
+
+// Expands the `$` at chars[start]. Returns its value, or None when the `$` is just a
+// character, and the index of the last character that was used.
+fn expand_dollar(
+    shell: &mut Shell,
+    chars: &[char],
+    start: usize,
+) -> Result<(Option<String>, usize), String> {
+    match chars.get(start + 1) {
+        Some('{') => {
+            let Some(end) = closing_bracket(chars, start + 1, '}') else {
+                let text: String = chars[start..].iter().collect();
+                return Err(format!("{}: bad substitution", text));
+            };
+            let value = expand_parameter(shell, &chars[start + 2..end])?;
+            Ok((Some(value), end))
+        }
+        Some('(') => {
+            let Some(end) = closing_bracket(chars, start + 1, ')') else {
+                let text: String = chars[start..].iter().collect();
+                return Err(format!("{}: unterminated command substitution", text));
+            };
+            let command: String = chars[start + 2..end].iter().collect();
+            Ok((Some(shell.capture_output(&command)?), end))
+        }
+        Some(&c @ ('?' | '#' | '@' | '*' | '0'..='9')) => {
+            Ok((special_parameter(shell, c), start + 1))
+        }
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let length = chars[start + 1..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count();
+            let name: String = chars[start + 1..start + 1 + length].iter().collect();
+            let value = shell.variables.get(&name).unwrap_or_default().to_string();
+            Ok((Some(value), start + length))
+        }
+        _ => Ok((None, start)),
+    }
+}
+
+// Parameters named by a single character: $? is the status of the last pipeline, $0 the
+// name of the script, $1 to $9 are the positional parameters, $# their count and $@
+// and $* all of them
+fn special_parameter(shell: &Shell, c: char) -> Option<String> {
+    match c {
+        '?' => Some(shell.last_status.to_string()),
+        '#' => Some(shell.positional.len().to_string()),
+        '@' => Some(shell.positional.join(" ")),
+        '*' => {
+            // Joined with the first character of IFS
+            let separator = match shell.variables.get("IFS") {
+                Some(ifs) => ifs.chars().next().map(String::from).unwrap_or_default(),
+                None => " ".to_string(),
+            };
+            Some(shell.positional.join(&separator))
+        }
+        '0'..='9' => Some(positional(shell, &c.to_string()).unwrap_or_default()),
+        _ => None,
+    }
+}
+
+// The positional parameter numbered by digits, as in $1 or ${10}, with $0 the name
+fn positional(shell: &Shell, digits: &str) -> Option<String> {
+    match digits.parse::<usize>().ok()? {
+        0 => Some(shell.arg0.clone()),
+        n => shell.positional.get(n - 1).cloned(),
+    }
+}
+
+// Index of the last character of a "$@" or "${@}" starting at start, which expands to
+// one word per parameter
+fn all_parameters_end(chars: &[char], start: usize) -> Option<usize> {
+    let rest = &chars[start..];
+    if rest.starts_with(&['$', '@']) {
+        Some(start + 1)
+    } else if rest.starts_with(&['$', '{', '@', '}']) {
+        Some(start + 3)
+    } else {
+        None
+    }
+}
+
+// ${NAME} and its forms: ${#NAME}, ${NAME:-word}, ${NAME:=word}, ${NAME:+word},
+// ${NAME:?message}, the same without the colon, and ${NAME%pattern} (%%, #, ##)
+fn expand_parameter(shell: &mut Shell, inner: &[char]) -> Result<String, String> {
+    let bad = || {
+        let text: String = inner.iter().collect();
+        format!("${{{}}}: bad substitution", text)
+    };
+
+    if let Some(('#', name)) = inner.split_first()
+        && !name.is_empty()
+    {
+        let name: String = name.iter().collect();
+        if !variables::is_valid_name(&name) {
+            return Err(bad());
+        }
+        let value = shell.variables.get(&name).unwrap_or_default();
+        return Ok(value.chars().count().to_string());
+    }
+
+    if let [c] = inner
+        && let Some(value) = special_parameter(shell, *c)
+    {
+        return Ok(value);
+    }
+
+    let length = inner
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+        .count();
+    let name: String = inner[..length].iter().collect();
+    let numbered = !name.is_empty() && name.chars().all(|c| c.is_ascii_digit());
+    if !variables::is_valid_name(&name) && !numbered {
+        return Err(bad());
+    }
+
+    let value = if numbered {
+        positional(shell, &name)
+    } else {
+        shell.variables.get(&name).map(str::to_string)
+    };
+    let rest = &inner[length..];
+    if rest.is_empty() {
+        return Ok(value.unwrap_or_default());
+    }
+
+    let colon = rest[0] == ':';
+    let rest = if colon { &rest[1..] } else { rest };
+    let Some((&op, operand)) = rest.split_first() else {
+        return Err(bad());
+    };
+    // With the colon an empty value counts the same as an unset one
+    let unset = match &value {
+        None => true,
+        Some(value) => colon && value.is_empty(),
+    };
+
+    match op {
+        '-' if unset => expand_word_chars(shell, operand),
+        '=' if unset => {
+            let value = expand_word_chars(shell, operand)?;
+            shell.variables.set(&name, value.clone());
+            Ok(value)
+        }
+        '+' if unset => Ok(String::new()),
+        '+' => expand_word_chars(shell, operand),
+        '?' if unset => {
+            let message = expand_word_chars(shell, operand)?;
+            if message.is_empty() {
+                Err(format!("{}: parameter null or not set", name))
+            } else {
+                Err(format!("{}: {}", name, message))
+            }
+        }
+        '-' | '=' | '?' => Ok(value.unwrap_or_default()),
+        '%' | '#' if !colon => {
+            let longest = operand.first() == Some(&op);
+            let operand = if longest { &operand[1..] } else { operand };
+            let (pattern, _) = expand(shell, operand, Mode::Word)?;
+            let pattern = to_pattern(&pattern);
+            Ok(remove_match(
+                &value.unwrap_or_default(),
+                &pattern,
+                op == '#',
+                longest,
+            ))
+        }
+        _ => Err(bad()),
+    }
+}
+
+fn expand_word_chars(shell: &mut Shell, chars: &[char]) -> Result<String, String> {
+    let (text, _) = expand(shell, chars, Mode::Word)?;
+    Ok(to_string(&text))
+}
+
+// Removes the shortest (or longest) prefix or suffix of value matching pattern
+fn remove_match(value: &str, pattern: &[(char, bool)], prefix: bool, longest: bool) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut lengths: Vec<usize> = (0..=chars.len()).collect();
+    if longest {
+        lengths.reverse();
+    }
+
+    for length in lengths {
+        if prefix && pattern::matches(pattern, &chars[..length]) {
+            return chars[length..].iter().collect();
+        }
+        if !prefix && pattern::matches(pattern, &chars[chars.len() - length..]) {
+            return chars[..chars.len() - length].iter().collect();
+        }
+    }
+
+    value.to_string()
+}
+
+// Splits the result of unquoted expansions on IFS characters. Runs of them count
+// as one separator, so an unquoted empty expansion produces no field at all.
+fn split_fields(text: Text, quoted: bool, ifs: &str) -> Vec<Text> {
+    let mut fields = Vec::new();
+    let mut field = Text::new();
+    // After a break of "$@" the field is kept even when it is empty
+    let mut kept = false;
+
+    for (c, origin) in text {
+        if origin == Origin::Break {
+            fields.push(std::mem::take(&mut field));
+            kept = true;
+        } else if origin == Origin::Expanded && ifs.contains(c) {
+            if !field.is_empty() {
+                fields.push(std::mem::take(&mut field));
+            }
+            kept = false;
+        } else {
+            field.push((c, origin));
+        }
+    }
+
+    if !field.is_empty() || kept || (fields.is_empty() && quoted) {
+        fields.push(field);
+    }
+    fields
+}
+
+fn home_dir(shell: &Shell) -> Option<String> {
+    match shell.variables.get("HOME") {
+        Some(home) => Some(home.to_string()),
+        None => dirs::home_dir().map(|home| home.to_string_lossy().into_owned()),
+    }
+}
+
+fn closing_single_quote(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|c| *c == '\'')
+        .map_or(chars.len(), |n| start + n)
+}
+
+// Index of the `"` closing a double-quoted part that starts at start
+fn closing_quote(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '"' => return i,
+            '$' | '`' => {
+                if let Some(end) = expansion_end(chars, i) {
+                    i = end;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+fn closing_backquote(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '`' => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// Index of the last character of the ${...}, $(...) or `...` starting at start
+fn expansion_end(chars: &[char], start: usize) -> Option<usize> {
+    match (chars[start], chars.get(start + 1)) {
+        ('$', Some('{')) => closing_bracket(chars, start + 1, '}'),
+        ('$', Some('(')) => closing_bracket(chars, start + 1, ')'),
+        ('`', _) => closing_backquote(chars, start + 1),
+        _ => None,
+    }
+}
+
+// Index of the bracket matching the `{` or `(` at open, skipping quoted parts and
+// nested expansions
+fn closing_bracket(chars: &[char], open: usize, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' => i = closing_single_quote(chars, i + 1),
+            '"' => i = closing_quote(chars, i + 1),
+            '$' | '`' => {
+                if let Some(end) = expansion_end(chars, i) {
+                    i = end;
+                }
+            }
+            c if c == chars[open] => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(text: &str) -> Vec<(char, bool)> {
+        text.chars().map(|c| (c, true)).collect()
+    }
+
+    fn text(parts: &[(&str, Origin)]) -> Text {
+        parts
+            .iter()
+            .flat_map(|(part, origin)| part.chars().map(|c| (c, *origin)))
+            .collect()
+    }
+
+    #[test]
+    fn removes_prefixes_and_suffixes() {
+        let path = "/a/b/c.tar.gz";
+        assert_eq!(
+            remove_match(path, &pattern("*/"), true, false),
+            "a/b/c.tar.gz"
+        );
+        assert_eq!(remove_match(path, &pattern("*/"), true, true), "c.tar.gz");
+        assert_eq!(
+            remove_match(path, &pattern(".*"), false, false),
+            "/a/b/c.tar"
+        );
+        assert_eq!(remove_match(path, &pattern(".*"), false, true), "/a/b/c");
+        assert_eq!(remove_match(path, &pattern("x*"), true, true), path);
+    }
+
+    #[test]
+    fn splits_only_expanded_text() {
+        let fields = split_fields(
+            text(&[("a b", Origin::Literal), (" c  d ", Origin::Expanded)]),
+            false,
+            " \t\n",
+        );
+        assert_eq!(
+            fields
+                .iter()
+                .map(|field| to_string(field))
+                .collect::<Vec<_>>(),
+            ["a b", "c", "d"]
+        );
+        // An empty unquoted expansion is no field at all, a quoted one is an empty field
+        assert!(split_fields(Text::new(), false, " ").is_empty());
+        assert_eq!(split_fields(Text::new(), true, " ").len(), 1);
+    }
+
+    #[test]
+    fn expands_lists() {
+        assert_eq!(brace_expand("a{b,c}d"), ["abd", "acd"]);
+        assert_eq!(brace_expand("{a,b}{1,2}"), ["a1", "a2", "b1", "b2"]);
+        assert_eq!(brace_expand("x{a,{b,c}}"), ["xa", "xb", "xc"]);
+        assert_eq!(brace_expand("{,un}do"), ["do", "undo"]);
+    }
+
+    #[test]
+    fn expands_ranges() {
+        assert_eq!(brace_expand("{1..3}"), ["1", "2", "3"]);
+        assert_eq!(brace_expand("{3..1}"), ["3", "2", "1"]);
+        assert_eq!(brace_expand("{-1..1}"), ["-1", "0", "1"]);
+        assert_eq!(brace_expand("{01..03}"), ["01", "02", "03"]);
+        assert_eq!(brace_expand("{8..010}"), ["008", "009", "010"]);
+        assert_eq!(brace_expand("{1..10..3}"), ["1", "4", "7", "10"]);
+        assert_eq!(brace_expand("{10..1..-4}"), ["10", "6", "2"]);
+        assert_eq!(brace_expand("{a..c}"), ["a", "b", "c"]);
+        assert_eq!(brace_expand("{e..a..2}"), ["e", "c", "a"]);
+        assert_eq!(brace_expand("f{1..2}.txt"), ["f1.txt", "f2.txt"]);
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        for word in [
+            "{a}",
+            "{}",
+            "{a..}",
+            "{1..b}",
+            "{aa..c}",
+            "{a,b",
+            "'{a,b}'",
+            "\"{a,b}\"",
+            "\\{a,b}",
+            "${x:-a,b}",
+            "$(echo {a,b})",
+            "`echo {a,b}`",
+        ] {
+            assert_eq!(brace_expand(word), [word], "{:?}", word);
+        }
+        // A quoted comma does not separate items, but stays in the item
+        assert_eq!(brace_expand("{'a,b',c}"), ["'a,b'", "c"]);
+    }
+
+    #[test]
+    fn command_substitutions_set_the_status() {
+        let mut shell = Shell::new().unwrap();
+        assert_eq!(expand_word(&mut shell, "$(exit 4) $?").unwrap(), " 4");
+        assert_eq!(shell.last_status, 4);
+
+        // An assignment alone gives the status of its last substitution
+        shell.run_script("test", "x=$(exit 3)");
+        assert_eq!(shell.last_status, 3);
+        shell.run_script("test", "x=y");
+        assert_eq!(shell.last_status, 0);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+// What the prompt shows about the repository the shell is in
+pub struct Status {
+    // Branch name, or the abbreviated commit when HEAD is detached
+    pub branch: String,
+    // Some tracked file differs from the index, found without running git
+    pub dirty: bool,
+}
+
+// Looks for a repository at dir or above it, reading .git directly
+pub fn status(dir: &Path) -> Option<Status> {
+    let (work_tree, git_dir) = find_repository(dir)?;
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let branch = match head.strip_prefix("ref: ") {
+        Some(reference) => reference
+            .strip_prefix("refs/heads/")
+            .unwrap_or(reference)
+            .to_string(),
+        None => head.chars().take(7).collect(),
+    };
+
+    let dirty = fs::read(git_dir.join("index")).is_ok_and(|index| is_dirty(&work_tree, &index));
+    Some(Status { branch, dirty })
+}
+
+// The work tree and its git directory. A .git file instead of a directory (worktrees,
+// submodules) points to the real one with `gitdir: path`.
+fn find_repository(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for work_tree in dir.ancestors() {
+        let dot_git = work_tree.join(".git");
+        if dot_git.is_dir() {
+            return Some((work_tree.to_path_buf(), dot_git));
+        }
+        if let Ok(content) = fs::read_to_string(&dot_git) {
+            let git_dir = content.trim().strip_prefix("gitdir: ")?;
+            return Some((work_tree.to_path_buf(), work_tree.join(git_dir)));
+        }
+    }
+    None
+}
+
+// Compares every entry of the index (format versions 2 to 4) with the file in the work
+// tree by size and modification time, the same quick check git starts with. A file
+// that was touched without changing counts as modified.
+fn is_dirty(work_tree: &Path, index: &[u8]) -> bool {
+    let read_u32 = |at: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(index.get(at..at + 4)?.try_into().ok()?))
+    };
+    if index.get(..4) != Some(b"DIRC") {
+        return false;
+    }
+    let (Some(version), Some(count)) = (read_u32(4), read_u32(8)) else {
+        return false;
+    };
+
+    let mut at = 12;
+    let mut path = Vec::new();
+    for _ in 0..count {
+        let start = at;
+        let (Some(mtime), Some(mode), Some(size)) =
+            (read_u32(at + 8), read_u32(at + 24), read_u32(at + 36))
+        else {
+            return false;
+        };
+        let Some(flags) = index.get(at + 60..at + 62) else {
+            return false;
+        };
+        let flags = u16::from_be_bytes([flags[0], flags[1]]);
+        at += 62;
+
+        // Version 3 adds a second flags word to some entries, skip-worktree among them
+        let mut skip_worktree = false;
+        if version >= 3 && flags & 0x4000 != 0 {
+            let Some(extended) = index.get(at..at + 2) else {
+                return false;
+            };
+            skip_worktree = extended[0] & 0x40 != 0;
+            at += 2;
+        }
+
+        // Version 4 only stores how much of the previous path to drop and what follows
+        if version >= 4 {
+            let mut strip = 0usize;
+            loop {
+                let Some(&byte) = index.get(at) else {
+                    return false;
+                };
+                at += 1;
+                strip = (strip << 7) | usize::from(byte & 0x7f);
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                strip += 1;
+            }
+            path.truncate(path.len().saturating_sub(strip));
+        } else {
+            path.clear();
+        }
+        let Some(length) = index
+            .get(at..)
+            .and_then(|rest| rest.iter().position(|b| *b == 0))
+        else {
+            return false;
+        };
+        path.extend_from_slice(&index[at..at + length]);
+        at += length + 1;
+        if version < 4 {
+            // Entries are padded with NULs to a multiple of 8 bytes
+            at = start + (at - start).div_ceil(8) * 8;
+        }
+
+        // Submodules (gitlinks) are not files
+        if skip_worktree || mode & 0o170000 == 0o160000 {
+            continue;
+        }
+        let Ok(name) = std::str::from_utf8(&path) else {
+            continue;
+        };
+        let Ok(metadata) = fs::symlink_metadata(work_tree.join(name)) else {
+            return true;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_secs() as u32);
+        if metadata.len() as u32 != size || modified != Some(mtime) {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh work tree under the temporary directory, removed when dropped
+    struct WorkTree(PathBuf);
+
+    impl WorkTree {
+        fn new(name: &str) -> WorkTree {
+            let dir = std::env::temp_dir().join(format!(
+                "mini-shell-git-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("dir")).unwrap();
+            WorkTree(dir)
+        }
+
+        fn write(&self, name: &str, content: &str) {
+            fs::write(self.0.join(name), content).unwrap();
+        }
+    }
+
+    impl Drop for WorkTree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // The fixed part of an entry, with the size and mtime the file has now
+    fn entry_stat(work_tree: &Path, name: &str, mode: u32) -> Vec<u8> {
+        let metadata = fs::metadata(work_tree.join(name)).unwrap();
+        let mtime = metadata
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let mut entry = vec![0; 62];
+        entry[8..12].copy_from_slice(&mtime.to_be_bytes());
+        entry[24..28].copy_from_slice(&mode.to_be_bytes());
+        entry[36..40].copy_from_slice(&(metadata.len() as u32).to_be_bytes());
+        entry[60..62].copy_from_slice(&(name.len() as u16).to_be_bytes());
+        entry
+    }
+
+    fn header(version: u32, count: u32) -> Vec<u8> {
+        let mut index = b"DIRC".to_vec();
+        index.extend_from_slice(&version.to_be_bytes());
+        index.extend_from_slice(&count.to_be_bytes());
+        index
+    }
+
+    fn index_v2(work_tree: &Path, names: &[&str]) -> Vec<u8> {
+        let mut index = header(2, names.len() as u32);
+        for name in names {
+            let start = index.len();
+            index.extend(entry_stat(work_tree, name, 0o100644));
+            index.extend_from_slice(name.as_bytes());
+            index.push(0);
+            while !(index.len() - start).is_multiple_of(8) {
+                index.push(0);
+            }
+        }
+        index
+    }
+
+    // Version 4 paths are stored as the number of bytes to drop from the previous path
+    // and the text that follows
+    fn index_v4(work_tree: &Path, names: &[&str]) -> Vec<u8> {
+        let mut index = header(4, names.len() as u32);
+        let mut previous = "";
+        for name in names {
+            let common = previous
+                .bytes()
+                .zip(name.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            index.extend(entry_stat(work_tree, name, 0o100644));
+            index.push((previous.len() - common) as u8);
+            index.extend_from_slice(&name.as_bytes()[common..]);
+            index.push(0);
+            previous = name;
+        }
+        index
+    }
+
+    #[test]
+    fn reads_version_2_indexes() {
+        let tree = WorkTree::new("v2");
+        tree.write("a.txt", "a");
+        tree.write("dir/long-file-name.txt", "b");
+        let index = index_v2(&tree.0, &["a.txt", "dir/long-file-name.txt"]);
+        assert!(!is_dirty(&tree.0, &index));
+
+        tree.write("dir/long-file-name.txt", "changed");
+        assert!(is_dirty(&tree.0, &index));
+
+        let index = index_v2(&tree.0, &["a.txt", "dir/long-file-name.txt"]);
+        fs::remove_file(tree.0.join("a.txt")).unwrap();
+        assert!(is_dirty(&tree.0, &index));
+    }
+
+    #[test]
+    fn reads_version_4_indexes() {
+        let tree = WorkTree::new("v4");
+        tree.write("dir/a.txt", "a");
+        tree.write("dir/b.txt", "b");
+        tree.write("z", "z");
+        let names = ["dir/a.txt", "dir/b.txt", "z"];
+        let index = index_v4(&tree.0, &names);
+        assert!(!is_dirty(&tree.0, &index));
+
+        // Only found through the shared prefix of the path before it
+        tree.write("dir/b.txt", "bb");
+        assert!(is_dirty(&tree.0, &index));
+    }
+
+    #[test]
+    fn skips_submodules_and_skip_worktree_entries() {
+        let tree = WorkTree::new("skip");
+        tree.write("a", "a");
+        let mut index = index_v2(&tree.0, &["a"]);
+        // A gitlink whose directory is missing
+        let mut entry = entry_stat(&tree.0, "a", 0o160000);
+        entry[60..62].copy_from_slice(&3u16.to_be_bytes());
+        index.extend(entry);
+        index.extend_from_slice(b"sub\0\0\0\0\0\0\0");
+        // A skip-worktree entry, with the extended flags of version 3
+        let mut entry = entry_stat(&tree.0, "a", 0o100644);
+        entry[60..62].copy_from_slice(&(0x4000u16 | 4).to_be_bytes());
+        index.extend(entry);
+        index.extend_from_slice(&[0x40, 0]);
+        index.extend_from_slice(b"gone\0\0\0\0");
+        index[4..8].copy_from_slice(&3u32.to_be_bytes());
+        index[8..12].copy_from_slice(&3u32.to_be_bytes());
+        assert!(!is_dirty(&tree.0, &index));
+    }
+
+    #[test]
+    fn ignores_broken_indexes() {
+        let tree = WorkTree::new("broken");
+        tree.write("a", "a");
+        let index = index_v2(&tree.0, &["a"]);
+        assert!(!is_dirty(&tree.0, b""));
+        assert!(!is_dirty(&tree.0, &index[..20]));
+        let mut wrong = index.clone();
+        wrong[..4].copy_from_slice(b"XXXX");
+        fs::remove_file(tree.0.join("a")).unwrap();
+        assert!(!is_dirty(&tree.0, &wrong));
+        assert!(is_dirty(&tree.0, &index));
+    }
+}
@@ -0,0 +1,759 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::{builtins, signals, stdio::Io};
+
+struct Options {
+    invert: bool,
+    line_numbers: bool,
+    count: bool,
+    files_with_matches: bool,
+    files_without_match: bool,
+    // None shows file names when searching several files
+    filenames: Option<bool>,
+    only_matching: bool,
+    quiet: bool,
+    no_messages: bool,
+    max_count: Option<u64>,
+    // -a searches binary files like text, -I skips them
+    text: bool,
+    skip_binary: bool,
+    color: bool,
+    recursive: bool,
+    // -R also follows symlinks inside directories
+    dereference: bool,
+}
+
+// How patterns are read: -G (the default) takes basic regular expressions, where `\+`,
+// `\?`, `\|`, `\(` and `\{` are the operators, -E extended ones and -F plain text
+#[derive(Clone, Copy, PartialEq)]
+enum Syntax {
+    Basic,
+    Extended,
+    Fixed,
+}
+
+// Colors of GNU grep: matches bold red, file names magenta, line numbers green and
+// separators cyan
+const MATCH_COLOR: &str = "\x1b[01;31m\x1b[K";
+const NAME_COLOR: &str = "\x1b[35m\x1b[K";
+const NUMBER_COLOR: &str = "\x1b[32m\x1b[K";
+const SEPARATOR_COLOR: &str = "\x1b[36m\x1b[K";
+const RESET: &str = "\x1b[m\x1b[K";
+
+const USAGE: &str =
+    "Usage: grep [-ivnrRclLhHoqswxEFaI] [-m NUM] [-e PATTERN]... [PATTERN] [FILE]...";
+
+// grep [OPTION]... PATTERN [FILE]... prints the lines that match a regular expression,
+// from the files, from stdin without files or for `-`, or from every file below the
+// given directories (the current one without files) with -r. Patterns are basic regular
+// expressions unless -E or -F is given. The status is 0 when a line
+// was selected, 1 when none was and 2 for errors.
+pub fn grep(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut options = Options {
+        invert: false,
+        line_numbers: false,
+        count: false,
+        files_with_matches: false,
+        files_without_match: false,
+        filenames: None,
+        only_matching: false,
+        quiet: false,
+        no_messages: false,
+        max_count: None,
+        text: false,
+        skip_binary: false,
+        color: false,
+        recursive: false,
+        dereference: false,
+    };
+    let mut ignore_case = false;
+    let mut syntax = Syntax::Basic;
+    let mut words = false;
+    let mut whole_lines = false;
+    let mut color = "auto";
+    let mut patterns = Vec::new();
+    let mut operands = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (option, value) = match *arg {
+            "--" => {
+                operands.extend(args.by_ref().copied());
+                break;
+            }
+            "--ignore-case" => {
+                ignore_case = true;
+                continue;
+            }
+            "--invert-match" => {
+                options.invert = true;
+                continue;
+            }
+            "--line-number" => {
+                options.line_numbers = true;
+                continue;
+            }
+            "--recursive" => {
+                options.recursive = true;
+                continue;
+            }
+            "--dereference-recursive" => {
+                options.recursive = true;
+                options.dereference = true;
+                continue;
+            }
+            "--count" => {
+                options.count = true;
+                continue;
+            }
+            "--files-with-matches" => {
+                options.files_with_matches = true;
+                continue;
+            }
+            "--files-without-match" => {
+                options.files_without_match = true;
+                continue;
+            }
+            "--no-filename" => {
+                options.filenames = Some(false);
+                continue;
+            }
+            "--with-filename" => {
+                options.filenames = Some(true);
+                continue;
+            }
+            "--only-matching" => {
+                options.only_matching = true;
+                continue;
+            }
+            "--quiet" | "--silent" => {
+                options.quiet = true;
+                continue;
+            }
+            "--no-messages" => {
+                options.no_messages = true;
+                continue;
+            }
+            "--word-regexp" => {
+                words = true;
+                continue;
+            }
+            "--line-regexp" => {
+                whole_lines = true;
+                continue;
+            }
+            "--fixed-strings" => {
+                syntax = Syntax::Fixed;
+                continue;
+            }
+            "--extended-regexp" => {
+                syntax = Syntax::Extended;
+                continue;
+            }
+            "--basic-regexp" => {
+                syntax = Syntax::Basic;
+                continue;
+            }
+            "--text" => {
+                options.text = true;
+                continue;
+            }
+            "--color" | "--colour" => {
+                color = "always";
+                continue;
+            }
+            _ if arg.starts_with("--color=") || arg.starts_with("--colour=") => {
+                color = &arg["--color=".len()..];
+                continue;
+            }
+            _ if arg.starts_with("--regexp=") => ('e', Some(&arg["--regexp=".len()..])),
+            _ if arg.starts_with("--max-count=") => ('m', Some(&arg["--max-count=".len()..])),
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "grep: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                let mut option = None;
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'i' | 'y' => ignore_case = true,
+                        'v' => options.invert = true,
+                        'n' => options.line_numbers = true,
+                        'r' => options.recursive = true,
+                        'R' => {
+                            options.recursive = true;
+                            options.dereference = true;
+                        }
+                        'c' => options.count = true,
+                        'l' => options.files_with_matches = true,
+                        'L' => options.files_without_match = true,
+                        'h' => options.filenames = Some(false),
+                        'H' => options.filenames = Some(true),
+                        'o' => options.only_matching = true,
+                        'q' => options.quiet = true,
+                        's' => options.no_messages = true,
+                        'w' => words = true,
+                        'x' => whole_lines = true,
+                        'F' => syntax = Syntax::Fixed,
+                        'E' => syntax = Syntax::Extended,
+                        'G' => syntax = Syntax::Basic,
+                        'a' => options.text = true,
+                        'I' => options.skip_binary = true,
+                        'e' | 'm' => {
+                            let rest = &arg[2 + i..];
+                            option = Some((flag, Some(rest).filter(|rest| !rest.is_empty())));
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "grep: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                match option {
+                    Some(option) => option,
+                    None => continue,
+                }
+            }
+            _ => {
+                operands.push(*arg);
+                continue;
+            }
+        };
+
+        let Some(value) = value.or_else(|| args.next().copied()) else {
+            writeln!(
+                io.stderr,
+                "grep: option requires an argument -- '{}'",
+                option
+            )?;
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        };
+        if option == 'e' {
+            patterns.push(value.to_string());
+            continue;
+        }
+        match value.parse::<u64>() {
+            Ok(max) => options.max_count = Some(max),
+            Err(_) => {
+                writeln!(io.stderr, "grep: invalid max count '{}'", value)?;
+                return Ok(2);
+            }
+        }
+    }
+
+    // Without -e the first operand is the pattern
+    if patterns.is_empty() {
+        if operands.is_empty() {
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        }
+        patterns.push(operands.remove(0).to_string());
+    }
+
+    options.color = match color {
+        "always" | "yes" | "force" => true,
+        "never" | "no" | "none" => false,
+        "auto" | "tty" | "if-tty" => {
+            io.stdout.is_terminal() && colored::control::SHOULD_COLORIZE.should_colorize()
+        }
+        _ => {
+            writeln!(
+                io.stderr,
+                "grep: invalid argument '{}' for '--color'",
+                color
+            )?;
+            return Ok(2);
+        }
+    };
+
+    // Several patterns (-e, or lines of one pattern) match when any of them does
+    let alternatives: Vec<String> = patterns
+        .iter()
+        .flat_map(|pattern| pattern.split('\n'))
+        .map(|pattern| match syntax {
+            Syntax::Basic => basic_to_extended(pattern),
+            Syntax::Extended => pattern.to_string(),
+            Syntax::Fixed => regex::escape(pattern),
+        })
+        .map(|pattern| format!("(?:{})", pattern))
+        .collect();
+    let mut expression = alternatives.join("|");
+    if whole_lines {
+        expression = format!("^(?:{})$", expression);
+    } else if words {
+        expression = format!(r"\b(?:{})\b", expression);
+    }
+    let regex = match RegexBuilder::new(&expression)
+        .case_insensitive(ignore_case)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(e) => {
+            // Parse errors draw the pattern over several lines, the last one says what is wrong
+            let message = e.to_string();
+            let reason = message.lines().last().unwrap_or_default();
+            writeln!(io.stderr, "grep: {}", reason.trim_start_matches("error: "))?;
+            return Ok(2);
+        }
+    };
+
+    let mut search = Search {
+        regex,
+        options,
+        selected: false,
+        failed: false,
+        done: false,
+    };
+
+    let implicit_directory = operands.is_empty() && search.options.recursive;
+    if operands.is_empty() && !search.options.recursive {
+        operands.push("-");
+    }
+    let show_names = search
+        .options
+        .filenames
+        .unwrap_or(operands.len() > 1 || search.options.recursive);
+
+    let result = if implicit_directory {
+        search.search_directory(current_dir, "", show_names, io)
+    } else {
+        operands.iter().try_for_each(|name| {
+            if search.done {
+                Ok(())
+            } else if *name == "-" {
+                let stdin = io.stdin.try_clone()?;
+                search.search_input(stdin, "(standard input)", show_names, io)
+            } else {
+                match builtins::resolve_path(current_dir, name) {
+                    Some(path) => search.search_path(&path, name, true, show_names, io),
+                    None => search.report(
+                        name,
+                        &io::Error::other("Could not determine home directory"),
+                        io,
+                    ),
+                }
+            }
+        })
+    };
+    match result {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(128 + signals::SIGINT),
+        Err(e) => return Err(e),
+    }
+
+    Ok(match (search.selected, search.failed) {
+        (true, _) if search.options.quiet => 0,
+        (_, true) => 2,
+        (true, false) => 0,
+        (false, false) => 1,
+    })
+}
+
+struct Search {
+    regex: Regex,
+    options: Options,
+    // Some line was selected somewhere
+    selected: bool,
+    // Some file could not be read
+    failed: bool,
+    // -q stops at the first selected line
+    done: bool,
+}
+
+impl Search {
+    // Searches a file, or with -r every file below a directory. Symlinks inside directories
+    // are followed only with -R.
+    fn search_path(
+        &mut self,
+        path: &Path,
+        name: &str,
+        operand: bool,
+        show_names: bool,
+        io: &mut Io,
+    ) -> io::Result<()> {
+        if signals::interrupt_pending() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let metadata = match operand || self.options.dereference {
+            true => fs::metadata(path),
+            false => fs::symlink_metadata(path),
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => return self.report(name, &e, io),
+        };
+
+        if metadata.is_dir() {
+            if !self.options.recursive {
+                if !self.options.no_messages {
+                    writeln!(io.stderr, "grep: {}: Is a directory", name)?;
+                }
+                return Ok(());
+            }
+            return self.search_directory(path, name, show_names, io);
+        }
+        if !metadata.is_file() && !operand {
+            // Devices, sockets and symlinks met on the way
+            return Ok(());
+        }
+        match fs::File::open(path) {
+            Ok(file) => self.search_input(file, name, show_names, io),
+            Err(e) => self.report(name, &e, io),
+        }
+    }
+
+    // name is how the directory is shown, empty for the current one without operands
+    fn search_directory(
+        &mut self,
+        path: &Path,
+        name: &str,
+        show_names: bool,
+        io: &mut Io,
+    ) -> io::Result<()> {
+        let mut entries: Vec<_> = match fs::read_dir(path) {
+            Ok(entries) => entries.flatten().map(|entry| entry.file_name()).collect(),
+            Err(e) => return self.report(name, &e, io),
+        };
+        entries.sort();
+        for entry in entries {
+            if self.done {
+                break;
+            }
+            let entry_name = entry.to_string_lossy();
+            let shown = match name {
+                "" => entry_name.into_owned(),
+                _ => format!("{}/{}", name.trim_end_matches('/'), entry_name),
+            };
+            self.search_path(&path.join(&entry), &shown, false, show_names, io)?;
+        }
+        Ok(())
+    }
+
+    fn search_input(
+        &mut self,
+        input: impl Read,
+        name: &str,
+        show_names: bool,
+        io: &mut Io,
+    ) -> io::Result<()> {
+        let mut reader = BufReader::new(input);
+        // Like GNU grep, a NUL byte in the first block means binary
+        let binary = !self.options.text
+            && match reader.fill_buf() {
+                Ok(start) => start.contains(&0),
+                Err(e) => return self.report(name, &e, io),
+            };
+        if binary && self.options.skip_binary {
+            return Ok(());
+        }
+        // Summaries print no lines, so a binary file is searched like any other
+        let summary = self.options.count
+            || self.options.files_with_matches
+            || self.options.files_without_match
+            || self.options.quiet;
+
+        let mut count = 0u64;
+        let mut number = 0u64;
+        let mut line = Vec::new();
+        loop {
+            if self.options.max_count.is_some_and(|max| count >= max) {
+                break;
+            }
+            line.clear();
+            match builtins::read_line(&mut reader, &mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+                Err(e) => {
+                    self.report(name, &e, io)?;
+                    break;
+                }
+            }
+            number += 1;
+            let text = line.strip_suffix(b"\n").unwrap_or(&line);
+            if self.regex.is_match(text) == self.options.invert {
+                continue;
+            }
+
+            count += 1;
+            self.selected = true;
+            if self.options.quiet {
+                self.done = true;
+                return Ok(());
+            }
+            if summary {
+                continue;
+            }
+            if binary {
+                writeln!(io.stdout, "grep: {}: binary file matches", name)?;
+                break;
+            }
+            self.print_line(text, name, number, show_names, io)?;
+        }
+
+        if self.options.count {
+            if show_names {
+                self.print_prefix(name, None, io)?;
+            }
+            writeln!(io.stdout, "{}", count)?;
+        }
+        let listed = match (
+            self.options.files_with_matches,
+            self.options.files_without_match,
+        ) {
+            (true, _) => count > 0,
+            (_, true) => count == 0,
+            _ => false,
+        };
+        if listed {
+            match self.options.color {
+                true => writeln!(io.stdout, "{}{}{}", NAME_COLOR, name, RESET)?,
+                false => writeln!(io.stdout, "{}", name)?,
+            }
+        }
+        Ok(())
+    }
+
+    // A selected line, or with -o each match in it on its own line
+    fn print_line(
+        &self,
+        text: &[u8],
+        name: &str,
+        number: u64,
+        show_names: bool,
+        io: &mut Io,
+    ) -> io::Result<()> {
+        let prefix = |search: &Self, io: &mut Io| match show_names {
+            true => search.print_prefix(name, search.options.line_numbers.then_some(number), io),
+            false => search.print_prefix("", search.options.line_numbers.then_some(number), io),
+        };
+
+        if self.options.only_matching {
+            // Inverted lines have no matches to show
+            if self.options.invert {
+                return Ok(());
+            }
+            for found in self.regex.find_iter(text).filter(|found| !found.is_empty()) {
+                prefix(self, io)?;
+                self.write_match(found.as_bytes(), io)?;
+                io.stdout.write_all(b"\n")?;
+            }
+            return Ok(());
+        }
+
+        prefix(self, io)?;
+        if self.options.color && !self.options.invert {
+            let mut at = 0;
+            for found in self.regex.find_iter(text).filter(|found| !found.is_empty()) {
+                io.stdout.write_all(&text[at..found.start()])?;
+                self.write_match(found.as_bytes(), io)?;
+                at = found.end();
+            }
+            io.stdout.write_all(&text[at..])?;
+        } else {
+            io.stdout.write_all(text)?;
+        }
+        io.stdout.write_all(b"\n")
+    }
+
+    // `name:` and `number:` in front of a line, each only when given
+    fn print_prefix(&self, name: &str, number: Option<u64>, io: &mut Io) -> io::Result<()> {
+        let separator = match self.options.color {
+            true => format!("{}:{}", SEPARATOR_COLOR, RESET),
+            false => ":".to_string(),
+        };
+        if !name.is_empty() {
+            match self.options.color {
+                true => write!(io.stdout, "{}{}{}{}", NAME_COLOR, name, RESET, separator)?,
+                false => write!(io.stdout, "{}{}", name, separator)?,
+            }
+        }
+        if let Some(number) = number {
+            match self.options.color {
+                true => write!(
+                    io.stdout,
+                    "{}{}{}{}",
+                    NUMBER_COLOR, number, RESET, separator
+                )?,
+                false => write!(io.stdout, "{}{}", number, separator)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_match(&self, text: &[u8], io: &mut Io) -> io::Result<()> {
+        if self.options.color {
+            io.stdout.write_all(MATCH_COLOR.as_bytes())?;
+            io.stdout.write_all(text)?;
+            io.stdout.write_all(RESET.as_bytes())
+        } else {
+            io.stdout.write_all(text)
+        }
+    }
+
+    // A file that could not be read makes the status 2, -s keeps it quiet
+    fn report(&mut self, name: &str, error: &io::Error, io: &mut Io) -> io::Result<()> {
+        self.failed = true;
+        if !self.options.no_messages {
+            writeln!(io.stderr, "grep: {}: {}", name, error)?;
+        }
+        Ok(())
+    }
+}
+
+// Rewrites a basic regular expression in the syntax of the regex crate: the escaped
+// operators lose their backslash, the plain characters gain one, and bracket expressions
+// keep their POSIX meaning, where a backslash is just a backslash.
+fn basic_to_extended(pattern: &str) -> String {
+    let mut expression = String::new();
+    let mut chars = pattern.chars().peekable();
+    // A `*` with nothing before it to repeat is a plain star
+    let mut at_start = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(operator @ ('+' | '?' | '|' | '(' | ')' | '{' | '}')) => {
+                    expression.push(operator);
+                    at_start = matches!(operator, '(' | '|');
+                    continue;
+                }
+                Some(other) => {
+                    expression.push('\\');
+                    expression.push(other);
+                }
+                None => expression.push_str("\\\\"),
+            },
+            '+' | '?' | '|' | '(' | ')' | '{' | '}' => {
+                expression.push('\\');
+                expression.push(c);
+            }
+            '*' if at_start => expression.push_str("\\*"),
+            '^' if at_start => {
+                expression.push(c);
+                continue;
+            }
+            '[' => {
+                expression.push('[');
+                if chars.next_if_eq(&'^').is_some() {
+                    expression.push('^');
+                }
+                // A `]` right at the start belongs to the set
+                if chars.next_if_eq(&']').is_some() {
+                    expression.push_str("\\]");
+                }
+                while let Some(c) = chars.next() {
+                    match c {
+                        ']' => break,
+                        // Classes such as [:alpha:] are copied whole
+                        '[' if chars.peek() == Some(&':') => {
+                            expression.push('[');
+                            for c in chars.by_ref() {
+                                expression.push(c);
+                                if c == ']' {
+                                    break;
+                                }
+                            }
+                            continue;
+                        }
+                        '\\' | '[' | '&' | '~' => expression.push('\\'),
+                        _ => {}
+                    }
+                    expression.push(c);
+                }
+                expression.push(']');
+            }
+            _ => expression.push(c),
+        }
+        at_start = false;
+    }
+    expression
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn grep_in(dir: &Path, input: &str, args: &[&str]) -> testing::Run {
+        testing::run(input, |io| grep(dir, args, io))
+    }
+
+    #[test]
+    fn rewrites_basic_expressions() {
+        assert_eq!(basic_to_extended(r"a\(b\)\{2\}c+"), r"a(b){2}c\+");
+        assert_eq!(basic_to_extended(r"a\|b?"), r"a|b\?");
+        assert_eq!(basic_to_extended("*a*"), r"\*a*");
+        assert_eq!(basic_to_extended(r"^*\(*x\)"), r"^\*(\*x)");
+        assert_eq!(basic_to_extended(r"[]\a[:digit:]]"), r"[\]\\a[:digit:]]");
+        assert_eq!(basic_to_extended(r"[^&~]"), r"[^\&\~]");
+        assert_eq!(basic_to_extended(r"a\"), r"a\\");
+    }
+
+    #[test]
+    fn selects_lines() {
+        let input = "apple\nBanana\ncherry\nbanana split\n";
+        let dir = Path::new(".");
+        let run = grep_in(dir, input, &["an"]);
+        assert_eq!(
+            (run.status, run.stdout.as_str()),
+            (0, "Banana\nbanana split\n")
+        );
+        assert_eq!(
+            grep_in(dir, input, &["-i", "^b"]).stdout,
+            "Banana\nbanana split\n"
+        );
+        assert_eq!(grep_in(dir, input, &["-v", "an"]).stdout, "apple\ncherry\n");
+        assert_eq!(grep_in(dir, input, &["-n", "rr"]).stdout, "3:cherry\n");
+        assert_eq!(grep_in(dir, input, &["-ci", "banana"]).stdout, "2\n");
+        assert_eq!(
+            grep_in(dir, input, &["-o", "an"]).stdout,
+            "an\nan\nan\nan\n"
+        );
+        assert_eq!(
+            grep_in(dir, input, &["-E", "p+le|^c"]).stdout,
+            "apple\ncherry\n"
+        );
+        assert_eq!(grep_in(dir, "a.b\naxb\n", &["-F", "a.b"]).stdout, "a.b\n");
+
+        let run = grep_in(dir, input, &["-q", "zebra"]);
+        assert_eq!((run.status, run.stdout.as_str()), (1, ""));
+    }
+
+    #[test]
+    fn names_files_when_there_are_several() {
+        let dir = TempDir::new("grep-files");
+        dir.write("a", "x\n");
+        dir.write("d/b", "y\nx\n");
+        let run = grep_in(dir.path(), "", &["x", "a", "d/b"]);
+        assert_eq!(run.stdout, "a:x\nd/b:x\n");
+        assert_eq!(
+            grep_in(dir.path(), "", &["-l", "y", "a", "d/b"]).stdout,
+            "d/b\n"
+        );
+        assert_eq!(grep_in(dir.path(), "", &["-r", "y", "d"]).stdout, "d/b:y\n");
+
+        let run = grep_in(dir.path(), "", &["x", "missing"]);
+        assert_eq!(run.status, 2);
+        assert!(run.stderr.starts_with("grep: missing: "));
+        let run = grep_in(dir.path(), "", &["-s", "x", "missing"]);
+        assert_eq!((run.status, run.stderr.as_str()), (2, ""));
+        let run = grep_in(dir.path(), "", &["-E", "a("]);
+        assert_eq!(run.status, 2);
+        let run = grep_in(dir.path(), "", &["-z", "x"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("grep: invalid option -- 'z'\n{}\n", USAGE)
+        );
+    }
+}
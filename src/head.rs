@@ -0,0 +1,226 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use crate::{builtins, stdio::Io};
+
+// How much of each input head prints
+#[derive(Clone, Copy)]
+enum Count {
+    Lines(u64),
+    Bytes(u64),
+    // -n -N and -c -N: everything except the last N
+    AllButLines(u64),
+    AllButBytes(u64),
+}
+
+const USAGE: &str = "Usage: head [-n LINES | -c BYTES] [-qv] [FILE]...";
+
+// head [OPTION]... [FILE]... prints the first 10 lines of each file, or of stdin without
+// files or for `-`. Several files get a `==> name <==` header each.
+pub fn head(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut count = Count::Lines(10);
+    // None shows headers only for several files
+    let mut headers = None;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (unit, value) = match *arg {
+            "--" => {
+                files.extend(args.by_ref().copied());
+                break;
+            }
+            "--quiet" | "--silent" => {
+                headers = Some(false);
+                continue;
+            }
+            "--verbose" => {
+                headers = Some(true);
+                continue;
+            }
+            _ if arg.starts_with("--lines=") => ('n', Some(&arg["--lines=".len()..])),
+            _ if arg.starts_with("--bytes=") => ('c', Some(&arg["--bytes=".len()..])),
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "head: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            // The old form: head -5
+            _ if arg.len() > 1 && arg[1..].bytes().all(|b| b.is_ascii_digit()) => {
+                ('n', Some(&arg[1..]))
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                let mut option = None;
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'q' => headers = Some(false),
+                        'v' => headers = Some(true),
+                        'n' | 'c' => {
+                            let rest = &arg[2 + i..];
+                            option = Some((flag, Some(rest).filter(|rest| !rest.is_empty())));
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "head: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                match option {
+                    Some(option) => option,
+                    None => continue,
+                }
+            }
+            _ => {
+                files.push(*arg);
+                continue;
+            }
+        };
+
+        let Some(value) = value.or_else(|| args.next().copied()) else {
+            writeln!(io.stderr, "head: option requires an argument -- '{}'", unit)?;
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        };
+        let (all_but, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value),
+        };
+        let Ok(number) = digits.parse::<u64>() else {
+            let what = if unit == 'n' { "lines" } else { "bytes" };
+            writeln!(io.stderr, "head: invalid number of {}: '{}'", what, value)?;
+            return Ok(1);
+        };
+        count = match (unit, all_but) {
+            ('n', false) => Count::Lines(number),
+            ('n', true) => Count::AllButLines(number),
+            (_, false) => Count::Bytes(number),
+            (_, true) => Count::AllButBytes(number),
+        };
+    }
+
+    if files.is_empty() {
+        files.push("-");
+    }
+    let headers = headers.unwrap_or(files.len() > 1);
+
+    let mut status = 0;
+    for (i, name) in files.iter().enumerate() {
+        let mut input = match builtins::open_input(current_dir, name, io) {
+            Ok(input) => input,
+            Err(e) => {
+                writeln!(io.stderr, "head: cannot open '{}' for reading: {}", name, e)?;
+                status = 1;
+                continue;
+            }
+        };
+        if headers {
+            let name = if *name == "-" { "standard input" } else { name };
+            let separator = if i > 0 { "\n" } else { "" };
+            writeln!(io.stdout, "{}==> {} <==", separator, name)?;
+        }
+        if let Err(e) = print_head(&mut input, count, io) {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return Err(e);
+            }
+            writeln!(io.stderr, "head: error reading '{}': {}", name, e)?;
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+fn print_head(input: &mut impl Read, count: Count, io: &mut Io) -> io::Result<()> {
+    let mut reader = BufReader::new(input);
+    let mut line = Vec::new();
+    match count {
+        // Only as much is read as gets printed, `yes | head` ends
+        Count::Lines(lines) => {
+            for _ in 0..lines {
+                line.clear();
+                if reader.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                io.stdout.write_all(&line)?;
+            }
+        }
+        Count::Bytes(bytes) => {
+            io::copy(&mut reader.take(bytes), &mut io.stdout)?;
+        }
+        // The last lines are held back until more come after them
+        Count::AllButLines(lines) => {
+            let mut held = VecDeque::new();
+            loop {
+                let mut line = Vec::new();
+                if reader.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                held.push_back(line);
+                if held.len() as u64 > lines
+                    && let Some(line) = held.pop_front()
+                {
+                    io.stdout.write_all(&line)?;
+                }
+            }
+        }
+        Count::AllButBytes(bytes) => {
+            let mut held = Vec::new();
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                held.extend_from_slice(&buffer[..read]);
+                let ready = held.len().saturating_sub(bytes as usize);
+                io.stdout.write_all(&held[..ready])?;
+                held.drain(..ready);
+            }
+        }
+    }
+    io.stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn head_of(input: &str, args: &[&str]) -> String {
+        let run = testing::run(input, |io| head(Path::new("."), args, io));
+        assert_eq!((run.status, run.stderr.as_str()), (0, ""));
+        run.stdout
+    }
+
+    #[test]
+    fn prints_the_first_lines_or_bytes() {
+        let input: String = (1..=12).map(|i| format!("{}\n", i)).collect();
+        assert_eq!(head_of(&input, &[]), input[..input.find("11").unwrap()]);
+        assert_eq!(head_of(&input, &["-n", "2"]), "1\n2\n");
+        assert_eq!(head_of(&input, &["-3"]), "1\n2\n3\n");
+        assert_eq!(head_of(&input, &["-n", "-10"]), "1\n2\n");
+        assert_eq!(head_of("abcdef", &["-c4"]), "abcd");
+        assert_eq!(head_of("abcdef", &["-c", "-4"]), "ab");
+        assert_eq!(head_of("a\nb", &["-n", "5"]), "a\nb");
+    }
+
+    #[test]
+    fn prints_headers_for_several_files() {
+        let dir = TempDir::new("head-headers");
+        dir.write("a", "a\n");
+        dir.write("b", "b\n");
+        let run = testing::run("", |io| head(dir.path(), &["a", "b"], io));
+        assert_eq!(run.stdout, "==> a <==\na\n\n==> b <==\nb\n");
+        let run = testing::run("", |io| head(dir.path(), &["-q", "a", "b"], io));
+        assert_eq!(run.stdout, "a\nb\n");
+
+        let run = testing::run("", |io| head(dir.path(), &["-n", "x"], io));
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stderr, "head: invalid number of lines: 'x'\n");
+    }
+}
@@ -0,0 +1,646 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{builtins, lexer, lexer::Token, stdio::Io, variables::Variables};
+
+const DEFAULT_SIZE: usize = 1000;
+
+#[derive(Clone)]
+pub struct History {
+    // Shared with subshells, which only read it (`history | grep x`), so cloning the
+    // shell for every pipeline stage does not copy the whole list
+    entries: Arc<Vec<String>>,
+    // File every new entry is appended to, None keeps history in memory only
+    path: Option<PathBuf>,
+    // Most entries kept in memory (HISTSIZE)
+    size: usize,
+    // HISTCONTROL options
+    ignore_space: bool,
+    ignore_dups: bool,
+    erase_dups: bool,
+    // How many entries at the front were read from the file, the rest were typed here
+    loaded: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            entries: Arc::default(),
+            path: None,
+            size: DEFAULT_SIZE,
+            ignore_space: false,
+            ignore_dups: false,
+            erase_dups: false,
+            loaded: 0,
+        }
+    }
+}
+
+impl History {
+    // Loads the history file. HISTFILE overrides the default location in the data dir,
+    // HISTSIZE limits how many entries are kept and HISTCONTROL picks the filters
+    // (ignorespace, ignoredups, ignoreboth, erasedups; ignoreboth when unset). They are
+    // read from the shell variables, so the rc file can set them.
+    pub fn load(variables: &Variables) -> Self {
+        let path = match variables.get("HISTFILE") {
+            Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => dirs::data_dir().map(|dir| dir.join("mini-shell").join("history")),
+        };
+        let size = variables
+            .get("HISTSIZE")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_SIZE);
+        let control = variables.get("HISTCONTROL").unwrap_or("ignoreboth");
+
+        let mut history = History {
+            path,
+            size,
+            ..History::default()
+        };
+        for option in control.split(':') {
+            match option {
+                "ignorespace" => history.ignore_space = true,
+                "ignoredups" => history.ignore_dups = true,
+                "ignoreboth" => {
+                    history.ignore_space = true;
+                    history.ignore_dups = true;
+                }
+                "erasedups" => history.erase_dups = true,
+                _ => {}
+            }
+        }
+
+        if let Some(path) = &history.path
+            && let Ok(contents) = fs::read_to_string(path)
+        {
+            let entries: Vec<String> = contents.lines().map(decode).collect();
+            let trimmed = entries.len() > history.size * 2;
+            for entry in entries {
+                history.push(entry);
+            }
+            history.loaded = history.entries.len();
+            // Other shells keep appending, shrink the file once it gets much longer
+            // than what is kept anyway
+            if trimmed {
+                let _ = history.trim_file();
+            }
+        }
+
+        history
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    // Records a command typed at the prompt and appends it to the history file
+    pub fn add(&mut self, line: &str) {
+        let line = line.trim_end_matches('\n');
+        if line.trim().is_empty() || (self.ignore_space && line.starts_with(' ')) {
+            return;
+        }
+        if self.ignore_dups && self.entries.last().is_some_and(|last| last == line) {
+            return;
+        }
+
+        self.push(line.to_string());
+
+        if let Some(path) = &self.path
+            && let Err(e) = append_entry(path, line)
+        {
+            eprintln!("history: {}: {}", path.display(), e);
+        }
+    }
+
+    fn push(&mut self, entry: String) {
+        if self.erase_dups {
+            let loaded = &self.entries[..self.loaded];
+            self.loaded -= loaded.iter().filter(|e| **e == entry).count();
+            Arc::make_mut(&mut self.entries).retain(|e| *e != entry);
+        }
+        let entries = Arc::make_mut(&mut self.entries);
+        entries.push(entry);
+        if entries.len() > self.size {
+            let excess = entries.len() - self.size;
+            entries.drain(..excess);
+            self.loaded = self.loaded.saturating_sub(excess);
+        }
+    }
+
+    // Replaces the file with the entries in memory (-w)
+    fn write_file(&self, path: Option<PathBuf>) -> io::Result<()> {
+        let Some(path) = path.or_else(|| self.path.clone()) else {
+            return Ok(());
+        };
+        // Only the history file is shared with other shells
+        let _lock = match Some(&path) == self.path.as_ref() {
+            true => Some(lock(&path)?),
+            false => None,
+        };
+        let lines: Vec<String> = self.entries.iter().map(|entry| encode(entry)).collect();
+        write_lines(&path, &lines)
+    }
+
+    // Keeps the last HISTSIZE lines of the file, read again under the lock so nothing
+    // another shell appended since loading is lost
+    fn trim_file(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _lock = lock(path)?;
+        let lines = read_lines(path)?;
+        write_lines(path, &lines[lines.len().saturating_sub(self.size)..])
+    }
+
+    // Removes entries from memory and their lines from the file (-c and -d). Other shells
+    // append to the same file, so it is read again under the lock and only the lines of
+    // these entries go. They are in the file in the same order as in memory, between the
+    // lines of other shells, so they are found by matching in order. Clearing also drops
+    // the lines older than the entries read when loading.
+    fn remove(&mut self, range: Range<usize>) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            let _lock = lock(path)?;
+            let lines = read_lines(path)?;
+
+            // An entry another shell already removed has no line
+            let mut positions = vec![None; self.entries.len()];
+            let mut line = 0;
+            for (position, entry) in positions.iter_mut().zip(self.entries.iter()) {
+                let entry = encode(entry);
+                if let Some(found) = lines[line..].iter().position(|line| *line == entry) {
+                    *position = Some(line + found);
+                    line += found + 1;
+                }
+            }
+
+            let mut keep = vec![true; lines.len()];
+            for position in positions[range.clone()].iter().flatten() {
+                keep[*position] = false;
+            }
+            if range == (0..self.entries.len())
+                && self.loaded > 0
+                && let Some(first) = positions[0]
+            {
+                keep[..first].fill(false);
+            }
+            let lines: Vec<String> = lines
+                .into_iter()
+                .zip(keep)
+                .filter_map(|(line, keep)| keep.then_some(line))
+                .collect();
+            write_lines(path, &lines)?;
+        }
+
+        self.loaded -= (range.start.min(self.loaded)..range.end.min(self.loaded)).len();
+        Arc::make_mut(&mut self.entries).drain(range);
+        Ok(())
+    }
+
+    // Performs history expansion (!!, !n, !-n, !prefix, !?text?, !$ and ^old^new).
+    // Returns None when the line has nothing to expand.
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        if let Some(rest) = line.strip_prefix('^') {
+            return self.quick_substitution(rest).map(Some);
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut result = String::new();
+        let mut expanded = false;
+        let mut in_single = false;
+        let mut in_double = false;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '\\' if !in_single => {
+                    result.push(c);
+                    if let Some(&next) = chars.get(i + 1) {
+                        result.push(next);
+                        i += 1;
+                    }
+                }
+                '\'' if !in_double => {
+                    in_single = !in_single;
+                    result.push(c);
+                }
+                '"' if !in_single => {
+                    in_double = !in_double;
+                    result.push(c);
+                }
+                // A `!` followed by a blank, `=` or `(` stays as it is
+                '!' if !in_single
+                    && chars.get(i + 1).is_some_and(|next| {
+                        !(matches!(next, ' ' | '\t' | '\n' | '=' | '(')
+                            || in_double && *next == '"')
+                    }) =>
+                {
+                    let (text, length) = self.event(&chars[i + 1..])?;
+                    result.push_str(&text);
+                    expanded = true;
+                    i += length;
+                }
+                _ => result.push(c),
+            }
+            i += 1;
+        }
+
+        Ok(expanded.then_some(result))
+    }
+
+    // Resolves the event after a `!`, returning its text and how many characters it used
+    fn event(&self, spec: &[char]) -> Result<(String, usize), String> {
+        let not_found = |text: &str| format!("!{}: event not found", text);
+        let last = || self.entries.last().cloned().ok_or_else(|| not_found("!"));
+
+        match spec[0] {
+            '!' => Ok((last()?, 1)),
+            '$' => {
+                let last = last()?;
+                let word = lexer::tokenize(&last)
+                    .ok()
+                    .and_then(|tokens| {
+                        tokens.into_iter().rev().find_map(|token| match token {
+                            Token::Word(word) => Some(word),
+                            _ => None,
+                        })
+                    })
+                    .unwrap_or_default();
+                Ok((word, 1))
+            }
+            '?' => {
+                let text: String = spec[1..]
+                    .iter()
+                    .take_while(|c| **c != '?' && **c != '\n')
+                    .collect();
+                let text_length = text.chars().count();
+                // The closing `?` is optional at the end of the line
+                let closed = spec.get(text_length + 1) == Some(&'?');
+                let length = 1 + text_length + usize::from(closed);
+                let entry = self
+                    .entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.contains(&text))
+                    .ok_or_else(|| not_found(&format!("?{}", text)))?;
+                Ok((entry.clone(), length))
+            }
+            _ => {
+                let word: String = spec
+                    .iter()
+                    .take_while(|c| !c.is_whitespace() && !"!;&|<>()\"'`".contains(**c))
+                    .collect();
+                let length = word.chars().count();
+
+                if let Ok(number) = word.parse::<isize>() {
+                    // !n counts from the first entry, !-n back from the last one
+                    let index = if number < 0 {
+                        self.entries.len().checked_sub(number.unsigned_abs())
+                    } else {
+                        (number as usize).checked_sub(1)
+                    };
+                    return index
+                        .and_then(|index| self.entries.get(index))
+                        .map(|entry| (entry.clone(), length))
+                        .ok_or_else(|| not_found(&word));
+                }
+
+                self.entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.starts_with(&word))
+                    .map(|entry| (entry.clone(), length))
+                    .ok_or_else(|| not_found(&word))
+            }
+        }
+    }
+
+    // ^old^new^ reruns the last command with the first `old` replaced by `new`
+    fn quick_substitution(&self, spec: &str) -> Result<String, String> {
+        let spec = spec.trim_end_matches('\n');
+        let mut parts = spec.splitn(3, '^');
+        let old = parts.next().unwrap_or_default();
+        let new = parts.next().unwrap_or_default();
+        let rest = parts.next().unwrap_or_default();
+
+        let failed = || format!("^{}: substitution failed", spec);
+        let last = self.entries.last().ok_or_else(failed)?;
+        if old.is_empty() || !last.contains(old) {
+            return Err(failed());
+        }
+
+        Ok(format!("{}{}\n", last.replacen(old, new, 1), rest))
+    }
+}
+
+// Appends one entry with a single write on a file opened for appending, so lines
+// from several shells sharing the file never get mixed up
+fn append_entry(path: &PathBuf, entry: &str) -> io::Result<()> {
+    let _lock = lock(path)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(format!("{}\n", encode(entry)).as_bytes())
+}
+
+// Locks `<file>.lock` until the returned file is dropped, creating the directory of the
+// history file when needed. Appends take it too, so a rewrite never replaces the file
+// while another shell writes to the old one.
+fn lock(path: &Path) -> io::Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(name)?;
+    file.lock()?;
+    Ok(file)
+}
+
+// The lines of the file as stored, still encoded. A missing file has none.
+fn read_lines(path: &Path) -> io::Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().map(str::to_string).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+// Replaces the file through a temporary one, so other shells never see it half written
+fn write_lines(path: &Path, lines: &[String]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut contents = String::new();
+    for line in lines {
+        contents.push_str(line);
+        contents.push('\n');
+    }
+    let temp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)
+}
+
+// Multiline commands are stored on one line with their newlines escaped
+fn encode(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn decode(line: &str) -> String {
+    let mut entry = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            _ => entry.push(c),
+        }
+    }
+    entry
+}
+
+// history [n] lists entries, -c clears them, -d n deletes one, -w [file] writes them out.
+// -c and -d remove the entries from the history file too, every entry was already
+// appended to it.
+pub fn handle_history_command(
+    history: &mut History,
+    current_dir: &Path,
+    args: &[&str],
+    io: &mut Io,
+) -> io::Result<i32> {
+    match args {
+        ["-c"] => history.remove(0..history.entries.len())?,
+        ["-d", offset] => {
+            let index = offset
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .filter(|index| *index < history.entries.len());
+            match index {
+                Some(index) => history.remove(index..index + 1)?,
+                None => {
+                    writeln!(
+                        io.stderr,
+                        "history: {}: history position out of range",
+                        offset
+                    )?;
+                    return Ok(1);
+                }
+            }
+        }
+        ["-w"] => history.write_file(None)?,
+        ["-w", file] => {
+            let path = builtins::resolve_path(current_dir, file)
+                .ok_or_else(|| io::Error::other("Could not determine home directory"))?;
+            history.write_file(Some(path))?;
+        }
+        [] | [_] => {
+            let count = match args.first() {
+                Some(count) => match count.parse::<usize>() {
+                    Ok(count) => count,
+                    Err(_) => {
+                        writeln!(io.stderr, "history: {}: numeric argument required", count)?;
+                        return Ok(1);
+                    }
+                },
+                None => history.entries.len(),
+            };
+            let start = history.entries.len().saturating_sub(count);
+            for (i, entry) in history.entries.iter().enumerate().skip(start) {
+                writeln!(io.stdout, "{:>5}  {}", i + 1, entry)?;
+            }
+        }
+        _ => {
+            writeln!(
+                io.stderr,
+                "history: usage: history [-c] [-d offset] [-w [file]] [n]"
+            )?;
+            return Ok(2);
+        }
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn history(entries: &[&str]) -> History {
+        let mut history = History::default();
+        for entry in entries {
+            history.add(entry);
+        }
+        history
+    }
+
+    #[test]
+    fn expands_events() {
+        let history = history(&["ls -l", "echo one two", "git status"]);
+        assert_eq!(history.expand("!!"), Ok(Some("git status".to_string())));
+        assert_eq!(
+            history.expand("sudo !!\n"),
+            Ok(Some("sudo git status\n".to_string()))
+        );
+        assert_eq!(history.expand("!1"), Ok(Some("ls -l".to_string())));
+        assert_eq!(history.expand("!-2"), Ok(Some("echo one two".to_string())));
+        assert_eq!(
+            history.expand("!ec; !l"),
+            Ok(Some("echo one two; ls -l".to_string()))
+        );
+        assert_eq!(
+            history.expand("!?one?x"),
+            Ok(Some("echo one twox".to_string()))
+        );
+        assert_eq!(history.expand("!?stat"), Ok(Some("git status".to_string())));
+        assert_eq!(history.expand("cat !$"), Ok(Some("cat status".to_string())));
+    }
+
+    #[test]
+    fn leaves_lines_without_events_alone() {
+        let history = history(&["ls"]);
+        for line in [
+            "ls",
+            "echo !",
+            "a != b",
+            "x=!(y)",
+            "echo '!!'",
+            "echo \\!!",
+            "echo \"a!\"",
+        ] {
+            assert_eq!(history.expand(line), Ok(None), "{:?}", line);
+        }
+        // Double quotes do not stop an event
+        assert_eq!(
+            history.expand("echo \"!!\""),
+            Ok(Some("echo \"ls\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn reports_missing_events() {
+        assert_eq!(
+            History::default().expand("!!"),
+            Err("!!: event not found".to_string())
+        );
+        let history = history(&["ls"]);
+        assert_eq!(history.expand("!5"), Err("!5: event not found".to_string()));
+        assert_eq!(
+            history.expand("!-2"),
+            Err("!-2: event not found".to_string())
+        );
+        assert_eq!(history.expand("!0"), Err("!0: event not found".to_string()));
+        assert_eq!(
+            history.expand("!cd"),
+            Err("!cd: event not found".to_string())
+        );
+        assert_eq!(
+            history.expand("!?x?"),
+            Err("!?x: event not found".to_string())
+        );
+    }
+
+    #[test]
+    fn substitutes_in_the_last_command() {
+        let history = history(&["cat fiel.txt fiel"]);
+        assert_eq!(
+            history.expand("^fiel^file\n"),
+            Ok(Some("cat file.txt fiel\n".to_string()))
+        );
+        assert_eq!(
+            history.expand("^fiel^file^ -n"),
+            Ok(Some("cat file.txt fiel -n\n".to_string()))
+        );
+        assert_eq!(
+            history.expand("^q^y"),
+            Err("^q^y: substitution failed".to_string())
+        );
+        assert_eq!(
+            history.expand("^^y"),
+            Err("^^y: substitution failed".to_string())
+        );
+    }
+
+    #[test]
+    fn skips_entries_as_histcontrol_says() {
+        let mut history = history(&[]);
+        history.ignore_space = true;
+        history.ignore_dups = true;
+        for line in ["a", "a", " b", "c", "\n"] {
+            history.add(line);
+        }
+        assert_eq!(*history.entries, ["a", "c"]);
+
+        history.erase_dups = true;
+        history.add("a");
+        assert_eq!(*history.entries, ["c", "a"]);
+    }
+
+    #[test]
+    fn keeps_entries_of_other_shells_sharing_the_file() {
+        let dir = TempDir::new("history-shared");
+        dir.write("history", "old 1\nold 2\n");
+        let mut variables = Variables::default();
+        variables.set("HISTFILE", dir.join("history").display().to_string());
+        let history_command = |history: &mut History, args: &[&str]| {
+            testing::run("", |io| {
+                handle_history_command(history, dir.path(), args, io)
+            })
+            .status
+        };
+
+        let mut first = History::load(&variables);
+        let mut second = History::load(&variables);
+        first.add("a 1");
+        second.add("b 1");
+        first.add("a 2");
+        second.add("b 2");
+        assert_eq!(dir.read("history"), "old 1\nold 2\na 1\nb 1\na 2\nb 2\n");
+
+        assert_eq!(history_command(&mut first, &["-d", "3"]), 0);
+        assert_eq!(*first.entries, ["old 1", "old 2", "a 2"]);
+        assert_eq!(dir.read("history"), "old 1\nold 2\nb 1\na 2\nb 2\n");
+
+        // Only what this shell knew of goes, the other shell's entries stay
+        second.add("b 3");
+        assert_eq!(history_command(&mut first, &["-c"]), 0);
+        assert!(first.entries.is_empty());
+        assert_eq!(dir.read("history"), "b 1\nb 2\nb 3\n");
+
+        first.add("a 3");
+        assert_eq!(history_command(&mut second, &["-c"]), 0);
+        assert_eq!(dir.read("history"), "a 3\n");
+    }
+
+    #[test]
+    fn trims_long_files_when_loading() {
+        let dir = TempDir::new("history-trim");
+        let lines: String = (1..=5).map(|i| format!("{}\n", i)).collect();
+        dir.write("history", &lines);
+        let mut variables = Variables::default();
+        variables.set("HISTFILE", dir.join("history").display().to_string());
+        variables.set("HISTSIZE", "2".to_string());
+
+        let mut first = History::load(&variables);
+        assert_eq!(*first.entries, ["4", "5"]);
+        assert_eq!(dir.read("history"), "4\n5\n");
+        first.add("6");
+        let second = History::load(&variables);
+        assert_eq!(*second.entries, ["5", "6"]);
+        assert_eq!(dir.read("history"), "4\n5\n6\n");
+    }
+}
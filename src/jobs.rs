@@ -0,0 +1,653 @@
+use std::{
+    io::{self, Write},
+    process::{Child, ExitStatus},
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+use crate::{
+    signals::{self, JobInterrupt},
+    stdio::Io,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProcessState {
+    Running,
+    Stopped,
+    Done(ExitStatus),
+}
+
+pub struct Process {
+    pub child: Child,
+    pub state: ProcessState,
+}
+
+// Where the exit code of a job comes from, which is always its last stage
+#[derive(Clone, Copy, PartialEq)]
+pub enum LastStage {
+    // The last process in the job
+    External,
+    // The last thread, a builtin or compound command
+    Thread,
+    // Known already: the thread was joined, or the stage never started
+    Exited(i32),
+}
+
+pub struct Job {
+    pub id: usize,
+    // Process group of the job, None when it shares the shell's group
+    pub pgid: Option<u32>,
+    pub command: String,
+    pub processes: Vec<Process>,
+    // Builtin and compound stages of the pipeline run as threads
+    pub threads: Vec<JoinHandle<i32>>,
+    // What the threads check to see whether the job was interrupted
+    pub interrupt: Arc<JobInterrupt>,
+    pub last_stage: LastStage,
+}
+
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+}
+
+// A subshell (e.g. a builtin inside a pipeline) starts without jobs of its own
+impl Clone for JobTable {
+    fn clone(&self) -> Self {
+        JobTable::default()
+    }
+}
+
+impl Job {
+    pub fn new(command: String, pgid: Option<u32>) -> Self {
+        Job {
+            id: 0,
+            pgid,
+            command,
+            processes: Vec::new(),
+            threads: Vec::new(),
+            interrupt: JobInterrupt::new(),
+            last_stage: LastStage::Exited(0),
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.processes
+            .iter()
+            .any(|p| p.state == ProcessState::Stopped)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.processes
+            .iter()
+            .all(|p| matches!(p.state, ProcessState::Done(_)))
+            && self.threads.iter().all(|t| t.is_finished())
+    }
+
+    // Exit status of the last stage, if it was an external command that finished
+    pub fn status(&self) -> Option<ExitStatus> {
+        if self.last_stage != LastStage::External {
+            return None;
+        }
+        match self.processes.last()?.state {
+            ProcessState::Done(status) => Some(status),
+            _ => None,
+        }
+    }
+
+    // Exit code of a finished job, 128 + the signal number when it was killed
+    pub fn exit_code(&self) -> i32 {
+        match self.last_stage {
+            LastStage::Exited(code) => code,
+            LastStage::Thread => 0,
+            LastStage::External => self.status().map_or(0, |status| exit_code(&status)),
+        }
+    }
+
+    pub fn state_name(&self) -> String {
+        if self.is_done() {
+            let signal = self.status().as_ref().and_then(signals::termination_signal);
+            match (self.exit_code(), signal) {
+                (_, Some(signal)) => format!("Terminated ({})", signals::signal_name(signal)),
+                (0, None) => "Done".to_string(),
+                (code, None) => format!("Exit {}", code),
+            }
+        } else if self.is_stopped() {
+            "Stopped".to_string()
+        } else {
+            "Running".to_string()
+        }
+    }
+
+    pub fn last_pid(&self) -> Option<u32> {
+        self.processes.last().map(|p| p.child.id())
+    }
+
+    // Blocks until every stage has finished or the job was stopped (Ctrl-Z)
+    pub fn wait(&mut self) {
+        for process in &mut self.processes {
+            if process.state == ProcessState::Running {
+                sys::wait_process(process);
+            }
+        }
+
+        if !self.is_stopped() {
+            self.join_threads();
+        }
+    }
+
+    // Checks for state changes without blocking
+    pub fn poll(&mut self) {
+        for process in &mut self.processes {
+            if !matches!(process.state, ProcessState::Done(_)) {
+                sys::poll_process(process);
+            }
+        }
+        if self.threads.iter().all(|t| t.is_finished()) {
+            self.join_threads();
+        }
+    }
+
+    // Waits for the threads, taking the exit code from the last one if it is the last stage
+    fn join_threads(&mut self) {
+        let last = self.threads.pop();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        if let Some(thread) = last {
+            // A thread that panicked counts as a failure
+            let code = thread.join().unwrap_or(1);
+            if self.last_stage == LastStage::Thread {
+                self.last_stage = LastStage::Exited(code);
+            }
+        }
+    }
+
+    pub fn resume(&mut self) {
+        sys::continue_job(self);
+        for process in &mut self.processes {
+            if process.state == ProcessState::Stopped {
+                process.state = ProcessState::Running;
+            }
+        }
+    }
+
+    pub fn signal(&mut self, signal: i32) -> io::Result<()> {
+        // Builtins stop at their next check for Ctrl-C, they cannot be stopped or continued
+        if signals::terminates(signal) {
+            self.interrupt.kill();
+        }
+        sys::signal_job(self, signal)?;
+        // A stopped job would only see SIGTERM or SIGHUP once it runs again
+        if self.is_stopped() && (signal == signals::SIGTERM || signal == signals::SIGHUP) {
+            self.resume();
+        }
+        Ok(())
+    }
+}
+
+impl JobTable {
+    pub fn add(&mut self, mut job: Job) -> usize {
+        job.id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        let id = job.id;
+        self.jobs.push(job);
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(index))
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    pub fn ids(&self) -> Vec<usize> {
+        self.jobs.iter().map(|job| job.id).collect()
+    }
+
+    // The most recently added job is the current one (`%+`), the one before it the previous (`%-`)
+    pub fn marker(&self, id: usize) -> char {
+        let count = self.jobs.len();
+        match self.jobs.iter().position(|job| job.id == id) {
+            Some(index) if index + 1 == count => '+',
+            Some(index) if index + 2 == count => '-',
+            _ => ' ',
+        }
+    }
+
+    // Resolves a job spec such as %1, %%, %+, %- or %prefix
+    pub fn find(&self, spec: &str) -> Option<usize> {
+        let spec = spec.strip_prefix('%').unwrap_or(spec);
+
+        match spec {
+            "" | "%" | "+" => self.jobs.last().map(|job| job.id),
+            "-" => self
+                .jobs
+                .iter()
+                .rev()
+                .nth(1)
+                .or(self.jobs.last())
+                .map(|job| job.id),
+            _ => match spec.parse::<usize>() {
+                Ok(id) => self.jobs.iter().find(|job| job.id == id).map(|job| job.id),
+                Err(_) => self
+                    .jobs
+                    .iter()
+                    .rev()
+                    .find(|job| job.command.starts_with(spec))
+                    .map(|job| job.id),
+            },
+        }
+    }
+
+    pub fn print(&self, id: usize, out: &mut dyn Write) -> io::Result<()> {
+        if let Some(job) = self.jobs.iter().find(|job| job.id == id) {
+            let state = job.state_name();
+            let suffix = if state == "Running" { " &" } else { "" };
+            writeln!(
+                out,
+                "[{}]{}  {:<24}{}{}",
+                job.id,
+                self.marker(id),
+                state,
+                job.command,
+                suffix
+            )?;
+        }
+        Ok(())
+    }
+
+    // Updates every job and reports the ones that finished since the last prompt
+    pub fn notify_finished(&mut self) {
+        for job in &mut self.jobs {
+            job.poll();
+        }
+
+        for id in self.ids() {
+            let done = self.jobs.iter().any(|job| job.id == id && job.is_done());
+            if done {
+                let _ = self.print(id, &mut io::stdout());
+                self.remove(id);
+            }
+        }
+    }
+}
+
+pub fn list_jobs(jobs: &mut JobTable, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    for job in &mut jobs.jobs {
+        job.poll();
+    }
+
+    // `jobs -p` only prints the process ids
+    let pids_only = args.contains(&"-p");
+
+    for id in jobs.ids() {
+        if pids_only {
+            if let Some(pid) = jobs.get_mut(id).and_then(|job| job.last_pid()) {
+                writeln!(io.stdout, "{}", pid)?;
+            }
+        } else {
+            jobs.print(id, &mut io.stdout)?;
+        }
+    }
+
+    // Finished jobs have been reported now, no need to do it again at the next prompt
+    jobs.jobs.retain(|job| !job.is_done());
+
+    Ok(0)
+}
+
+pub fn background_job(jobs: &mut JobTable, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let spec = args.first().copied().unwrap_or("%+");
+    let Some(id) = jobs.find(spec) else {
+        writeln!(io.stderr, "bg: {}: no such job", spec)?;
+        return Ok(1);
+    };
+
+    let marker = jobs.marker(id);
+    if let Some(job) = jobs.get_mut(id) {
+        job.resume();
+        writeln!(io.stdout, "[{}]{} {} &", id, marker, job.command)?;
+    }
+
+    Ok(0)
+}
+
+// The exit code is the one of the last job waited for, 127 if it did not exist
+pub fn wait_jobs(jobs: &mut JobTable, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut status = 0;
+    let ids = if args.is_empty() {
+        jobs.ids()
+    } else {
+        let mut ids = Vec::new();
+        for spec in args {
+            match jobs.find(spec) {
+                Some(id) => ids.push(id),
+                None => {
+                    writeln!(io.stderr, "wait: {}: no such job", spec)?;
+                    status = 127;
+                }
+            }
+        }
+        ids
+    };
+
+    for id in ids {
+        if let Some(job) = jobs.get_mut(id) {
+            job.wait();
+            status = job.exit_code();
+            if job.is_done() {
+                jobs.remove(id);
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+pub fn kill(jobs: &mut JobTable, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut signal = signals::SIGTERM;
+    let mut targets = args;
+
+    match args.first() {
+        Some(&"-l") => {
+            let names: Vec<&str> = signals::SIGNALS.iter().map(|(name, _)| *name).collect();
+            writeln!(io.stdout, "{}", names.join(" "))?;
+            return Ok(0);
+        }
+        Some(&"-s") => {
+            let Some(name) = args.get(1) else {
+                writeln!(io.stderr, "kill: -s: option requires an argument")?;
+                return Ok(2);
+            };
+            match signals::signal_number(name) {
+                Some(number) => signal = number,
+                None => {
+                    writeln!(io.stderr, "kill: {}: invalid signal specification", name)?;
+                    return Ok(1);
+                }
+            }
+            targets = &args[2..];
+        }
+        Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+            match signals::signal_number(&arg[1..]) {
+                Some(number) => signal = number,
+                None => {
+                    writeln!(
+                        io.stderr,
+                        "kill: {}: invalid signal specification",
+                        &arg[1..]
+                    )?;
+                    return Ok(1);
+                }
+            }
+            targets = &args[1..];
+        }
+        _ => {}
+    }
+
+    if targets.is_empty() {
+        writeln!(
+            io.stderr,
+            "Usage: kill [-s signal | -signal] <pid | %job> ..."
+        )?;
+        return Ok(2);
+    }
+
+    let mut status = 0;
+    for target in targets {
+        let result = if target.starts_with('%') {
+            match jobs.find(target).and_then(|id| jobs.get_mut(id)) {
+                Some(job) => job.signal(signal),
+                None => {
+                    writeln!(io.stderr, "kill: {}: no such job", target)?;
+                    status = 1;
+                    continue;
+                }
+            }
+        } else {
+            match target.parse::<u32>() {
+                Ok(pid) => sys::signal_pid(pid, signal),
+                Err(_) => {
+                    writeln!(
+                        io.stderr,
+                        "kill: {}: arguments must be process or job IDs",
+                        target
+                    )?;
+                    status = 1;
+                    continue;
+                }
+            }
+        };
+
+        if let Err(e) = result {
+            writeln!(io.stderr, "kill: ({}) - {}", target, e)?;
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+// The number a finished process leaves in $?, 128 + the signal number when it was killed
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    match status.code() {
+        Some(code) => code,
+        None => 128 + signals::termination_signal(status).unwrap_or(0),
+    }
+}
+
+#[cfg(unix)]
+pub mod sys {
+    use std::{io, os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    use super::{Job, Process, ProcessState};
+
+    pub fn signal_pid(pid: u32, signal: i32) -> io::Result<()> {
+        // SAFETY: plain kill(2), the user asked for this pid
+        if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    // Makes the shell the owner of its terminal so it can hand it to foreground jobs
+    pub fn init_job_control() {
+        // SAFETY: plain syscalls on the shell's own process and terminal
+        unsafe {
+            libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+            libc::signal(libc::SIGTTIN, libc::SIG_IGN);
+            libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+
+            let pid = libc::getpid();
+            libc::setpgid(0, 0);
+            libc::tcsetpgrp(libc::STDIN_FILENO, pid);
+        }
+    }
+
+    pub fn set_process_group(pid: u32, pgid: u32) {
+        // SAFETY: harmless if the child already did it itself or has exited
+        unsafe {
+            libc::setpgid(pid as libc::pid_t, pgid as libc::pid_t);
+        }
+    }
+
+    pub fn give_terminal_to(pgid: u32) {
+        // SAFETY: SIGTTOU is ignored, so this cannot stop the shell
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid as libc::pid_t);
+        }
+    }
+
+    pub fn take_terminal_back() {
+        // SAFETY: see give_terminal_to
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+        }
+    }
+
+    fn update_state(process: &mut Process, status: i32) {
+        process.state = if libc::WIFSTOPPED(status) {
+            ProcessState::Stopped
+        } else if libc::WIFCONTINUED(status) {
+            ProcessState::Running
+        } else {
+            ProcessState::Done(ExitStatus::from_raw(status))
+        };
+    }
+
+    pub fn wait_process(process: &mut Process) {
+        let pid = process.child.id() as libc::pid_t;
+        loop {
+            let mut status = 0;
+            // SAFETY: waiting on our own child
+            let result = unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) };
+            if result == pid {
+                update_state(process, status);
+                return;
+            }
+            if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                // Already reaped somewhere else, nothing left to wait for
+                process.state = ProcessState::Done(ExitStatus::from_raw(0));
+                return;
+            }
+        }
+    }
+
+    pub fn poll_process(process: &mut Process) {
+        let pid = process.child.id() as libc::pid_t;
+        let mut status = 0;
+        // SAFETY: waiting on our own child
+        let result = unsafe {
+            libc::waitpid(
+                pid,
+                &mut status,
+                libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED,
+            )
+        };
+        if result == pid {
+            update_state(process, status);
+        } else if result == -1 {
+            process.state = ProcessState::Done(ExitStatus::from_raw(0));
+        }
+    }
+
+    pub fn continue_job(job: &mut Job) {
+        let _ = signal_job(job, libc::SIGCONT);
+    }
+
+    pub fn signal_job(job: &mut Job, signal: i32) -> io::Result<()> {
+        // SAFETY: only signals processes that belong to this job
+        let result = match job.pgid {
+            Some(pgid) => unsafe { libc::killpg(pgid as libc::pid_t, signal) },
+            None => {
+                let mut result = 0;
+                for process in &job.processes {
+                    if !matches!(process.state, ProcessState::Done(_)) {
+                        result |= unsafe { libc::kill(process.child.id() as libc::pid_t, signal) };
+                    }
+                }
+                result
+            }
+        };
+
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub mod sys {
+    use std::io;
+
+    use super::{Job, Process, ProcessState};
+
+    pub fn signal_pid(_pid: u32, _signal: i32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only jobs can be signalled on this platform",
+        ))
+    }
+
+    pub fn wait_process(process: &mut Process) {
+        if let Ok(status) = process.child.wait() {
+            process.state = ProcessState::Done(status);
+        }
+    }
+
+    pub fn poll_process(process: &mut Process) {
+        if let Ok(Some(status)) = process.child.try_wait() {
+            process.state = ProcessState::Done(status);
+        }
+    }
+
+    // Processes cannot be stopped here, so there is nothing to continue
+    pub fn continue_job(_job: &mut Job) {}
+
+    pub fn signal_job(job: &mut Job, _signal: i32) -> io::Result<()> {
+        for process in &mut job.processes {
+            if !matches!(process.state, ProcessState::Done(_)) {
+                process.child.kill()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::{thread, time::Duration};
+
+    // A background job of one builtin that runs until it is interrupted
+    fn builtin_job() -> Job {
+        let mut job = Job::new("loop &".to_string(), None);
+        let interrupt = Arc::clone(&job.interrupt);
+        job.threads.push(thread::spawn(move || {
+            interrupt.enter();
+            while !signals::interrupt_pending() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            128 + signals::SIGINT
+        }));
+        job.last_stage = LastStage::Thread;
+        job
+    }
+
+    #[test]
+    fn kill_stops_the_builtins_of_that_job_only() {
+        let mut jobs = JobTable::default();
+        let first = jobs.add(builtin_job());
+        let second = jobs.add(builtin_job());
+
+        let run = testing::run("", |io| kill(&mut jobs, &[&format!("%{}", first)], io));
+        assert_eq!((run.status, run.stderr.as_str()), (0, ""));
+        let job = jobs.get_mut(first).unwrap();
+        job.wait();
+        assert_eq!(job.exit_code(), 128 + signals::SIGINT);
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!jobs.get_mut(second).unwrap().is_done());
+        assert!(!signals::interrupt_pending());
+
+        // Continuing is no reason to stop
+        #[cfg(unix)]
+        {
+            let run = testing::run("", |io| kill(&mut jobs, &["-s", "CONT", "%2"], io));
+            assert_eq!(run.status, 0);
+            thread::sleep(Duration::from_millis(20));
+            assert!(!jobs.get_mut(second).unwrap().is_done());
+        }
+
+        let run = testing::run("", |io| kill(&mut jobs, &["-9", "%2"], io));
+        assert_eq!(run.status, 0);
+        jobs.get_mut(second).unwrap().wait();
+    }
+}
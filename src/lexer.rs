@@ -0,0 +1,694 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // Raw word text, quotes and escapes are kept so later stages know what was quoted
+    Word(String),
+    Pipe,
+    Ampersand,
+    Semicolon,
+    DoubleSemicolon, // ;; ends an item of a case
+    And,             // &&
+    Or,              // ||
+    LeftParen,
+    RightParen,
+    Newline,
+    // Optional file descriptor number written right before the operator, as in `2>`
+    Redirect(Option<u32>, RedirectOp),
+    // Body of a here-document, always follows a `<<` redirect
+    HereDocBody(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectOp {
+    Input,      // <
+    Output,     // >
+    Append,     // >>
+    DupInput,   // <&
+    DupOutput,  // >&
+    OutputAll,  // &>
+    AppendAll,  // &>>
+    HereDoc,    // << and <<-
+    HereString, // <<<
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LexError {
+    UnterminatedQuote(char),
+    MissingDelimiter,
+    // Input ended in the middle of something (trailing backslash, here-document),
+    // more input is needed
+    Incomplete,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnterminatedQuote('\'') => write!(f, "unterminated single quote"),
+            LexError::UnterminatedQuote('`') => write!(f, "unterminated backquote"),
+            LexError::UnterminatedQuote(_) => write!(f, "unterminated double quote"),
+            LexError::MissingDelimiter => write!(f, "missing here-document delimiter"),
+            LexError::Incomplete => write!(f, "unexpected end of input"),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Pipe => write!(f, "|"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Semicolon => write!(f, ";"),
+            Token::DoubleSemicolon => write!(f, ";;"),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::Newline => write!(f, "newline"),
+            Token::Redirect(fd, op) => {
+                if let Some(fd) = fd {
+                    write!(f, "{}", fd)?;
+                }
+                write!(f, "{}", op)
+            }
+            Token::HereDocBody(_) => write!(f, "newline"),
+        }
+    }
+}
+
+impl fmt::Display for RedirectOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            RedirectOp::Input => "<",
+            RedirectOp::Output => ">",
+            RedirectOp::Append => ">>",
+            RedirectOp::DupInput => "<&",
+            RedirectOp::DupOutput => ">&",
+            RedirectOp::OutputAll => "&>",
+            RedirectOp::AppendAll => "&>>",
+            RedirectOp::HereDoc => "<<",
+            RedirectOp::HereString => "<<<",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+struct PendingHereDoc {
+    // Index of the HereDocBody token to fill in
+    index: usize,
+    delimiter: String,
+    strip_tabs: bool,
+    // A quoted delimiter keeps the body from being expanded
+    literal: bool,
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    tokens: Vec<Token>,
+    word: String,
+    in_word: bool,
+    // Here-documents whose bodies start after the next newline
+    pending_heredocs: Vec<PendingHereDoc>,
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = Lexer {
+        chars: input.chars().peekable(),
+        tokens: Vec::new(),
+        word: String::new(),
+        in_word: false,
+        pending_heredocs: Vec::new(),
+    };
+
+    lexer.run()?;
+    Ok(lexer.tokens)
+}
+
+impl Lexer<'_> {
+    fn run(&mut self) -> Result<(), LexError> {
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\n' => {
+                    self.finish_word();
+                    self.tokens.push(Token::Newline);
+                    self.read_heredoc_bodies()?;
+                }
+                ' ' | '\t' | '\r' => self.finish_word(),
+                '|' => {
+                    self.finish_word();
+                    if self.chars.next_if_eq(&'|').is_some() {
+                        self.tokens.push(Token::Or);
+                    } else {
+                        self.tokens.push(Token::Pipe);
+                    }
+                }
+                ';' => {
+                    self.finish_word();
+                    if self.chars.next_if_eq(&';').is_some() {
+                        self.tokens.push(Token::DoubleSemicolon);
+                    } else {
+                        self.tokens.push(Token::Semicolon);
+                    }
+                }
+                // A comment runs to the end of the line, but only where a word could start
+                '#' if !self.in_word => while self.chars.next_if(|c| *c != '\n').is_some() {},
+                '(' => {
+                    self.finish_word();
+                    self.tokens.push(Token::LeftParen);
+                }
+                ')' => {
+                    self.finish_word();
+                    self.tokens.push(Token::RightParen);
+                }
+                '<' | '>' => {
+                    let fd = self.take_io_number();
+                    self.finish_word();
+                    self.read_redirect(c, fd)?;
+                }
+                '&' if self.chars.peek() == Some(&'>') => {
+                    self.finish_word();
+                    self.chars.next();
+                    let op = if self.chars.next_if_eq(&'>').is_some() {
+                        RedirectOp::AppendAll
+                    } else {
+                        RedirectOp::OutputAll
+                    };
+                    self.tokens.push(Token::Redirect(None, op));
+                }
+                '&' => {
+                    self.finish_word();
+                    if self.chars.next_if_eq(&'&').is_some() {
+                        self.tokens.push(Token::And);
+                    } else {
+                        self.tokens.push(Token::Ampersand);
+                    }
+                }
+                _ => self.read_word_char(c)?,
+            }
+        }
+
+        self.finish_word();
+
+        if !self.pending_heredocs.is_empty() {
+            // The body has not been typed yet
+            return Err(LexError::Incomplete);
+        }
+
+        Ok(())
+    }
+
+    // Handles a character that is part of a word, including quotes and escapes
+    fn read_word_char(&mut self, c: char) -> Result<(), LexError> {
+        self.in_word = true;
+
+        match c {
+            '\\' => match self.chars.next() {
+                // Line continuation
+                Some('\n') if self.chars.peek().is_none() => return Err(LexError::Incomplete),
+                Some('\n') => {}
+                Some(next) => {
+                    self.word.push('\\');
+                    self.word.push(next);
+                }
+                None => return Err(LexError::Incomplete),
+            },
+            '\'' => {
+                self.word.push('\'');
+                loop {
+                    match self.chars.next() {
+                        Some('\'') => break,
+                        Some(next) => self.word.push(next),
+                        None => return Err(LexError::UnterminatedQuote('\'')),
+                    }
+                }
+                self.word.push('\'');
+            }
+            '"' => {
+                self.word.push('"');
+                loop {
+                    match self.chars.next() {
+                        Some('"') => break,
+                        Some('$') if matches!(self.chars.peek(), Some('{' | '(')) => {
+                            self.read_word_char('$')?;
+                        }
+                        Some('`') => self.read_word_char('`')?,
+                        Some('\\') => match self.chars.next() {
+                            Some('\n') => {}
+                            Some(next) => {
+                                self.word.push('\\');
+                                self.word.push(next);
+                            }
+                            None => return Err(LexError::UnterminatedQuote('"')),
+                        },
+                        Some(next) => self.word.push(next),
+                        None => return Err(LexError::UnterminatedQuote('"')),
+                    }
+                }
+                self.word.push('"');
+            }
+            '$' if matches!(self.chars.peek(), Some('{' | '(')) => {
+                self.word.push('$');
+                let close = if self.chars.peek() == Some(&'{') {
+                    '}'
+                } else {
+                    ')'
+                };
+                self.read_nested(close)?;
+            }
+            '`' => {
+                self.word.push('`');
+                loop {
+                    match self.chars.next() {
+                        Some('`') => break,
+                        Some('\\') => {
+                            self.word.push('\\');
+                            match self.chars.next() {
+                                Some(next) => self.word.push(next),
+                                None => return Err(LexError::UnterminatedQuote('`')),
+                            }
+                        }
+                        Some(next) => self.word.push(next),
+                        None => return Err(LexError::UnterminatedQuote('`')),
+                    }
+                }
+                self.word.push('`');
+            }
+            _ => self.word.push(c),
+        }
+
+        Ok(())
+    }
+
+    // Reads a `${...}` or `$(...)` up to the bracket closing it, so blanks, quotes and
+    // operators inside stay part of the word
+    fn read_nested(&mut self, close: char) -> Result<(), LexError> {
+        let open = if close == '}' { '{' } else { '(' };
+        let mut depth = 0;
+        loop {
+            match self.chars.next() {
+                Some(c) if c == open => {
+                    self.word.push(c);
+                    depth += 1;
+                }
+                Some(c) if c == close => {
+                    self.word.push(c);
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                Some(c @ ('\\' | '\'' | '"' | '`')) => self.read_word_char(c)?,
+                Some('$') if matches!(self.chars.peek(), Some('{' | '(')) => {
+                    self.read_word_char('$')?;
+                }
+                Some(c) => self.word.push(c),
+                None => return Err(LexError::Incomplete),
+            }
+        }
+    }
+
+    fn finish_word(&mut self) {
+        if self.in_word {
+            self.tokens
+                .push(Token::Word(std::mem::take(&mut self.word)));
+            self.in_word = false;
+        }
+    }
+
+    // A word made only of digits right before `<` or `>` is the fd to redirect (`2>`)
+    fn take_io_number(&mut self) -> Option<u32> {
+        if !self.in_word || !self.word.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let fd = self.word.parse().ok()?;
+        self.word.clear();
+        self.in_word = false;
+        Some(fd)
+    }
+
+    fn read_redirect(&mut self, c: char, fd: Option<u32>) -> Result<(), LexError> {
+        let op = if c == '<' {
+            if self.chars.next_if_eq(&'<').is_some() {
+                if self.chars.next_if_eq(&'<').is_some() {
+                    RedirectOp::HereString
+                } else {
+                    let strip_tabs = self.chars.next_if_eq(&'-').is_some();
+                    return self.read_heredoc_delimiter(fd, strip_tabs);
+                }
+            } else if self.chars.next_if_eq(&'&').is_some() {
+                RedirectOp::DupInput
+            } else {
+                RedirectOp::Input
+            }
+        } else if self.chars.next_if_eq(&'>').is_some() {
+            RedirectOp::Append
+        } else if self.chars.next_if_eq(&'&').is_some() {
+            RedirectOp::DupOutput
+        } else {
+            // `>|` is the same as `>` since there is no noclobber option
+            self.chars.next_if_eq(&'|');
+            RedirectOp::Output
+        };
+
+        self.tokens.push(Token::Redirect(fd, op));
+        Ok(())
+    }
+
+    fn read_heredoc_delimiter(
+        &mut self,
+        fd: Option<u32>,
+        strip_tabs: bool,
+    ) -> Result<(), LexError> {
+        while self.chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+
+        while let Some(c) = self.chars.next_if(|c| {
+            !matches!(
+                c,
+                ' ' | '\t' | '\r' | '\n' | '|' | '<' | '>' | '&' | ';' | '(' | ')'
+            )
+        }) {
+            self.read_word_char(c)?;
+        }
+
+        if !self.in_word {
+            return Err(LexError::MissingDelimiter);
+        }
+
+        let raw = std::mem::take(&mut self.word);
+        let literal = raw.contains(['\'', '"', '\\']);
+        let delimiter = unquote(&raw);
+        self.in_word = false;
+
+        self.tokens.push(Token::Redirect(fd, RedirectOp::HereDoc));
+        self.pending_heredocs.push(PendingHereDoc {
+            index: self.tokens.len(),
+            delimiter,
+            strip_tabs,
+            literal,
+        });
+        self.tokens.push(Token::HereDocBody(String::new()));
+
+        Ok(())
+    }
+
+    fn read_heredoc_bodies(&mut self) -> Result<(), LexError> {
+        for heredoc in std::mem::take(&mut self.pending_heredocs) {
+            let mut body = String::new();
+
+            loop {
+                if self.chars.peek().is_none() {
+                    return Err(LexError::Incomplete);
+                }
+
+                let mut line: String = self.chars.by_ref().take_while(|c| *c != '\n').collect();
+                if heredoc.strip_tabs {
+                    line = line.trim_start_matches('\t').to_string();
+                }
+                if line.trim_end_matches('\r') == heredoc.delimiter {
+                    break;
+                }
+
+                body.push_str(&line);
+                body.push('\n');
+            }
+
+            if heredoc.literal {
+                // Escape what expansion would act on so the body comes out unchanged
+                body = body
+                    .replace('\\', "\\\\")
+                    .replace('$', "\\$")
+                    .replace('`', "\\`");
+            }
+
+            self.tokens[heredoc.index] = Token::HereDocBody(body);
+        }
+
+        Ok(())
+    }
+}
+
+// Quote removal: strips quotes and resolves backslash escapes in a raw word
+pub fn unquote(raw: &str) -> String {
+    let mut result = String::new();
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    result.push(next);
+                }
+            }
+            '\'' => {
+                for next in chars.by_ref() {
+                    if next == '\'' {
+                        break;
+                    }
+                    result.push(next);
+                }
+            }
+            '"' => {
+                while let Some(next) = chars.next() {
+                    match next {
+                        '"' => break,
+                        // Inside double quotes a backslash only escapes a few characters
+                        '\\' => match chars.next() {
+                            Some(escaped @ ('"' | '\\' | '$' | '`')) => result.push(escaped),
+                            Some(other) => {
+                                result.push('\\');
+                                result.push(other);
+                            }
+                            None => result.push('\\'),
+                        },
+                        _ => result.push(next),
+                    }
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> Token {
+        Token::Word(text.to_string())
+    }
+
+    #[test]
+    fn splits_words_on_blanks() {
+        assert_eq!(
+            tokenize(" ls\t-l  src\r").unwrap(),
+            vec![word("ls"), word("-l"), word("src")]
+        );
+        assert_eq!(tokenize("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn splits_pipes() {
+        assert_eq!(
+            tokenize("ls|grep x | wc").unwrap(),
+            vec![
+                word("ls"),
+                Token::Pipe,
+                word("grep"),
+                word("x"),
+                Token::Pipe,
+                word("wc"),
+            ]
+        );
+        assert_eq!(
+            tokenize("echo 'a|b'").unwrap(),
+            vec![word("echo"), word("'a|b'")]
+        );
+    }
+
+    #[test]
+    fn splits_command_lists() {
+        assert_eq!(
+            tokenize("a && b || c; (d)\ne").unwrap(),
+            vec![
+                word("a"),
+                Token::And,
+                word("b"),
+                Token::Or,
+                word("c"),
+                Token::Semicolon,
+                Token::LeftParen,
+                word("d"),
+                Token::RightParen,
+                Token::Newline,
+                word("e"),
+            ]
+        );
+        assert_eq!(
+            tokenize("a;b&&c").unwrap(),
+            vec![
+                word("a"),
+                Token::Semicolon,
+                word("b"),
+                Token::And,
+                word("c")
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_case_items_and_skips_comments() {
+        assert_eq!(
+            tokenize("a) b;; # c ;;\nd").unwrap(),
+            vec![
+                word("a"),
+                Token::RightParen,
+                word("b"),
+                Token::DoubleSemicolon,
+                Token::Newline,
+                word("d"),
+            ]
+        );
+        // Only a `#` at the start of a word starts a comment
+        assert_eq!(
+            tokenize("echo a#b '#c'").unwrap(),
+            vec![word("echo"), word("a#b"), word("'#c'")]
+        );
+    }
+
+    #[test]
+    fn splits_background_jobs() {
+        assert_eq!(
+            tokenize("sleep 1&").unwrap(),
+            vec![word("sleep"), word("1"), Token::Ampersand]
+        );
+        assert_eq!(
+            tokenize("a & b &>f").unwrap(),
+            vec![
+                word("a"),
+                Token::Ampersand,
+                word("b"),
+                Token::Redirect(None, RedirectOp::OutputAll),
+                word("f"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_redirections() {
+        assert_eq!(
+            tokenize("cmd 2>&1 >>log <in &>all 3>|x").unwrap(),
+            vec![
+                word("cmd"),
+                Token::Redirect(Some(2), RedirectOp::DupOutput),
+                word("1"),
+                Token::Redirect(None, RedirectOp::Append),
+                word("log"),
+                Token::Redirect(None, RedirectOp::Input),
+                word("in"),
+                Token::Redirect(None, RedirectOp::OutputAll),
+                word("all"),
+                Token::Redirect(Some(3), RedirectOp::Output),
+                word("x"),
+            ]
+        );
+        // Only a word made of digits is a file descriptor
+        assert_eq!(
+            tokenize("a2>f").unwrap(),
+            vec![
+                word("a2"),
+                Token::Redirect(None, RedirectOp::Output),
+                word("f"),
+            ]
+        );
+        assert_eq!(
+            tokenize("cat <<<word").unwrap(),
+            vec![
+                word("cat"),
+                Token::Redirect(None, RedirectOp::HereString),
+                word("word"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_heredoc_bodies() {
+        assert_eq!(
+            tokenize("cat <<EOF\nhello $x\nEOF\n").unwrap(),
+            vec![
+                word("cat"),
+                Token::Redirect(None, RedirectOp::HereDoc),
+                Token::HereDocBody("hello $x\n".to_string()),
+                Token::Newline,
+            ]
+        );
+
+        // <<- strips leading tabs from the body and the delimiter line
+        let tokens = tokenize("cat <<-END\n\tx\n\tEND\n").unwrap();
+        assert_eq!(tokens[2], Token::HereDocBody("x\n".to_string()));
+    }
+
+    #[test]
+    fn keeps_quotes_and_escapes_in_words() {
+        assert_eq!(
+            tokenize(r#"echo 'a b' "c $d" e\ f"#).unwrap(),
+            vec![word("echo"), word("'a b'"), word("\"c $d\""), word(r"e\ f")]
+        );
+        assert_eq!(tokenize("a'b'\"c\"").unwrap(), vec![word("a'b'\"c\"")]);
+        assert_eq!(tokenize("a\\\nb").unwrap(), vec![word("ab")]);
+    }
+
+    #[test]
+    fn keeps_parameter_expansions_in_one_word() {
+        assert_eq!(
+            tokenize("echo $a|${x:-a b} \"${y}\"").unwrap(),
+            vec![
+                word("echo"),
+                word("$a"),
+                Token::Pipe,
+                word("${x:-a b}"),
+                word("\"${y}\""),
+            ]
+        );
+        // A quoted delimiter keeps the body from being expanded
+        let tokens = tokenize("cat <<'END'\n$x `y` \\\nEND\n").unwrap();
+        assert_eq!(
+            tokens[2],
+            Token::HereDocBody("\\$x \\`y\\` \\\\\n".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_command_substitutions_in_one_word() {
+        assert_eq!(
+            tokenize("echo $(ls | wc -l) `a |b` \"$(echo \")\")\"").unwrap(),
+            vec![
+                word("echo"),
+                word("$(ls | wc -l)"),
+                word("`a |b`"),
+                word("\"$(echo \")\")\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unfinished_input() {
+        assert_eq!(tokenize("echo 'a"), Err(LexError::UnterminatedQuote('\'')));
+        assert_eq!(tokenize("echo \"a"), Err(LexError::UnterminatedQuote('"')));
+        assert_eq!(tokenize("echo a\\"), Err(LexError::Incomplete));
+        assert_eq!(tokenize("echo `a"), Err(LexError::UnterminatedQuote('`')));
+        assert_eq!(tokenize("echo $(ls"), Err(LexError::Incomplete));
+        assert_eq!(tokenize("echo a\\\n"), Err(LexError::Incomplete));
+        assert_eq!(tokenize("cat <<EOF\nbody\n"), Err(LexError::Incomplete));
+        assert_eq!(tokenize("cat << |"), Err(LexError::MissingDelimiter));
+    }
+
+    #[test]
+    fn removes_quotes() {
+        assert_eq!(unquote(r#"'a b'"c d"e\ f"#), "a bc de f");
+        assert_eq!(unquote("'\\'"), "\\");
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{builtins, stdio::Io};
+
+const USAGE: &str = "Usage: ln [-sfinvrT] TARGET... [LINK_NAME | -t DIRECTORY]";
+
+// ln [OPTION]... TARGET [LINK_NAME] makes a hard link, or a symbolic one with -s. With
+// a directory as the last operand (or -t) each target is linked there under its own name,
+// and a single operand is linked into the current directory.
+pub fn link(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut symbolic = false;
+    let mut force = false;
+    let mut interactive = false;
+    let mut no_dereference = false;
+    let mut verbose = false;
+    let mut relative = false;
+    let mut no_target_directory = false;
+    let mut target_directory = None;
+    let mut operands = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--" => {
+                operands.extend(args.by_ref().copied());
+                break;
+            }
+            "--symbolic" => symbolic = true,
+            "--force" => {
+                force = true;
+                interactive = false;
+            }
+            "--interactive" => {
+                interactive = true;
+                force = false;
+            }
+            "--no-dereference" => no_dereference = true,
+            "--verbose" => verbose = true,
+            "--relative" => relative = true,
+            "--no-target-directory" => no_target_directory = true,
+            _ if arg.starts_with("--target-directory=") => {
+                target_directory = Some(arg["--target-directory=".len()..].to_string());
+            }
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "ln: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        's' => symbolic = true,
+                        'f' => {
+                            force = true;
+                            interactive = false;
+                        }
+                        'i' => {
+                            interactive = true;
+                            force = false;
+                        }
+                        'n' => no_dereference = true,
+                        'v' => verbose = true,
+                        'r' => relative = true,
+                        'T' => no_target_directory = true,
+                        't' => {
+                            let rest = &arg[2 + i..];
+                            let value = match rest.is_empty() {
+                                true => args.next().map(|value| value.to_string()),
+                                false => Some(rest.to_string()),
+                            };
+                            let Some(value) = value else {
+                                writeln!(io.stderr, "ln: option requires an argument -- 't'")?;
+                                writeln!(io.stderr, "{}", USAGE)?;
+                                return Ok(2);
+                            };
+                            target_directory = Some(value);
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "ln: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => operands.push(*arg),
+        }
+    }
+
+    if relative && !symbolic {
+        writeln!(io.stderr, "ln: cannot do --relative without --symbolic")?;
+        return Ok(2);
+    }
+    if operands.is_empty() {
+        writeln!(io.stderr, "ln: missing file operand")?;
+        writeln!(io.stderr, "{}", USAGE)?;
+        return Ok(2);
+    }
+
+    // Pairs of (target, link name) as the user wrote them
+    let mut links = Vec::new();
+    let directory = match &target_directory {
+        Some(directory) => Some(directory.as_str()),
+        None if operands.len() == 1 => Some("."),
+        None => {
+            let last = operands[operands.len() - 1];
+            let path = builtins::resolve_path(current_dir, last).unwrap_or_default();
+            // -n keeps a symlink to a directory from counting as the directory
+            let is_directory = match no_dereference && path.is_symlink() {
+                true => false,
+                false => path.is_dir(),
+            };
+            if !no_target_directory && is_directory {
+                operands.pop();
+                Some(last)
+            } else if operands.len() > 2 {
+                writeln!(io.stderr, "ln: target '{}' is not a directory", last)?;
+                return Ok(1);
+            } else {
+                links.push((operands[0], last.to_string()));
+                None
+            }
+        }
+    };
+    if let Some(directory) = directory {
+        for target in &operands {
+            let name = builtins::file_name(target);
+            let link_name = match directory {
+                "." if target_directory.is_none() => name,
+                _ => format!("{}/{}", directory.trim_end_matches('/'), name),
+            };
+            links.push((target, link_name));
+        }
+    }
+
+    let mut status = 0;
+    for (target, link_name) in links {
+        let (Some(target_path), Some(link_path)) = (
+            builtins::resolve_path(current_dir, target),
+            builtins::resolve_path(current_dir, &link_name),
+        ) else {
+            writeln!(io.stderr, "ln: Could not determine home directory")?;
+            status = 1;
+            continue;
+        };
+        let kind = if symbolic {
+            "symbolic link"
+        } else {
+            "hard link"
+        };
+
+        if !symbolic && target_path.is_dir() {
+            writeln!(
+                io.stderr,
+                "ln: '{}': hard link not allowed for directory",
+                target
+            )?;
+            status = 1;
+            continue;
+        }
+
+        // What the link will point to, relative to where the link is with -r
+        let link_target = match relative {
+            true => relative_path(&link_path, &target_path),
+            false => PathBuf::from(target),
+        };
+
+        if fs::symlink_metadata(&link_path).is_ok() {
+            if fs::canonicalize(&target_path).ok() == fs::canonicalize(&link_path).ok() && !symbolic
+            {
+                writeln!(
+                    io.stderr,
+                    "ln: '{}' and '{}' are the same file",
+                    target, link_name
+                )?;
+                status = 1;
+                continue;
+            }
+            if interactive && !builtins::confirm(&format!("ln: replace '{}'? ", link_name), io)? {
+                continue;
+            }
+            if force || interactive {
+                let removed = match link_path.is_dir() && !link_path.is_symlink() {
+                    true => Err(io::Error::from(io::ErrorKind::IsADirectory)),
+                    false => fs::remove_file(&link_path),
+                };
+                if let Err(e) = removed {
+                    writeln!(io.stderr, "ln: cannot remove '{}': {}", link_name, e)?;
+                    status = 1;
+                    continue;
+                }
+            }
+        }
+
+        let result = match symbolic {
+            true => symlink(&link_target, &link_path),
+            false => fs::hard_link(&target_path, &link_path),
+        };
+        match result {
+            Ok(()) => {
+                if verbose {
+                    let arrow = if symbolic { "->" } else { "=>" };
+                    writeln!(
+                        io.stdout,
+                        "'{}' {} '{}'",
+                        link_name,
+                        arrow,
+                        link_target.display()
+                    )?;
+                }
+            }
+            Err(e) => {
+                writeln!(
+                    io.stderr,
+                    "ln: failed to create {} '{}': {}",
+                    kind, link_name, e
+                )?;
+                status = 1;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+// Makes link point to target, which is taken relative to the link's directory
+#[cfg(unix)]
+pub fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+// Windows has separate links for files and directories
+#[cfg(windows)]
+pub fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    let resolved = link.parent().unwrap_or(Path::new("")).join(target);
+    match resolved.is_dir() {
+        true => std::os::windows::fs::symlink_dir(target, link),
+        false => std::os::windows::fs::symlink_file(target, link),
+    }
+}
+
+// The path from the directory of link to target, both made absolute first
+fn relative_path(link: &Path, target: &Path) -> PathBuf {
+    let absolute = |path: &Path| -> PathBuf {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => fs::canonicalize(parent)
+                .map(|parent| parent.join(name))
+                .unwrap_or_else(|_| path.to_path_buf()),
+            _ => path.to_path_buf(),
+        }
+    };
+    let link = absolute(link);
+    let target = absolute(target);
+    let from: Vec<Component> = link
+        .parent()
+        .unwrap_or(Path::new("/"))
+        .components()
+        .collect();
+    let to: Vec<Component> = target.components().collect();
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn ln(dir: &TempDir, args: &[&str]) -> testing::Run {
+        testing::run("", |io| link(dir.path(), args, io))
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let dir = TempDir::new("ln-arguments");
+        let run = ln(&dir, &["-x", "a"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("ln: invalid option -- 'x'\n{}\n", USAGE)
+        );
+        assert!(
+            ln(&dir, &[])
+                .stderr
+                .starts_with("ln: missing file operand\n")
+        );
+        let run = ln(&dir, &["-r", "a", "b"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(run.stderr, "ln: cannot do --relative without --symbolic\n");
+    }
+
+    #[test]
+    fn makes_hard_and_symbolic_links() {
+        let dir = TempDir::new("ln-links");
+        dir.write("a", "a");
+        dir.write("d/f", "f");
+
+        assert_eq!(ln(&dir, &["a", "hard"]).status, 0);
+        assert_eq!(dir.read("hard"), "a");
+        let run = ln(&dir, &["d", "e"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stderr, "ln: 'd': hard link not allowed for directory\n");
+
+        let run = ln(&dir, &["-sv", "a", "soft"]);
+        assert_eq!((run.status, run.stdout.as_str()), (0, "'soft' -> 'a'\n"));
+        assert_eq!(fs::read_link(dir.join("soft")).unwrap(), Path::new("a"));
+
+        let run = ln(&dir, &["-s", "d", "soft"]);
+        assert_eq!(run.status, 1);
+        assert!(
+            run.stderr
+                .starts_with("ln: failed to create symbolic link 'soft': ")
+        );
+        assert_eq!(ln(&dir, &["-sf", "d", "soft"]).status, 0);
+        assert_eq!(fs::read_link(dir.join("soft")).unwrap(), Path::new("d"));
+
+        assert_eq!(ln(&dir, &["-sr", "a", "d"]).status, 0);
+        assert_eq!(fs::read_link(dir.join("d/a")).unwrap(), Path::new("../a"));
+        assert_eq!(dir.read("d/a"), "a");
+    }
+
+    #[test]
+    fn finds_relative_paths() {
+        let dir = TempDir::new("ln-relative");
+        dir.write("a/b/c", "c");
+        dir.write("x/y", "y");
+        let relative = |link: &str, target: &str| relative_path(&dir.join(link), &dir.join(target));
+        assert_eq!(relative("a/link", "x/y"), Path::new("../x/y"));
+        assert_eq!(relative("a/b/link", "x/y"), Path::new("../../x/y"));
+        assert_eq!(relative("link", "a/b/c"), Path::new("a/b/c"));
+        assert_eq!(relative("a/b/link", "a/b"), Path::new("."));
+    }
+}
@@ -0,0 +1,1066 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs::{self, Metadata},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{builtins, signals, stdio::Io, timing, variables::Variables};
+
+// Colors GNU ls uses when LS_COLORS is not set
+const DEFAULT_COLORS: &str = "rs=0:di=01;34:ln=01;36:pi=40;33:so=01;35:do=01;35:bd=40;33;01:\
+    cd=40;33;01:or=40;31;01:su=37;41:sg=30;43:tw=30;42:ow=34;42:st=37;44:ex=01;32";
+
+// Files older than this (about six months) show the year instead of the time of day
+const RECENT_SECONDS: i64 = 15_778_476;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Sort {
+    Name,
+    Time,
+    Size,
+    Extension,
+    // Directory order, as read_dir returns it
+    None,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Long,
+    Columns,
+    OnePerLine,
+}
+
+struct Options {
+    // -a shows every entry including . and .., -A all but those two
+    all: bool,
+    almost_all: bool,
+    format: Format,
+    human: bool,
+    recursive: bool,
+    sort: Sort,
+    reverse: bool,
+    // -d lists directories themselves instead of what they contain
+    directory: bool,
+    // -F adds / * @ | = after names by type
+    classify: bool,
+    // -n shows user and group ids instead of names
+    numeric: bool,
+    group_directories_first: bool,
+    colors: Option<Colors>,
+    width: usize,
+}
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    // The entry itself, links not followed
+    metadata: Metadata,
+    // What a symlink points to, None when it is broken
+    target: Option<Metadata>,
+}
+
+impl Entry {
+    fn new(name: String, path: PathBuf) -> io::Result<Entry> {
+        let metadata = fs::symlink_metadata(&path)?;
+        let target = if metadata.file_type().is_symlink() {
+            fs::metadata(&path).ok()
+        } else {
+            None
+        };
+        Ok(Entry {
+            name,
+            path,
+            metadata,
+            target,
+        })
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.metadata.file_type().is_symlink()
+    }
+
+    // Directories and links to them
+    fn is_dir(&self) -> bool {
+        self.metadata.is_dir() || self.target.as_ref().is_some_and(|t| t.is_dir())
+    }
+
+    fn modified(&self) -> i64 {
+        seconds(&self.metadata)
+    }
+}
+
+// ls [-aAlhRtSrXU1CdFn] [--color[=WHEN]] [--sort=WORD] [--group-directories-first]
+// [FILE]... lists files and the contents of directories
+pub fn list_directory(
+    current_dir: &Path,
+    variables: &Variables,
+    args: &[&str],
+    io: &mut Io,
+) -> io::Result<i32> {
+    let terminal = io.stdout.is_terminal();
+    let mut options = Options {
+        all: false,
+        almost_all: false,
+        format: if terminal {
+            Format::Columns
+        } else {
+            Format::OnePerLine
+        },
+        human: false,
+        recursive: false,
+        sort: Sort::Name,
+        reverse: false,
+        directory: false,
+        classify: false,
+        numeric: false,
+        group_directories_first: false,
+        colors: None,
+        width: terminal_width(variables, terminal),
+    };
+    let mut color = terminal;
+    let mut operands = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if *arg == "--" {
+            operands.extend(args.by_ref());
+            break;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            match (name, value) {
+                ("all", None) => options.all = true,
+                ("almost-all", None) => options.almost_all = true,
+                ("human-readable", None) => options.human = true,
+                ("recursive", None) => options.recursive = true,
+                ("reverse", None) => options.reverse = true,
+                ("directory", None) => options.directory = true,
+                ("classify", None) => options.classify = true,
+                ("numeric-uid-gid", None) => {
+                    options.numeric = true;
+                    options.format = Format::Long;
+                }
+                ("group-directories-first", None) => options.group_directories_first = true,
+                ("color" | "colour", None | Some("always" | "yes" | "force")) => color = true,
+                ("color" | "colour", Some("never" | "no" | "none")) => color = false,
+                ("color" | "colour", Some("auto" | "tty" | "if-tty")) => color = terminal,
+                ("sort", Some(word)) => {
+                    options.sort = match word {
+                        "name" => Sort::Name,
+                        "time" => Sort::Time,
+                        "size" => Sort::Size,
+                        "extension" => Sort::Extension,
+                        "none" => Sort::None,
+                        _ => {
+                            writeln!(io.stderr, "ls: invalid argument '{}' for '--sort'", word)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                _ => {
+                    writeln!(io.stderr, "ls: unrecognized option '{}'", arg)?;
+                    return Ok(2);
+                }
+            }
+        } else if let Some(flags) = arg.strip_prefix('-')
+            && !flags.is_empty()
+        {
+            for flag in flags.chars() {
+                match flag {
+                    'a' => options.all = true,
+                    'A' => options.almost_all = true,
+                    'l' => options.format = Format::Long,
+                    'n' => {
+                        options.numeric = true;
+                        options.format = Format::Long;
+                    }
+                    '1' => options.format = Format::OnePerLine,
+                    'C' => options.format = Format::Columns,
+                    'h' => options.human = true,
+                    'R' => options.recursive = true,
+                    't' => options.sort = Sort::Time,
+                    'S' => options.sort = Sort::Size,
+                    'X' => options.sort = Sort::Extension,
+                    'U' => options.sort = Sort::None,
+                    'r' => options.reverse = true,
+                    'd' => options.directory = true,
+                    'F' => options.classify = true,
+                    _ => {
+                        writeln!(io.stderr, "ls: invalid option -- '{}'", flag)?;
+                        writeln!(
+                            io.stderr,
+                            "Usage: ls [-aAlhRtSrXU1CdFn] [--color[=WHEN]] [--sort=WORD] [FILE]..."
+                        )?;
+                        return Ok(2);
+                    }
+                }
+            }
+        } else {
+            operands.push(*arg);
+        }
+    }
+
+    if color {
+        let spec = variables.get("LS_COLORS").unwrap_or(DEFAULT_COLORS);
+        options.colors = Some(Colors::parse(spec));
+    }
+
+    if operands.is_empty() {
+        operands.push(".");
+    }
+
+    // Files named on the command line are listed first, then each directory
+    let mut status = 0;
+    let mut files = Vec::new();
+    let mut directories = Vec::new();
+    for operand in &operands {
+        let Some(path) = builtins::resolve_path(current_dir, operand) else {
+            writeln!(io.stderr, "ls: Could not determine home directory")?;
+            status = 2;
+            continue;
+        };
+        let entry = match Entry::new(operand.to_string(), path) {
+            Ok(entry) => entry,
+            Err(e) => {
+                writeln!(io.stderr, "ls: cannot access '{}': {}", operand, e)?;
+                status = 2;
+                continue;
+            }
+        };
+
+        // A link to a directory on the command line is followed, unless it is
+        // shown in long format where the link itself is of interest
+        let follow = !entry.is_symlink() || options.format != Format::Long;
+        if !options.directory && entry.is_dir() && follow {
+            directories.push(entry);
+        } else {
+            files.push(entry);
+        }
+    }
+
+    sort_entries(&mut files, &options);
+    sort_entries(&mut directories, &options);
+
+    let mut cache = Names::default();
+    let mut first = true;
+    if !files.is_empty() {
+        print_entries(&files, &options, &mut cache, false, io)?;
+        first = false;
+    }
+
+    let headers = operands.len() > 1 || options.recursive;
+    for directory in &directories {
+        if !first {
+            writeln!(io.stdout)?;
+        }
+        first = false;
+        let listed = list_one(
+            directory,
+            &directory.name,
+            headers,
+            &options,
+            &mut cache,
+            io,
+        );
+        match listed {
+            Ok(listed) => status = status.max(listed),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(128 + signals::SIGINT),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(status)
+}
+
+// Lists the contents of one directory, shown as display, and with -R everything below
+fn list_one(
+    directory: &Entry,
+    display: &str,
+    header: bool,
+    options: &Options,
+    cache: &mut Names,
+    io: &mut Io,
+) -> io::Result<i32> {
+    // -R on a large tree should stop on Ctrl-C
+    if signals::interrupt_pending() {
+        return Err(io::ErrorKind::Interrupted.into());
+    }
+    if header {
+        writeln!(io.stdout, "{}:", display)?;
+    }
+
+    let reader = match fs::read_dir(&directory.path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            writeln!(io.stderr, "ls: cannot open directory '{}': {}", display, e)?;
+            return Ok(2);
+        }
+    };
+
+    let mut status = 0;
+    let mut entries = Vec::new();
+    if options.all {
+        for name in [".", ".."] {
+            if let Ok(entry) = Entry::new(name.to_string(), directory.path.join(name)) {
+                entries.push(entry);
+            }
+        }
+    }
+    for dir_entry in reader {
+        let dir_entry = match dir_entry {
+            Ok(dir_entry) => dir_entry,
+            Err(e) => {
+                writeln!(io.stderr, "ls: reading directory '{}': {}", display, e)?;
+                status = 1;
+                continue;
+            }
+        };
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') && !options.all && !options.almost_all {
+            continue;
+        }
+        match Entry::new(name, dir_entry.path()) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                writeln!(
+                    io.stderr,
+                    "ls: cannot access '{}': {}",
+                    dir_entry.path().display(),
+                    e
+                )?;
+                status = 1;
+            }
+        }
+    }
+
+    sort_entries(&mut entries, options);
+    print_entries(&entries, options, cache, true, io)?;
+
+    if options.recursive {
+        let prefix = display.trim_end_matches('/');
+        for entry in &entries {
+            // Links are not followed, they could lead back up the tree
+            if entry.name == "." || entry.name == ".." || !entry.metadata.is_dir() {
+                continue;
+            }
+            writeln!(io.stdout)?;
+            let display = format!("{}/{}", prefix, entry.name);
+            let listed = list_one(entry, &display, true, options, cache, io)?;
+            status = status.max(listed);
+        }
+    }
+
+    Ok(status)
+}
+
+fn sort_entries(entries: &mut [Entry], options: &Options) {
+    if options.sort != Sort::None {
+        entries.sort_by(|a, b| {
+            let order = match options.sort {
+                // Newest and biggest first, like GNU ls
+                Sort::Time => b.modified().cmp(&a.modified()),
+                Sort::Size => b.metadata.len().cmp(&a.metadata.len()),
+                Sort::Extension => extension(&a.name).cmp(extension(&b.name)),
+                Sort::Name | Sort::None => Ordering::Equal,
+            };
+            order.then_with(|| a.name.cmp(&b.name))
+        });
+        if options.reverse {
+            entries.reverse();
+        }
+    }
+    if options.group_directories_first {
+        // A stable sort keeps the order within both groups
+        entries.sort_by_key(|entry| !entry.is_dir());
+    }
+}
+
+fn extension(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => &name[dot + 1..],
+        _ => "",
+    }
+}
+
+fn print_entries(
+    entries: &[Entry],
+    options: &Options,
+    cache: &mut Names,
+    total: bool,
+    io: &mut Io,
+) -> io::Result<()> {
+    match options.format {
+        Format::Long => print_long(entries, options, cache, total, io),
+        Format::OnePerLine => {
+            for entry in entries {
+                writeln!(io.stdout, "{}", decorated_name(entry, options))?;
+            }
+            Ok(())
+        }
+        Format::Columns => print_columns(entries, options, io),
+    }
+}
+
+// The name in its color, followed by the -F indicator
+fn decorated_name(entry: &Entry, options: &Options) -> String {
+    let mut name = match &options.colors {
+        Some(colors) => colors.paint(&entry.name, entry),
+        None => entry.name.clone(),
+    };
+    if options.classify {
+        name.push_str(indicator(entry));
+    }
+    name
+}
+
+fn indicator(entry: &Entry) -> &'static str {
+    let file_type = entry.metadata.file_type();
+    if file_type.is_dir() {
+        "/"
+    } else if file_type.is_symlink() {
+        "@"
+    } else if is_fifo(&entry.metadata) {
+        "|"
+    } else if is_socket(&entry.metadata) {
+        "="
+    } else if is_executable(&entry.metadata) {
+        "*"
+    } else {
+        ""
+    }
+}
+
+// Fills columns top to bottom, using as few rows as the terminal width allows
+fn print_columns(entries: &[Entry], options: &Options, io: &mut Io) -> io::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = entries
+        .iter()
+        .map(|entry| decorated_name(entry, options))
+        .collect();
+    let widths: Vec<usize> = entries
+        .iter()
+        .map(|entry| {
+            entry.name.width() + usize::from(options.classify && !indicator(entry).is_empty())
+        })
+        .collect();
+
+    const GAP: usize = 2;
+    let mut rows = 1;
+    let column_widths = loop {
+        let columns = widths.len().div_ceil(rows);
+        let column_widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                widths[column * rows..((column + 1) * rows).min(widths.len())]
+                    .iter()
+                    .copied()
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let total: usize = column_widths.iter().sum::<usize>() + GAP * (columns - 1);
+        if total <= options.width || rows >= widths.len() {
+            break column_widths;
+        }
+        rows += 1;
+    };
+
+    for row in 0..rows {
+        let mut line = String::new();
+        for (column, width) in column_widths.iter().enumerate() {
+            let index = column * rows + row;
+            let Some(name) = names.get(index) else {
+                break;
+            };
+            line.push_str(name);
+            // No padding after the last name of a row
+            if names.get(index + rows).is_some() {
+                line.push_str(&" ".repeat(width - widths[index] + GAP));
+            }
+        }
+        writeln!(io.stdout, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn print_long(
+    entries: &[Entry],
+    options: &Options,
+    cache: &mut Names,
+    total: bool,
+    io: &mut Io,
+) -> io::Result<()> {
+    if total {
+        // Disk usage in 1K blocks
+        let blocks: u64 = entries.iter().map(|entry| blocks(&entry.metadata)).sum();
+        let total = if options.human {
+            human_size(blocks * 512)
+        } else {
+            (blocks / 2).to_string()
+        };
+        writeln!(io.stdout, "total {}", total)?;
+    }
+
+    let now = timing::unix_now();
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| {
+            let (user, group) = owner(&entry.metadata);
+            let size = if options.human {
+                human_size(entry.metadata.len())
+            } else {
+                entry.metadata.len().to_string()
+            };
+            [
+                links(&entry.metadata).to_string(),
+                cache.user(user, options.numeric),
+                cache.group(group, options.numeric),
+                size,
+                modified_time(entry.modified(), now),
+            ]
+        })
+        .collect();
+
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.width());
+        }
+    }
+
+    for (entry, [links, user, group, size, time]) in entries.iter().zip(rows) {
+        let mut line = format!(
+            "{} {:>links_width$} {:<user_width$} {:<group_width$} {:>size_width$} {} {}",
+            mode_string(&entry.metadata),
+            links,
+            user,
+            group,
+            size,
+            time,
+            decorated_name(entry, options),
+            links_width = widths[0],
+            user_width = widths[1],
+            group_width = widths[2],
+            size_width = widths[3],
+        );
+        if entry.is_symlink() {
+            let target = fs::read_link(&entry.path)
+                .map(|target| target.display().to_string())
+                .unwrap_or_default();
+            let target = match (&options.colors, &entry.target) {
+                (Some(colors), Some(metadata)) => colors.paint_metadata(&target, metadata),
+                (Some(colors), None) => colors.paint_code(&target, "mi"),
+                (None, _) => target,
+            };
+            line.push_str(" -> ");
+            line.push_str(&target);
+        }
+        writeln!(io.stdout, "{}", line)?;
+    }
+    Ok(())
+}
+
+// Powers of 1024 with one decimal below 10, rounded up like GNU ls -h: 512, 4.0K, 12K, 1.5M
+pub fn human_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64;
+    for unit in UNITS {
+        value /= 1024.0;
+        if value < 10.0 {
+            let rounded = (value * 10.0).ceil() / 10.0;
+            if rounded < 10.0 {
+                return format!("{:.1}{}", rounded, unit);
+            }
+        }
+        let rounded = value.ceil();
+        if rounded < 1024.0 {
+            return format!("{}{}", rounded, unit);
+        }
+    }
+    format!("{}E", value.ceil())
+}
+
+// "Mon DD HH:MM" for the last six months, "Mon DD  YYYY" for anything else
+fn modified_time(seconds: i64, now: i64) -> String {
+    let time = timing::local_time(seconds);
+    let month = timing::MONTHS[time.month as usize - 1];
+    if seconds <= now && now - seconds < RECENT_SECONDS {
+        format!(
+            "{} {:>2} {:02}:{:02}",
+            month, time.day, time.hour, time.minute
+        )
+    } else {
+        format!("{} {:>2}  {}", month, time.day, time.year)
+    }
+}
+
+fn seconds(metadata: &Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .map(|time| match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        })
+        .unwrap_or(0)
+}
+
+// LS_COLORS: `key=SGR` pairs separated by colons. Keys are file types (di, ln, ex, ...)
+// or `*.ext` patterns matched against the end of the name.
+struct Colors {
+    types: HashMap<String, String>,
+    extensions: Vec<(String, String)>,
+}
+
+impl Colors {
+    fn parse(spec: &str) -> Colors {
+        let mut colors = Colors {
+            types: HashMap::new(),
+            extensions: Vec::new(),
+        };
+        for item in spec.split(':') {
+            let Some((key, code)) = item.split_once('=') else {
+                continue;
+            };
+            match key.strip_prefix('*') {
+                Some(suffix) => colors
+                    .extensions
+                    .push((suffix.to_lowercase(), code.to_string())),
+                None => {
+                    colors.types.insert(key.to_string(), code.to_string());
+                }
+            }
+        }
+        colors
+    }
+
+    fn paint(&self, text: &str, entry: &Entry) -> String {
+        if entry.is_symlink() {
+            return match (&entry.target, self.types.get("ln").map(String::as_str)) {
+                (None, _) => self.paint_code(text, "or"),
+                // ln=target colors a link like what it points to
+                (Some(target), Some("target")) => self.paint_metadata(text, target),
+                (Some(_), _) => self.paint_code(text, "ln"),
+            };
+        }
+        self.paint_metadata(text, &entry.metadata)
+    }
+
+    fn paint_metadata(&self, text: &str, metadata: &Metadata) -> String {
+        let key = type_key(metadata);
+        if key == "fi" {
+            let lower = text.to_lowercase();
+            // The last matching pattern wins, as later ones are meant to override
+            if let Some((_, code)) = self
+                .extensions
+                .iter()
+                .rev()
+                .find(|(suffix, _)| lower.ends_with(suffix.as_str()))
+            {
+                return paint(text, code);
+            }
+        }
+        self.paint_code(text, key)
+    }
+
+    fn paint_code(&self, text: &str, key: &str) -> String {
+        match self.types.get(key) {
+            Some(code) if !code.is_empty() && code != "0" && code != "00" => paint(text, code),
+            _ => text.to_string(),
+        }
+    }
+}
+
+fn paint(text: &str, code: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+// LS_COLORS key for what metadata describes, links already followed
+fn type_key(metadata: &Metadata) -> &'static str {
+    let mode = mode_bits(metadata);
+    if metadata.is_dir() {
+        match (mode & 0o1000 != 0, mode & 0o002 != 0) {
+            (true, true) => "tw",
+            (false, true) => "ow",
+            (true, false) => "st",
+            (false, false) => "di",
+        }
+    } else if is_fifo(metadata) {
+        "pi"
+    } else if is_socket(metadata) {
+        "so"
+    } else if is_device(metadata, true) {
+        "bd"
+    } else if is_device(metadata, false) {
+        "cd"
+    } else if mode & 0o4000 != 0 {
+        "su"
+    } else if mode & 0o2000 != 0 {
+        "sg"
+    } else if is_executable(metadata) {
+        "ex"
+    } else {
+        "fi"
+    }
+}
+
+// Looks up user and group names once per listing
+#[derive(Default)]
+struct Names {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Names {
+    fn user(&mut self, id: Option<u32>, numeric: bool) -> String {
+        match id {
+            Some(id) if numeric => id.to_string(),
+            Some(id) => self
+                .users
+                .entry(id)
+                .or_insert_with(|| sys::user_name(id).unwrap_or_else(|| id.to_string()))
+                .clone(),
+            None => "-".to_string(),
+        }
+    }
+
+    fn group(&mut self, id: Option<u32>, numeric: bool) -> String {
+        match id {
+            Some(id) if numeric => id.to_string(),
+            Some(id) => self
+                .groups
+                .entry(id)
+                .or_insert_with(|| sys::group_name(id).unwrap_or_else(|| id.to_string()))
+                .clone(),
+            None => "-".to_string(),
+        }
+    }
+}
+
+// `drwxr-xr-x` and friends
+#[cfg(unix)]
+fn mode_string(metadata: &Metadata) -> String {
+    use std::os::unix::fs::FileTypeExt;
+
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else {
+        '-'
+    };
+
+    let mode = mode_bits(metadata);
+    let mut text = String::from(kind);
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        text.push(if bits & 4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 2 != 0 { 'w' } else { '-' });
+        text.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
+
+#[cfg(not(unix))]
+fn mode_string(metadata: &Metadata) -> String {
+    let kind = if metadata.is_dir() {
+        'd'
+    } else if metadata.file_type().is_symlink() {
+        'l'
+    } else {
+        '-'
+    };
+    let write = if metadata.permissions().readonly() {
+        '-'
+    } else {
+        'w'
+    };
+    format!("{}r{}-r{}-r{}-", kind, write, write, write)
+}
+
+#[cfg(unix)]
+fn mode_bits(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn mode_bits(_metadata: &Metadata) -> u32 {
+    0
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &Metadata) -> bool {
+    metadata.is_file() && mode_bits(metadata) & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_fifo(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_fifo()
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_socket(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_socket()
+}
+
+#[cfg(not(unix))]
+fn is_socket(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_device(metadata: &Metadata, block: bool) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = metadata.file_type();
+    if block {
+        file_type.is_block_device()
+    } else {
+        file_type.is_char_device()
+    }
+}
+
+#[cfg(not(unix))]
+fn is_device(_metadata: &Metadata, _block: bool) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn links(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+fn links(_metadata: &Metadata) -> u64 {
+    1
+}
+
+// Allocated size in 512-byte blocks
+#[cfg(unix)]
+fn blocks(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks()
+}
+
+#[cfg(not(unix))]
+fn blocks(metadata: &Metadata) -> u64 {
+    metadata.len().div_ceil(512)
+}
+
+#[cfg(unix)]
+fn owner(metadata: &Metadata) -> (Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+    (Some(metadata.uid()), Some(metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_metadata: &Metadata) -> (Option<u32>, Option<u32>) {
+    (None, None)
+}
+
+// Width of the terminal, or COLUMNS (80 when unset) when not writing to one
+fn terminal_width(variables: &Variables, terminal: bool) -> usize {
+    if terminal
+        && let Ok((columns, _)) = crossterm::terminal::size()
+        && columns > 0
+    {
+        return columns as usize;
+    }
+    variables
+        .get("COLUMNS")
+        .and_then(|columns| columns.parse().ok())
+        .filter(|columns| *columns > 0)
+        .unwrap_or(80)
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::CStr;
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        let mut buffer = vec![0; 4096];
+        // SAFETY: every pointer refers to live storage of the given size, and the name
+        // is copied out before the buffer goes away
+        unsafe {
+            let mut passwd: libc::passwd = std::mem::zeroed();
+            let mut result = std::ptr::null_mut();
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            );
+            if result.is_null() {
+                return None;
+            }
+            Some(
+                CStr::from_ptr(passwd.pw_name)
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        }
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        let mut buffer = vec![0; 4096];
+        // SAFETY: as for user_name
+        unsafe {
+            let mut group: libc::group = std::mem::zeroed();
+            let mut result = std::ptr::null_mut();
+            libc::getgrgid_r(
+                gid,
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            );
+            if result.is_null() {
+                return None;
+            }
+            Some(CStr::from_ptr(group.gr_name).to_string_lossy().into_owned())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    pub fn user_name(_uid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn group_name(_gid: u32) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn ls(dir: &TempDir, args: &[&str]) -> testing::Run {
+        testing::run("", |io| {
+            list_directory(dir.path(), &Variables::default(), args, io)
+        })
+    }
+
+    fn sample(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        dir.write("b.txt", "bbb");
+        dir.write("a.rs", "aaaaaaaaaa");
+        dir.write("c", "");
+        dir.write(".hidden", "");
+        dir.write("sub/inner", "x");
+        dir
+    }
+
+    #[test]
+    fn sizes_are_rounded_up_like_gnu_ls() {
+        assert_eq!(human_size(0), "0");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(10 * 1024 - 1), "10K");
+        assert_eq!(human_size(1536 * 1024), "1.5M");
+        // Rounding up can carry into the next unit
+        assert_eq!(human_size(1024 * 1024 * 1024 - 1), "1.0G");
+    }
+
+    #[test]
+    fn extensions_skip_leading_dots() {
+        assert_eq!(extension("a.tar.gz"), "gz");
+        assert_eq!(extension(".bashrc"), "");
+        assert_eq!(extension("Makefile"), "");
+    }
+
+    #[test]
+    fn lists_and_sorts_entries() {
+        let dir = sample("ls-sort");
+        assert_eq!(ls(&dir, &[]).stdout, "a.rs\nb.txt\nc\nsub\n");
+        assert_eq!(ls(&dir, &["-A"]).stdout, ".hidden\na.rs\nb.txt\nc\nsub\n");
+        assert_eq!(
+            ls(&dir, &["-a"]).stdout,
+            ".\n..\n.hidden\na.rs\nb.txt\nc\nsub\n"
+        );
+        assert_eq!(ls(&dir, &["-r"]).stdout, "sub\nc\nb.txt\na.rs\n");
+        assert_eq!(
+            ls(&dir, &["-S", "a.rs", "b.txt", "c"]).stdout,
+            "a.rs\nb.txt\nc\n"
+        );
+        assert_eq!(ls(&dir, &["-X"]).stdout, "c\nsub\na.rs\nb.txt\n");
+        assert_eq!(
+            ls(&dir, &["--group-directories-first"]).stdout,
+            "sub\na.rs\nb.txt\nc\n"
+        );
+        assert_eq!(ls(&dir, &["-F", "sub", "c"]).stdout, "c\n\nsub:\ninner\n");
+        assert_eq!(ls(&dir, &["-dF", "sub"]).stdout, "sub/\n");
+    }
+
+    #[test]
+    fn lists_recursively() {
+        let dir = sample("ls-recursive");
+        assert_eq!(ls(&dir, &["-R", "sub"]).stdout, "sub:\ninner\n");
+        assert_eq!(
+            ls(&dir, &["-R"]).stdout,
+            ".:\na.rs\nb.txt\nc\nsub\n\n./sub:\ninner\n"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_options_and_missing_files() {
+        let dir = sample("ls-errors");
+        let run = ls(&dir, &["-Q"]);
+        assert_eq!(run.status, 2);
+        assert!(
+            run.stderr
+                .starts_with("ls: invalid option -- 'Q'\nUsage: ls")
+        );
+        assert_eq!(ls(&dir, &["--sort=color"]).status, 2);
+        assert_eq!(ls(&dir, &["--bogus"]).status, 2);
+
+        let run = ls(&dir, &["missing", "c"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(run.stdout, "c\n");
+        assert!(run.stderr.starts_with("ls: cannot access 'missing': "));
+    }
+}
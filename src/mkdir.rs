@@ -0,0 +1,303 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{builtins, stdio::Io};
+
+const USAGE: &str = "Usage: mkdir [-pv] [-m MODE] DIRECTORY...";
+
+// mkdir [OPTION]... DIRECTORY... creates each directory, whose parent has to exist unless
+// -p is given. With -p a directory that is already there is not an error.
+pub fn make_directory(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut parents = false;
+    let mut verbose = false;
+    let mut mode = None;
+    let mut directories = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match *arg {
+            "--" => {
+                directories.extend(args.by_ref());
+                break;
+            }
+            "--parents" => {
+                parents = true;
+                continue;
+            }
+            "--verbose" => {
+                verbose = true;
+                continue;
+            }
+            "--mode" => None,
+            _ if arg.starts_with("--mode=") => Some(&arg[7..]),
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "mkdir: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                let mut wants_mode = false;
+                let mut value = None;
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'p' => parents = true,
+                        'v' => verbose = true,
+                        // The rest of the word or the next argument is the mode
+                        'm' => {
+                            wants_mode = true;
+                            value = Some(&arg[2 + i..]).filter(|rest| !rest.is_empty());
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "mkdir: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                if !wants_mode {
+                    continue;
+                }
+                value
+            }
+            _ => {
+                directories.push(*arg);
+                continue;
+            }
+        };
+
+        let Some(value) = value.or_else(|| args.next().copied()) else {
+            writeln!(io.stderr, "mkdir: option requires an argument -- 'm'")?;
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        };
+        match parse_mode(value) {
+            Some(parsed) => mode = Some(parsed),
+            None => {
+                writeln!(io.stderr, "mkdir: invalid mode '{}'", value)?;
+                return Ok(1);
+            }
+        }
+    }
+
+    if directories.is_empty() {
+        writeln!(io.stderr, "mkdir: missing operand")?;
+        writeln!(io.stderr, "{}", USAGE)?;
+        return Ok(2);
+    }
+
+    let mut status = 0;
+    for name in directories {
+        // With -p the missing parents come first, as the operand spells them: a, a/b, a/b/c
+        let mut steps: Vec<&Path> = vec![Path::new(name)];
+        if parents {
+            steps = Path::new(name)
+                .ancestors()
+                .filter(|ancestor| !ancestor.as_os_str().is_empty())
+                .collect();
+            steps.reverse();
+        }
+
+        for (i, step) in steps.iter().enumerate() {
+            let last = i + 1 == steps.len();
+            let step_name = step.to_string_lossy();
+            let Some(path) = builtins::resolve_path(current_dir, &step_name) else {
+                writeln!(io.stderr, "mkdir: Could not determine home directory")?;
+                status = 1;
+                break;
+            };
+
+            match fs::create_dir(&path) {
+                Ok(()) => {
+                    if verbose {
+                        writeln!(io.stdout, "mkdir: created directory '{}'", step_name)?;
+                    }
+                    // Parents get the default permissions, only the operand gets -m
+                    if last
+                        && let Some(mode) = mode
+                        && let Err(e) = set_mode(&path, mode)
+                    {
+                        writeln!(
+                            io.stderr,
+                            "mkdir: cannot set permissions of '{}': {}",
+                            step_name, e
+                        )?;
+                        status = 1;
+                    }
+                }
+                Err(e) if parents && e.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => {}
+                Err(e) => {
+                    writeln!(
+                        io.stderr,
+                        "mkdir: cannot create directory '{}': {}",
+                        step_name, e
+                    )?;
+                    status = 1;
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+// Other platforms have no permission bits to set
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+// An octal mode like 755, or a symbolic one like u=rwx,go+rx applied to a=rwx
+fn parse_mode(text: &str) -> Option<u32> {
+    if text.bytes().all(|b| b.is_ascii_digit()) {
+        return u32::from_str_radix(text, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777);
+    }
+
+    let mut mode = 0o777;
+    for clause in text.split(',') {
+        let mut chars = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(&c) = chars.peek() {
+            who |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o0007,
+                'a' => 0o6777,
+                _ => break,
+            };
+            chars.next();
+        }
+        if who == 0 {
+            who = 0o6777;
+        }
+
+        // One or more operators, each with its permissions: u+w-x
+        let mut operator = chars.next().filter(|c| matches!(c, '+' | '-' | '='))?;
+        loop {
+            let mut bits = 0;
+            while let Some(&c) = chars.peek() {
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    // Directories are always searchable, so X is the same as x
+                    'x' | 'X' => 0o111,
+                    's' => 0o6000,
+                    't' => 0o1000,
+                    _ => break,
+                };
+                chars.next();
+            }
+            // The sticky bit belongs to nobody in particular
+            let mask = who | if who & 0o7 != 0 { 0o1000 } else { 0 };
+            match operator {
+                '+' => mode |= bits & mask,
+                '-' => mode &= !(bits & mask),
+                _ => mode = (mode & !mask) | (bits & mask),
+            }
+            match chars.next() {
+                None => break,
+                Some(c @ ('+' | '-' | '=')) => operator = c,
+                Some(_) => return None,
+            }
+        }
+    }
+    Some(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    #[test]
+    fn parses_octal_modes() {
+        assert_eq!(parse_mode("755"), Some(0o755));
+        assert_eq!(parse_mode("0700"), Some(0o700));
+        assert_eq!(parse_mode("1777"), Some(0o1777));
+        assert_eq!(parse_mode("8"), None);
+        assert_eq!(parse_mode("17777"), None);
+    }
+
+    #[test]
+    fn parses_symbolic_modes() {
+        assert_eq!(parse_mode("u=rwx,go=rx"), Some(0o755));
+        assert_eq!(parse_mode("go-rwx"), Some(0o700));
+        assert_eq!(parse_mode("a-w"), Some(0o555));
+        assert_eq!(parse_mode("-w"), Some(0o555));
+        assert_eq!(parse_mode("u=r+w"), Some(0o677));
+        assert_eq!(parse_mode("a="), Some(0));
+        assert_eq!(parse_mode("+t"), Some(0o1777));
+        assert_eq!(parse_mode("u+s"), Some(0o4777));
+        assert_eq!(parse_mode("g"), None);
+        assert_eq!(parse_mode("u=rwq"), None);
+        assert_eq!(parse_mode("u+x,"), None);
+    }
+
+    #[test]
+    fn creates_parents_only_with_p() {
+        let dir = TempDir::new("mkdir");
+        let mkdir = |args: &[&str]| testing::run("", |io| make_directory(dir.path(), args, io));
+
+        let run = mkdir(&["a/b"]);
+        assert_eq!(run.status, 1);
+        assert!(
+            run.stderr
+                .starts_with("mkdir: cannot create directory 'a/b': ")
+        );
+
+        let run = mkdir(&["-pv", "a/b"]);
+        assert_eq!(run.status, 0);
+        assert_eq!(
+            run.stdout,
+            "mkdir: created directory 'a'\nmkdir: created directory 'a/b'\n"
+        );
+        assert!(dir.join("a/b").is_dir());
+
+        // Already there is only an error without -p
+        assert_eq!(mkdir(&["-p", "a/b"]).status, 0);
+        assert_eq!(mkdir(&["a"]).status, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn applies_the_mode_to_the_operand_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("mkdir-mode");
+        let run = testing::run("", |io| {
+            make_directory(dir.path(), &["-p", "-m", "u=rwx,go=", "a/b"], io)
+        });
+        assert_eq!(run.status, 0);
+        let mode = |name: &str| fs::metadata(dir.join(name)).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode("a/b"), 0o700);
+        assert_ne!(mode("a"), 0o700);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let dir = TempDir::new("mkdir-errors");
+        let mkdir = |args: &[&str]| testing::run("", |io| make_directory(dir.path(), args, io));
+
+        let run = mkdir(&["-m", "999", "a"]);
+        assert_eq!(
+            (run.status, run.stderr.as_str()),
+            (1, "mkdir: invalid mode '999'\n")
+        );
+        assert_eq!(mkdir(&["-m"]).status, 2);
+        assert_eq!(mkdir(&["-x", "a"]).status, 2);
+        assert_eq!(mkdir(&[]).status, 2);
+        assert!(!dir.join("a").exists());
+    }
+}
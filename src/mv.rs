@@ -0,0 +1,324 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    builtins,
+    cp::{self, Overwrite, Progress},
+    signals,
+    stdio::{Io, Output},
+};
+
+const USAGE: &str = "Usage: mv [-finvuT] SOURCE... DEST | -t DIRECTORY SOURCE...";
+
+// mv [OPTION]... SOURCE... DEST renames files and directories, or moves them into DEST
+// when that is a directory. Between file systems they are copied and then deleted.
+pub fn move_files(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut overwrite = Overwrite::Always;
+    let mut verbose = false;
+    let mut update = false;
+    let mut no_target_directory = false;
+    let mut target_directory = None;
+    let mut operands = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--" => {
+                operands.extend(args.by_ref().copied());
+                break;
+            }
+            "--force" => overwrite = Overwrite::Force,
+            "--interactive" => overwrite = Overwrite::Ask,
+            "--no-clobber" => overwrite = Overwrite::Never,
+            "--verbose" => verbose = true,
+            "--update" => update = true,
+            "--no-target-directory" => no_target_directory = true,
+            _ if arg.starts_with("--target-directory=") => {
+                target_directory = Some(arg["--target-directory=".len()..].to_string());
+            }
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "mv: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        // The last of -f, -i and -n wins
+                        'f' => overwrite = Overwrite::Force,
+                        'i' => overwrite = Overwrite::Ask,
+                        'n' => overwrite = Overwrite::Never,
+                        'v' => verbose = true,
+                        'u' => update = true,
+                        'T' => no_target_directory = true,
+                        't' => {
+                            let rest = &arg[2 + i..];
+                            let value = match rest.is_empty() {
+                                true => args.next().map(|value| value.to_string()),
+                                false => Some(rest.to_string()),
+                            };
+                            let Some(value) = value else {
+                                writeln!(io.stderr, "mv: option requires an argument -- 't'")?;
+                                writeln!(io.stderr, "{}", USAGE)?;
+                                return Ok(2);
+                            };
+                            target_directory = Some(value);
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "mv: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => operands.push(*arg),
+        }
+    }
+
+    let pairs = match cp::destinations(
+        "mv",
+        current_dir,
+        operands,
+        target_directory.as_deref(),
+        no_target_directory,
+        io,
+    )? {
+        Ok(pairs) => pairs,
+        Err(status) => return Ok(status),
+    };
+
+    let mut status = 0;
+    for (from_name, to_name) in pairs {
+        let (Some(from), Some(to)) = (
+            builtins::resolve_path(current_dir, &from_name),
+            builtins::resolve_path(current_dir, &to_name),
+        ) else {
+            writeln!(io.stderr, "mv: Could not determine home directory")?;
+            status = 1;
+            continue;
+        };
+
+        let metadata = match fs::symlink_metadata(&from) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                writeln!(io.stderr, "mv: cannot stat '{}': {}", from_name, e)?;
+                status = 1;
+                continue;
+            }
+        };
+
+        if let Ok(existing) = fs::symlink_metadata(&to) {
+            if cp::same_file(&from, &to, false) {
+                writeln!(
+                    io.stderr,
+                    "mv: '{}' and '{}' are the same file",
+                    from_name, to_name
+                )?;
+                status = 1;
+                continue;
+            }
+            if metadata.is_dir() && !existing.is_dir() {
+                writeln!(
+                    io.stderr,
+                    "mv: cannot overwrite non-directory '{}' with directory '{}'",
+                    to_name, from_name
+                )?;
+                status = 1;
+                continue;
+            }
+            if !metadata.is_dir() && existing.is_dir() {
+                writeln!(
+                    io.stderr,
+                    "mv: cannot overwrite directory '{}' with non-directory",
+                    to_name
+                )?;
+                status = 1;
+                continue;
+            }
+            if update && cp::is_newer(&existing, &metadata) {
+                continue;
+            }
+            match overwrite {
+                Overwrite::Never => continue,
+                Overwrite::Ask => {
+                    if !builtins::confirm(&format!("mv: overwrite '{}'? ", to_name), io)? {
+                        continue;
+                    }
+                }
+                Overwrite::Always | Overwrite::Force => {}
+            }
+        }
+
+        if metadata.is_dir() && cp::is_inside(&from, &to) {
+            writeln!(
+                io.stderr,
+                "mv: cannot move '{}' to a subdirectory of itself, '{}'",
+                from_name, to_name
+            )?;
+            status = 1;
+            continue;
+        }
+
+        match move_path(&from, &to, Some(&io.stderr)) {
+            Ok(()) => {
+                if verbose {
+                    writeln!(io.stdout, "renamed '{}' -> '{}'", from_name, to_name)?;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(128 + signals::SIGINT),
+            Err(e) => {
+                writeln!(
+                    io.stderr,
+                    "mv: cannot move '{}' to '{}': {}",
+                    from_name, to_name, e
+                )?;
+                status = 1;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+// Renames from to to, or copies it over and deletes it when they are on different file
+// systems. A large copy draws a progress bar on stderr when that is given.
+pub fn move_path(from: &Path, to: &Path, stderr: Option<&Output>) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        result => return result,
+    }
+
+    let mut progress = match stderr {
+        Some(stderr) => Progress::new(stderr, || cp::tree_size(from, false)),
+        None => Progress::none(),
+    };
+    let result = copy_and_remove(from, to, &mut progress);
+    progress.clear();
+    result
+}
+
+// Moves from to to by copying. The copy is made under a temporary name next to to and
+// renamed over it once complete, so a failed or interrupted copy leaves both the source
+// and whatever was at to as they were. The source goes last.
+fn copy_and_remove(from: &Path, to: &Path, progress: &mut Progress) -> io::Result<()> {
+    let is_dir = fs::symlink_metadata(from)?.is_dir();
+    let name = to.file_name().unwrap_or_default().to_string_lossy();
+    let temp = to.with_file_name(format!(".{}.mv{}", name, std::process::id()));
+
+    // rename replaces a file or an empty directory, like it would have done with from
+    let result = cp::copy_all(from, &temp, progress).and_then(|()| fs::rename(&temp, to));
+    if let Err(e) = result {
+        let _ = match is_dir {
+            true => fs::remove_dir_all(&temp),
+            false => fs::remove_file(&temp),
+        };
+        return Err(e);
+    }
+
+    match is_dir {
+        true => fs::remove_dir_all(from),
+        false => fs::remove_file(from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn mv(dir: &TempDir, input: &str, args: &[&str]) -> testing::Run {
+        testing::run(input, |io| move_files(dir.path(), args, io))
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        let dir = TempDir::new("mv-options");
+        let run = mv(&dir, "", &["-x", "a", "b"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("mv: invalid option -- 'x'\n{}\n", USAGE)
+        );
+        assert_eq!(mv(&dir, "", &["-t"]).status, 2);
+    }
+
+    #[test]
+    fn renames_and_moves_into_directories() {
+        let dir = TempDir::new("mv-rename");
+        dir.write("a", "a");
+        dir.write("b", "b");
+        dir.write("d/f", "f");
+
+        let run = mv(&dir, "", &["-n", "a", "b"]);
+        assert_eq!(run.status, 0);
+        assert_eq!((dir.read("a"), dir.read("b")), ("a".into(), "b".into()));
+
+        let run = mv(&dir, "", &["-v", "a", "c"]);
+        assert_eq!(
+            (run.status, run.stdout.as_str()),
+            (0, "renamed 'a' -> 'c'\n")
+        );
+        assert!(!dir.join("a").exists());
+        assert_eq!(dir.read("c"), "a");
+
+        assert_eq!(mv(&dir, "", &["b", "c", "d"]).status, 0);
+        assert_eq!((dir.read("d/b"), dir.read("d/c")), ("b".into(), "a".into()));
+
+        let run = mv(&dir, "", &["d", "d/e"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(
+            run.stderr,
+            "mv: cannot move 'd' to a subdirectory of itself, 'd/e'\n"
+        );
+        let run = mv(&dir, "", &["-T", "d/f", "d"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(
+            run.stderr,
+            "mv: cannot overwrite directory 'd' with non-directory\n"
+        );
+    }
+
+    #[test]
+    fn copies_over_the_destination_only_when_complete() {
+        let dir = TempDir::new("mv-copy");
+        dir.write("a", "new");
+        dir.write("b", "old");
+        copy_and_remove(&dir.join("a"), &dir.join("b"), &mut Progress::none()).unwrap();
+        assert!(!dir.join("a").exists());
+        assert_eq!(dir.read("b"), "new");
+
+        dir.write("d/f", "f");
+        fs::create_dir(dir.join("e")).unwrap();
+        copy_and_remove(&dir.join("d"), &dir.join("e"), &mut Progress::none()).unwrap();
+        assert!(!dir.join("d").exists());
+        assert_eq!(dir.read("e/f"), "f");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_both_sides_when_the_copy_fails() {
+        let dir = TempDir::new("mv-copy-fails");
+        dir.write("d/f", "f");
+        dir.write("e", "old");
+        // A fifo cannot be copied
+        let fifo = dir.join("d/fifo").into_os_string().into_encoded_bytes();
+        let fifo = std::ffi::CString::new(fifo).unwrap();
+        // SAFETY: fifo is a valid C string for the duration of the call
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o644) }, 0);
+
+        let result = copy_and_remove(&dir.join("d"), &dir.join("e"), &mut Progress::none());
+        assert!(result.is_err());
+        assert_eq!(dir.read("d/f"), "f");
+        assert_eq!(dir.read("e"), "old");
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 2, "{:?}", names);
+    }
+}
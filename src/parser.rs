@@ -0,0 +1,841 @@
+use std::{fmt, iter::Peekable, sync::Arc, vec::IntoIter};
+
+use crate::{
+    alias::Aliases,
+    lexer::{self, LexError, RedirectOp, Token},
+    variables,
+};
+
+#[derive(Debug, Clone)]
+pub struct SimpleCommand {
+    // Raw words as produced by the lexer, expanded right before execution
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub fd: Option<u32>,
+    pub op: RedirectOp,
+    // Raw target word, or the body for here-documents
+    pub target: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirect>),
+    // name() compound-command. The body is shared with every shell that defines it.
+    Function(String, Arc<Command>),
+}
+
+#[derive(Debug, Clone)]
+pub enum CompoundCommand {
+    // ( list ) runs in a copy of the shell, so changes made inside do not last
+    Subshell(List),
+    // { list; } runs in the shell itself
+    Group(List),
+    // if/elif conditions with their bodies, then the else part
+    If {
+        branches: Vec<(List, List)>,
+        otherwise: Option<List>,
+    },
+    // until loops as long as the condition fails
+    While {
+        condition: List,
+        body: List,
+        until: bool,
+    },
+    // Without `in` the loop goes over the positional parameters
+    For {
+        name: String,
+        words: Option<Vec<String>>,
+        body: List,
+    },
+    Case {
+        word: String,
+        items: Vec<CaseItem>,
+    },
+}
+
+// pattern | pattern) list ;;
+#[derive(Debug, Clone)]
+pub struct CaseItem {
+    pub patterns: Vec<String>,
+    pub body: List,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+    // `! pipeline` inverts the exit status
+    pub negated: bool,
+    // `time pipeline` reports how long it took
+    pub time: Option<TimeFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    Bash,  // time
+    Posix, // time -p
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    And, // &&
+    Or,  // ||
+}
+
+// Pipelines joined by && and ||, each one runs depending on the status of the last
+#[derive(Debug, Clone)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    // Run as a background job (`cmd &`)
+    pub background: bool,
+}
+
+// Commands separated by `;`, `&` or newlines
+#[derive(Debug, Clone, Default)]
+pub struct List {
+    pub items: Vec<AndOr>,
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    UnexpectedToken(String),
+    // Input ended before the command did (after `|`, `&&`, inside `( )`), more input
+    // is needed
+    Incomplete,
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(fd) = self.fd {
+            write!(f, "{}", fd)?;
+        }
+        if self.op == RedirectOp::HereDoc {
+            // The delimiter is gone by now, only the operator is shown
+            write!(f, "{}", self.op)
+        } else {
+            write!(f, "{} {}", self.op, self.target)
+        }
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = self.words.clone();
+        parts.extend(self.redirects.iter().map(|r| r.to_string()));
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Simple(command) => write!(f, "{}", command),
+            Command::Compound(command, redirects) => {
+                write!(f, "{}", command)?;
+                for redirect in redirects {
+                    write!(f, " {}", redirect)?;
+                }
+                Ok(())
+            }
+            Command::Function(name, body) => write!(f, "{}() {}", name, body),
+        }
+    }
+}
+
+impl fmt::Display for CompoundCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompoundCommand::Subshell(list) => write!(f, "({})", list),
+            CompoundCommand::Group(list) => write!(f, "{{ {}; }}", list),
+            CompoundCommand::If {
+                branches,
+                otherwise,
+            } => {
+                for (i, (condition, body)) in branches.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { "elif" };
+                    write!(f, "{} {}; then {}; ", keyword, condition, body)?;
+                }
+                if let Some(otherwise) = otherwise {
+                    write!(f, "else {}; ", otherwise)?;
+                }
+                write!(f, "fi")
+            }
+            CompoundCommand::While {
+                condition,
+                body,
+                until,
+            } => {
+                let keyword = if *until { "until" } else { "while" };
+                write!(f, "{} {}; do {}; done", keyword, condition, body)
+            }
+            CompoundCommand::For { name, words, body } => {
+                write!(f, "for {}", name)?;
+                if let Some(words) = words {
+                    write!(f, " in {}", words.join(" "))?;
+                }
+                write!(f, "; do {}; done", body)
+            }
+            CompoundCommand::Case { word, items } => {
+                write!(f, "case {} in ", word)?;
+                for item in items {
+                    write!(f, "{}) {};; ", item.patterns.join(" | "), item.body)?;
+                }
+                write!(f, "esac")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time {
+            Some(TimeFormat::Bash) => write!(f, "time ")?,
+            Some(TimeFormat::Posix) => write!(f, "time -p ")?,
+            None => {}
+        }
+        if self.negated {
+            write!(f, "! ")?;
+        }
+        let commands: Vec<String> = self.commands.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", commands.join(" | "))
+    }
+}
+
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (connector, pipeline) in &self.rest {
+            let op = match connector {
+                Connector::And => "&&",
+                Connector::Or => "||",
+            };
+            write!(f, " {} {}", op, pipeline)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, and_or) in self.items.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", and_or)?;
+            if and_or.background {
+                write!(f, " &")?;
+            } else if i + 1 < self.items.len() {
+                write!(f, ";")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken(token) => {
+                write!(f, "syntax error near unexpected token `{}'", token)
+            }
+            ParseError::Incomplete => write!(f, "syntax error: unexpected end of file"),
+        }
+    }
+}
+
+// Whether the input stops in the middle of a command and needs another line
+pub fn is_incomplete(input: &str) -> bool {
+    match lexer::tokenize(input) {
+        Ok(tokens) => matches!(parse(tokens, &Aliases::new()), Err(ParseError::Incomplete)),
+        Err(e) => e == LexError::Incomplete,
+    }
+}
+
+// Tokenizes and parses a whole piece of source, such as a sourced file or a `$(...)`
+pub fn parse_str(input: &str, aliases: &Aliases) -> Result<List, String> {
+    let tokens = lexer::tokenize(input).map_err(|e| format!("syntax error: {}", e))?;
+    parse(tokens, aliases).map_err(|e| e.to_string())
+}
+
+// Aliases are replaced while parsing, wherever a command name is expected
+pub fn parse(tokens: Vec<Token>, aliases: &Aliases) -> Result<List, ParseError> {
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+        aliases,
+        expanding: Vec::new(),
+        check_alias_at: None,
+    };
+
+    let list = parser.list()?;
+    match parser.tokens.next() {
+        None => Ok(list),
+        Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+    }
+}
+
+struct Parser<'a> {
+    tokens: Peekable<IntoIter<Token>>,
+    aliases: &'a Aliases,
+    // Aliases whose text is being parsed, with the number of tokens left after it. An
+    // alias is not expanded again inside its own text, which stops `ls='ls -F'` and
+    // loops like `a='b'`, `b='a'`.
+    expanding: Vec<(String, usize)>,
+    // An alias ending in a blank has the word after it checked for an alias too. This
+    // is the number of tokens left when that word comes up.
+    check_alias_at: Option<usize>,
+}
+
+impl Parser<'_> {
+    // Reads and-or lists up to the end of input or a token that closes the list
+    fn list(&mut self) -> Result<List, ParseError> {
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.at_list_end() {
+                break;
+            }
+
+            let mut and_or = self.and_or()?;
+            match self.tokens.peek() {
+                Some(Token::Semicolon | Token::Newline) => {
+                    self.tokens.next();
+                }
+                Some(Token::Ampersand) => {
+                    self.tokens.next();
+                    and_or.background = true;
+                }
+                _ => {
+                    items.push(and_or);
+                    break;
+                }
+            }
+            items.push(and_or);
+        }
+
+        Ok(List { items })
+    }
+
+    // Lists end with the input, a `)` or `;;`, or a reserved word that closes a compound
+    // command
+    fn at_list_end(&mut self) -> bool {
+        match self.tokens.peek() {
+            None | Some(Token::RightParen | Token::DoubleSemicolon) => true,
+            Some(Token::Word(word)) => matches!(
+                word.as_str(),
+                "}" | "then" | "elif" | "else" | "fi" | "do" | "done" | "esac"
+            ),
+            _ => false,
+        }
+    }
+
+    // Whether the next token is the unquoted word, as reserved words like `{` and `}`
+    // only count where a command starts
+    fn at_word(&mut self, word: &str) -> bool {
+        matches!(self.tokens.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+
+        loop {
+            let connector = match self.tokens.peek() {
+                Some(Token::And) => Connector::And,
+                Some(Token::Or) => Connector::Or,
+                _ => break,
+            };
+            self.tokens.next();
+            self.skip_newlines();
+            rest.push((connector, self.pipeline()?));
+        }
+
+        Ok(AndOr {
+            first,
+            rest,
+            background: false,
+        })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut time = None;
+        if self.at_word("time") {
+            self.tokens.next();
+            time = Some(TimeFormat::Bash);
+            if self.at_word("-p") {
+                self.tokens.next();
+                time = Some(TimeFormat::Posix);
+            }
+        }
+        let negated = self.at_word("!");
+        if negated {
+            self.tokens.next();
+        }
+
+        // `time` on its own times nothing at all
+        if time.is_some()
+            && !negated
+            && (self.at_list_end()
+                || matches!(
+                    self.tokens.peek(),
+                    Some(
+                        Token::Semicolon
+                            | Token::Newline
+                            | Token::Ampersand
+                            | Token::And
+                            | Token::Or
+                    )
+                ))
+        {
+            return Ok(Pipeline {
+                commands: Vec::new(),
+                negated,
+                time,
+            });
+        }
+
+        let mut commands = vec![self.command()?];
+        while self.tokens.next_if_eq(&Token::Pipe).is_some() {
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+
+        Ok(Pipeline {
+            commands,
+            negated,
+            time,
+        })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        self.expand_alias();
+        let compound = match self.tokens.peek() {
+            Some(Token::LeftParen) => {
+                self.tokens.next();
+                let list = self.compound_list()?;
+                self.expect(Token::RightParen)?;
+                CompoundCommand::Subshell(list)
+            }
+            Some(Token::Word(word)) => match word.as_str() {
+                "{" => {
+                    self.tokens.next();
+                    let list = self.compound_list()?;
+                    self.expect_word("}")?;
+                    CompoundCommand::Group(list)
+                }
+                "if" => self.if_command()?,
+                "while" | "until" => self.while_command()?,
+                "for" => self.for_command()?,
+                "case" => self.case_command()?,
+                "function" => {
+                    self.tokens.next();
+                    let name = self.name(is_function_name)?;
+                    if self.tokens.next_if_eq(&Token::LeftParen).is_some() {
+                        self.expect(Token::RightParen)?;
+                    }
+                    return self.function_body(name);
+                }
+                _ => return self.simple_command_or_function(),
+            },
+            _ => return self.simple_command_or_function(),
+        };
+
+        Ok(Command::Compound(compound, self.redirects()?))
+    }
+
+    // Replaces the word about to be read with the tokens of its alias, again and again
+    // while the text starts with another alias
+    fn expand_alias(&mut self) {
+        loop {
+            let remaining = self.tokens.len();
+            self.expanding.retain(|(_, end)| remaining > *end);
+
+            // Quoted or escaped words never match, so `\ls` and `'ls'` skip the alias
+            let Some(Token::Word(word)) = self.tokens.peek() else {
+                return;
+            };
+            if self.expanding.iter().any(|(name, _)| name == word) {
+                return;
+            }
+            let Some(value) = self.aliases.get(word) else {
+                return;
+            };
+            let Ok(mut tokens) = lexer::tokenize(value) else {
+                return;
+            };
+
+            let name = word.clone();
+            self.tokens.next();
+            let end = self.tokens.len();
+            if value.ends_with([' ', '\t']) {
+                self.check_alias_at = Some(end);
+            }
+            tokens.extend(self.tokens.by_ref());
+            self.tokens = tokens.into_iter().peekable();
+            self.expanding.push((name, end));
+        }
+    }
+
+    // A simple command, unless it turns out to be `name()` starting a function
+    fn simple_command_or_function(&mut self) -> Result<Command, ParseError> {
+        let command = self.simple_command()?;
+        if let [name] = command.words.as_slice()
+            && command.redirects.is_empty()
+            && self.tokens.next_if_eq(&Token::LeftParen).is_some()
+        {
+            if !is_function_name(name) {
+                return Err(ParseError::UnexpectedToken("(".to_string()));
+            }
+            self.expect(Token::RightParen)?;
+            return self.function_body(name.clone());
+        }
+        Ok(Command::Simple(command))
+    }
+
+    fn function_body(&mut self, name: String) -> Result<Command, ParseError> {
+        self.skip_newlines();
+        let body = self.command()?;
+        if !matches!(body, Command::Compound(..)) {
+            // Only a compound command can be the body, as in `f() { ...; }`
+            return Err(ParseError::UnexpectedToken(body.to_string()));
+        }
+        Ok(Command::Function(name, Arc::new(body)))
+    }
+
+    // if list; then list; [elif list; then list;]... [else list;] fi
+    fn if_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_word("if")?;
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+
+        loop {
+            let condition = self.compound_list()?;
+            self.expect_word("then")?;
+            branches.push((condition, self.compound_list()?));
+
+            if self.at_word("elif") {
+                self.tokens.next();
+                continue;
+            }
+            if self.at_word("else") {
+                self.tokens.next();
+                otherwise = Some(self.compound_list()?);
+            }
+            self.expect_word("fi")?;
+            break;
+        }
+
+        Ok(CompoundCommand::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    // while list; do list; done, or the same with until
+    fn while_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        let until = self.at_word("until");
+        self.tokens.next();
+        let condition = self.compound_list()?;
+        let body = self.do_group()?;
+        Ok(CompoundCommand::While {
+            condition,
+            body,
+            until,
+        })
+    }
+
+    // for name [in word...]; do list; done
+    fn for_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_word("for")?;
+        let name = self.name(variables::is_valid_name)?;
+        self.skip_newlines();
+
+        let mut words = None;
+        if self.at_word("in") {
+            self.tokens.next();
+            let mut list = Vec::new();
+            while let Some(Token::Word(_)) = self.tokens.peek() {
+                if let Some(Token::Word(word)) = self.tokens.next() {
+                    list.push(word);
+                }
+            }
+            words = Some(list);
+            if self.tokens.next_if_eq(&Token::Semicolon).is_none() {
+                self.expect(Token::Newline)?;
+            }
+        } else {
+            self.tokens.next_if_eq(&Token::Semicolon);
+        }
+
+        self.skip_newlines();
+        let body = self.do_group()?;
+        Ok(CompoundCommand::For { name, words, body })
+    }
+
+    fn do_group(&mut self) -> Result<List, ParseError> {
+        self.expect_word("do")?;
+        let body = self.compound_list()?;
+        self.expect_word("done")?;
+        Ok(body)
+    }
+
+    // case word in [(]pattern [| pattern]...) list ;; ... esac
+    fn case_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_word("case")?;
+        let word = match self.tokens.next() {
+            Some(Token::Word(word)) => word,
+            Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            None => return Err(ParseError::Incomplete),
+        };
+        self.skip_newlines();
+        self.expect_word("in")?;
+        self.skip_newlines();
+
+        let mut items = Vec::new();
+        while !self.at_word("esac") {
+            self.tokens.next_if_eq(&Token::LeftParen);
+            let mut patterns = Vec::new();
+            loop {
+                match self.tokens.next() {
+                    Some(Token::Word(pattern)) => patterns.push(pattern),
+                    Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+                    None => return Err(ParseError::Incomplete),
+                }
+                if self.tokens.next_if_eq(&Token::Pipe).is_none() {
+                    break;
+                }
+            }
+            self.expect(Token::RightParen)?;
+
+            // The body may be empty, and the last `;;` may be left out
+            let body = self.list()?;
+            items.push(CaseItem { patterns, body });
+            if self.tokens.next_if_eq(&Token::DoubleSemicolon).is_none() {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect_word("esac")?;
+
+        Ok(CompoundCommand::Case { word, items })
+    }
+
+    // The variable of a for loop or the name of a function, checked by valid
+    fn name(&mut self, valid: fn(&str) -> bool) -> Result<String, ParseError> {
+        match self.tokens.next() {
+            Some(Token::Word(word)) if valid(&word) => Ok(word),
+            Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+            None => Err(ParseError::Incomplete),
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.tokens.next_if_eq(&Token::Newline).is_some() {}
+    }
+
+    // The body of a compound command, which may not be empty
+    fn compound_list(&mut self) -> Result<List, ParseError> {
+        let list = self.list()?;
+        if list.items.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(list)
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand {
+            words: Vec::new(),
+            redirects: Vec::new(),
+        };
+
+        loop {
+            if self.check_alias_at == Some(self.tokens.len()) {
+                self.check_alias_at = None;
+                self.expand_alias();
+            }
+            match self.tokens.peek() {
+                Some(Token::Word(_)) => {
+                    if let Some(Token::Word(word)) = self.tokens.next() {
+                        command.words.push(word);
+                    }
+                }
+                Some(Token::Redirect(..)) => command.redirects.push(self.redirect()?),
+                _ => break,
+            }
+        }
+
+        if command.words.is_empty() && command.redirects.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(command)
+    }
+
+    fn redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let mut redirects = Vec::new();
+        while matches!(self.tokens.peek(), Some(Token::Redirect(..))) {
+            redirects.push(self.redirect()?);
+        }
+        Ok(redirects)
+    }
+
+    fn redirect(&mut self) -> Result<Redirect, ParseError> {
+        let Some(Token::Redirect(fd, op)) = self.tokens.next() else {
+            unreachable!("redirect() called without a redirect token");
+        };
+        let target = match self.tokens.next() {
+            Some(Token::Word(word)) if op != RedirectOp::HereDoc => word,
+            Some(Token::HereDocBody(body)) if op == RedirectOp::HereDoc => body,
+            Some(other) => return Err(ParseError::UnexpectedToken(other.to_string())),
+            None => return Err(ParseError::UnexpectedToken("newline".to_string())),
+        };
+        Ok(Redirect { fd, op, target })
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        self.expect(Token::Word(word.to_string()))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if self.tokens.next_if_eq(&token).is_some() {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    // Error for the next token, running out of input means the command goes on
+    fn unexpected(&mut self) -> ParseError {
+        match self.tokens.next() {
+            Some(token) => ParseError::UnexpectedToken(token.to_string()),
+            None => ParseError::Incomplete,
+        }
+    }
+}
+
+// Function names are plain words, nothing that needs quoting or expanding
+fn is_function_name(word: &str) -> bool {
+    !word.is_empty()
+        && !word.starts_with(|c: char| c.is_ascii_digit())
+        && word
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses and prints the input back, which shows how it was grouped
+    fn reparse(input: &str) -> String {
+        parse_str(input, &Aliases::new()).unwrap().to_string()
+    }
+
+    fn parse_error(input: &str) -> String {
+        parse_str(input, &Aliases::new()).unwrap_err()
+    }
+
+    #[test]
+    fn parses_lists_and_pipelines() {
+        assert_eq!(reparse("a | b -x && c || d"), "a | b -x && c || d");
+        assert_eq!(reparse("a; b & c\nd"), "a; b & c; d");
+        assert_eq!(reparse("! a | b"), "! a | b");
+        assert_eq!(reparse("time a | b"), "time a | b");
+        assert_eq!(reparse("time -p ! a"), "time -p ! a");
+        assert_eq!(reparse("a >out 2>>err <in"), "a > out 2>> err < in");
+        assert_eq!(reparse("a &&\n\nb"), "a && b");
+        assert_eq!(reparse(""), "");
+    }
+
+    #[test]
+    fn parses_subshells_and_groups() {
+        assert_eq!(reparse("(a; b) >out"), "(a; b) > out");
+        assert_eq!(reparse("{ a; b; }"), "{ a; b; }");
+        assert_eq!(reparse("(a | (b)) && { c; }"), "(a | (b)) && { c; }");
+    }
+
+    #[test]
+    fn parses_compound_commands() {
+        assert_eq!(
+            reparse("if a; then b; elif c; then d; else e; fi"),
+            "if a; then b; elif c; then d; else e; fi"
+        );
+        assert_eq!(reparse("while a\ndo\n  b\ndone"), "while a; do b; done");
+        assert_eq!(reparse("until a; do b; done"), "until a; do b; done");
+        assert_eq!(
+            reparse("for x in 1 2; do echo $x; done"),
+            "for x in 1 2; do echo $x; done"
+        );
+        assert_eq!(reparse("for x; do b; done"), "for x; do b; done");
+        assert_eq!(
+            reparse("case $x in a|b) c;; *) d;; esac"),
+            "case $x in a | b) c;; *) d;; esac"
+        );
+        assert_eq!(reparse("f() { a; }"), "f() { a; }");
+        // Keywords only count in command position
+        assert_eq!(reparse("echo if then fi"), "echo if then fi");
+    }
+
+    #[test]
+    fn replaces_aliases_in_command_position() {
+        let mut aliases = Aliases::new();
+        aliases.insert("ll".to_string(), "ls -l".to_string());
+        aliases.insert("ls".to_string(), "ls -F".to_string());
+        aliases.insert("sudo".to_string(), "sudo ".to_string());
+        let list = parse_str("ll x | echo ll; ls; sudo ll", &aliases).unwrap();
+        // An alias is not expanded again inside its own text, and one ending in a
+        // blank has the next word checked too
+        assert_eq!(
+            list.to_string(),
+            "ls -F -l x | echo ll; ls -F; sudo ls -F -l"
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert!(parse_error("a )").contains("`)'"));
+        assert!(parse_error("| a").contains("`|'"));
+        assert!(parse_error("a && ;").contains("`;'"));
+        assert!(parse_error("{ a }").contains("end of file"));
+        assert!(parse_error("if a; then b; done").contains("`done'"));
+        assert_eq!(
+            parse_error("echo 'a"),
+            format!("syntax error: {}", LexError::UnterminatedQuote('\''))
+        );
+    }
+
+    #[test]
+    fn knows_when_more_input_is_needed() {
+        for input in [
+            "a |",
+            "a &&",
+            "a ||",
+            "(a",
+            "{ a;",
+            "a \\",
+            "cat <<EOF\n",
+            "if a; then",
+            "while a; do b;",
+            "for x in a",
+            "case x in",
+            "f()",
+        ] {
+            assert!(is_incomplete(input), "{:?} should be incomplete", input);
+        }
+        for input in [
+            "a",
+            "a | b",
+            "a )",
+            "(a)",
+            "if a; then b; fi",
+            "echo 'a' \"b\"",
+            "cat <<EOF\nx\nEOF\n",
+        ] {
+            assert!(!is_incomplete(input), "{:?} should be complete", input);
+        }
+    }
+}
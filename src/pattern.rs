@@ -0,0 +1,239 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Shell pattern matching (*, ?, [...]). Each pattern character carries whether it
+// may act as a wildcard; quoted characters only ever match themselves.
+pub type Pattern = [(char, bool)];
+
+pub fn has_wildcards(pattern: &Pattern) -> bool {
+    pattern
+        .iter()
+        .any(|(c, special)| *special && matches!(c, '*' | '?' | '['))
+}
+
+pub fn matches(pattern: &Pattern, text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*` when the rest fails to match
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(('*', true)) => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some(('?', true)) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(('[', true)) => {
+                if let Some((matched, end)) = match_bracket(&pattern[p..], text[t]) {
+                    if matched {
+                        p += end;
+                        t += 1;
+                        continue;
+                    }
+                } else if text[t] == '[' {
+                    // An unterminated bracket is an ordinary character
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+            }
+            Some((c, _)) if *c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match backtrack {
+            Some((star, start)) => {
+                // Let the `*` swallow one more character and try again
+                p = star + 1;
+                t = start + 1;
+                backtrack = Some((star, start + 1));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == ('*', true))
+}
+
+// Matches c against the bracket expression at the start of pattern, returning the
+// result and the length of the expression, or None when it is never closed
+fn match_bracket(pattern: &Pattern, c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some(('!' | '^', true)));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let (start, special) = *pattern.get(i)?;
+        if start == ']' && special && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+
+        // a-z ranges, a trailing `-` is literal
+        if let (Some(('-', _)), Some((end, _))) = (pattern.get(i + 1), pattern.get(i + 2))
+            && *end != ']'
+        {
+            matched |= start <= c && c <= *end;
+            i += 3;
+        } else {
+            matched |= start == c;
+            i += 1;
+        }
+    }
+}
+
+// Pathname expansion: every path matching the pattern, sorted. Relative patterns are
+// matched against current_dir and give relative paths. A `**` component matches any
+// number of directories, and names starting with a dot are only matched by a
+// component that starts with a dot too.
+pub fn glob(current_dir: &Path, pattern: &Pattern) -> Vec<String> {
+    let components: Vec<&Pattern> = pattern.split(|(c, _)| *c == '/').collect();
+
+    let mut paths = Vec::new();
+    if pattern.first().is_some_and(|(c, _)| *c == '/') {
+        glob_in(Path::new("/"), "/", &components[1..], &mut paths);
+    } else {
+        glob_in(current_dir, "", &components, &mut paths);
+    }
+
+    paths.sort();
+    paths
+}
+
+// Matches components against the directory dir, which is spelled prefix in the results
+fn glob_in(dir: &Path, prefix: &str, components: &[&Pattern], paths: &mut Vec<String>) {
+    let Some((component, rest)) = components.split_first() else {
+        return;
+    };
+
+    if !has_wildcards(component) {
+        // Nothing to match, the name just has to exist
+        let name: String = component.iter().map(|(c, _)| c).collect();
+        let path = dir.join(&name);
+        if rest.is_empty() {
+            if fs::symlink_metadata(&path).is_ok() {
+                paths.push(format!("{}{}", prefix, name));
+            }
+        } else if path.is_dir() {
+            glob_in(&path, &format!("{}{}/", prefix, name), rest, paths);
+        }
+        return;
+    }
+
+    let globstar = **component == [('*', true), ('*', true)];
+    if globstar && !rest.is_empty() {
+        // `**/` may stand for no directory at all
+        glob_in(dir, prefix, rest, paths);
+    }
+
+    let dotfiles = component.first().is_some_and(|(c, _)| *c == '.');
+    for (name, path) in entries(dir) {
+        if name.starts_with('.') && !dotfiles {
+            continue;
+        }
+        if globstar {
+            // Symlinked directories are not followed, they could lead back up the tree
+            let is_dir = fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir());
+            if rest.is_empty() {
+                paths.push(format!("{}{}", prefix, name));
+            }
+            if is_dir {
+                glob_in(&path, &format!("{}{}/", prefix, name), components, paths);
+            }
+            continue;
+        }
+
+        let chars: Vec<char> = name.chars().collect();
+        if !matches(component, &chars) {
+            continue;
+        }
+        if rest.is_empty() {
+            paths.push(format!("{}{}", prefix, name));
+        } else if path.is_dir() {
+            glob_in(&path, &format!("{}{}/", prefix, name), rest, paths);
+        }
+    }
+}
+
+fn entries(dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| Some((entry.file_name().into_string().ok()?, entry.path())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character of the text is special, like an unquoted word
+    fn pattern(text: &str) -> Vec<(char, bool)> {
+        text.chars().map(|c| (c, true)).collect()
+    }
+
+    fn is_match(pattern_text: &str, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        matches(&pattern(pattern_text), &text)
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(is_match("*", ""));
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(!is_match("*.rs", "main.rsx"));
+        assert!(is_match("a*b*c", "aXbYbZc"));
+        assert!(!is_match("a*b*c", "aXbYbZ"));
+        assert!(is_match("?", "é"));
+        assert!(!is_match("?", ""));
+        assert!(is_match("a??d", "abcd"));
+        assert!(is_match("**a", "bba"));
+        assert!(is_match("abc", "abc"));
+        assert!(!is_match("abc", "abcd"));
+    }
+
+    #[test]
+    fn matches_brackets() {
+        assert!(is_match("[abc]", "b"));
+        assert!(!is_match("[abc]", "d"));
+        assert!(is_match("[a-c]x", "bx"));
+        assert!(!is_match("[a-c]x", "dx"));
+        assert!(is_match("[!a-c]", "d"));
+        assert!(is_match("[^a-c]", "d"));
+        assert!(!is_match("[!a-c]", "a"));
+        assert!(is_match("[]a]", "]"));
+        assert!(is_match("[!]]", "a"));
+        assert!(!is_match("[!]]", "]"));
+        assert!(is_match("[a-]", "-"));
+        // An unterminated bracket is an ordinary character
+        assert!(is_match("[a", "[a"));
+        assert!(!is_match("[a", "a"));
+    }
+
+    #[test]
+    fn quoted_characters_match_themselves() {
+        let mut quoted = pattern("a*");
+        quoted[1].1 = false;
+        assert!(matches(&quoted, &['a', '*']));
+        assert!(!matches(&quoted, &['a', 'b']));
+        assert!(!has_wildcards(&quoted));
+        assert!(has_wildcards(&pattern("a*")));
+    }
+}
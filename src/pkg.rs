@@ -0,0 +1,582 @@
+use std::{
+    io::{self, Write},
+    process::Command,
+};
+
+use is_root::is_root;
+
+use crate::{
+    platform::{Platform, command_exists},
+    signals,
+    stdio::Io,
+    variables::Variables,
+};
+
+// Subcommands of `pkg`, each also has a short alias
+pub const SUBCOMMANDS: &[&str] = &["install", "search", "update", "list"];
+
+#[derive(Clone)]
+pub struct PackageManager {
+    name: &'static str,
+    install_cmd: &'static str,
+    search_cmd: &'static str,
+    update_cmd: &'static str,
+    is_available: fn() -> bool,
+    platform: Platform,
+}
+
+pub fn package_managers() -> Vec<PackageManager> {
+    vec![
+        // Windows package managers
+        PackageManager {
+            name: "chocolatey",
+            install_cmd: "choco install",
+            search_cmd: "choco search",
+            update_cmd: "choco upgrade",
+            is_available: || command_exists("choco"),
+            platform: Platform::Windows,
+        },
+        PackageManager {
+            name: "winget",
+            install_cmd: "winget install",
+            search_cmd: "winget search",
+            update_cmd: "winget upgrade",
+            is_available: || command_exists("winget"),
+            platform: Platform::Windows,
+        },
+        PackageManager {
+            name: "scoop",
+            install_cmd: "scoop install",
+            search_cmd: "scoop search",
+            update_cmd: "scoop update",
+            is_available: || command_exists("scoop"),
+            platform: Platform::Windows,
+        },
+        // macOS package managers
+        PackageManager {
+            name: "homebrew",
+            install_cmd: "brew install",
+            search_cmd: "brew search",
+            update_cmd: "brew upgrade",
+            is_available: || command_exists("brew"),
+            platform: Platform::MacOS,
+        },
+        PackageManager {
+            name: "macports",
+            install_cmd: "port install",
+            search_cmd: "port search",
+            update_cmd: "port upgrade",
+            is_available: || command_exists("port"),
+            platform: Platform::MacOS,
+        },
+        // Linux package managers
+        PackageManager {
+            name: "apt",
+            install_cmd: "apt install",
+            search_cmd: "apt search",
+            update_cmd: "apt upgrade",
+            is_available: || command_exists("apt"),
+            platform: Platform::Linux,
+        },
+        PackageManager {
+            name: "dnf",
+            install_cmd: "dnf install",
+            search_cmd: "dnf search",
+            update_cmd: "dnf upgrade",
+            is_available: || command_exists("dnf"),
+            platform: Platform::Linux,
+        },
+        PackageManager {
+            name: "pacman",
+            install_cmd: "pacman -S",
+            search_cmd: "pacman -Ss",
+            update_cmd: "pacman -Syu",
+            is_available: || command_exists("pacman"),
+            platform: Platform::Linux,
+        },
+        PackageManager {
+            name: "zypper",
+            install_cmd: "zypper install",
+            search_cmd: "zypper search",
+            update_cmd: "zypper update",
+            is_available: || command_exists("zypper"),
+            platform: Platform::Linux,
+        },
+        // Cross-platform package managers
+        PackageManager {
+            name: "snap",
+            install_cmd: "snap install",
+            search_cmd: "snap find",
+            update_cmd: "snap refresh",
+            is_available: || command_exists("snap"),
+            platform: Platform::Any,
+        },
+        PackageManager {
+            name: "flatpak",
+            install_cmd: "flatpak install",
+            search_cmd: "flatpak search",
+            update_cmd: "flatpak update",
+            is_available: || command_exists("flatpak"),
+            platform: Platform::Any,
+        },
+    ]
+}
+
+pub fn handle_package_command(
+    package_managers: &[PackageManager],
+    args: &[&str],
+    current_platform: &Platform,
+    variables: &Variables,
+    io: &mut Io,
+) -> io::Result<i32> {
+    if args.is_empty() {
+        writeln!(io.stderr, "Usage: pkg <command> [arguments]")?;
+        writeln!(io.stdout, "Commands: {}", SUBCOMMANDS.join(", "))?;
+        return Ok(2);
+    }
+
+    match args[0] {
+        "install" | "i" => {
+            if args.len() < 2 {
+                writeln!(io.stderr, "Usage: pkg install <package>")?;
+                return Ok(2);
+            }
+            let package = args[1];
+            install_package(package_managers, package, current_platform, variables, io)
+        }
+        "search" | "s" => {
+            if args.len() < 2 {
+                writeln!(io.stderr, "Usage: pkg search <query>")?;
+                return Ok(2);
+            }
+            let query = args[1];
+            search_packages(package_managers, query, current_platform, variables, io)
+        }
+        "update" | "u" | "upgrade" => {
+            let package = if args.len() > 1 { Some(args[1]) } else { None };
+            update_packages(package_managers, package, current_platform, variables, io)
+        }
+        "list" | "ls" => {
+            list_package_managers(package_managers, current_platform, io)?;
+            Ok(0)
+        }
+        _ => {
+            writeln!(io.stderr, "Unknown package command: {}", args[0])?;
+            writeln!(io.stdout, "Available commands: {}", SUBCOMMANDS.join(", "))?;
+            Ok(2)
+        }
+    }
+}
+
+fn list_package_managers(
+    package_managers: &[PackageManager],
+    current_platform: &Platform,
+    io: &mut Io,
+) -> io::Result<()> {
+    writeln!(io.stdout, "Available package managers for your platform:")?;
+
+    for pm in package_managers {
+        if pm.platform == *current_platform || pm.platform == Platform::Any {
+            let available = (pm.is_available)();
+            let status = if available {
+                "installed"
+            } else {
+                "not installed"
+            };
+            writeln!(io.stdout, "  {} ({})", pm.name, status)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn install_package(
+    package_managers: &[PackageManager],
+    package: &str,
+    current_platform: &Platform,
+    variables: &Variables,
+    io: &mut Io,
+) -> io::Result<i32> {
+    let mut installed = false;
+
+    let platform_pms: Vec<&PackageManager> = package_managers
+        .iter()
+        .filter(|pm| pm.platform == *current_platform || pm.platform == Platform::Any)
+        .collect();
+
+    for pm in platform_pms {
+        if (pm.is_available)() {
+            writeln!(
+                io.stdout,
+                "Attempting to install {} using {}...",
+                package, pm.name
+            )?;
+
+            let mut cmd_parts = pm.install_cmd.split_whitespace().collect::<Vec<&str>>();
+            cmd_parts.push(package);
+
+            if let Some(cmd_name) = cmd_parts.first() {
+                let mut cmd = Command::new(cmd_name);
+                signals::restore_defaults(&mut cmd);
+                variables.apply_to(&mut cmd);
+                cmd.args(&cmd_parts[1..])
+                    .stdout(io.stdout.to_stdio()?)
+                    .stderr(io.stderr.to_stdio()?)
+                    .stdin(io.stdin.to_stdio()?);
+
+                // On Linux/macOS, use sudo for system package managers if running as non-root
+                if *current_platform == Platform::Linux
+                    || *current_platform == Platform::MacOS
+                        && ["apt", "dnf", "pacman", "zypper", "port"].contains(&pm.name)
+                {
+                    let is_root = is_root();
+
+                    if !is_root {
+                        let mut sudo_cmd = Command::new("sudo");
+                        signals::restore_defaults(&mut sudo_cmd);
+                        variables.apply_to(&mut sudo_cmd);
+                        sudo_cmd
+                            .arg(cmd_name)
+                            .args(&cmd_parts[1..])
+                            .stdout(io.stdout.to_stdio()?)
+                            .stderr(io.stderr.to_stdio()?)
+                            .stdin(io.stdin.to_stdio()?);
+
+                        match sudo_cmd.status() {
+                            Ok(status) => {
+                                if status.success() {
+                                    installed = true;
+                                    writeln!(
+                                        io.stdout,
+                                        "Successfully installed {} using {}",
+                                        package, pm.name
+                                    )?;
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                writeln!(io.stderr, "Failed to execute sudo {}: {}", pm.name, e)?
+                            }
+                        }
+                        continue;
+                    }
+                }
+
+                match cmd.status() {
+                    Ok(status) => {
+                        if status.success() {
+                            installed = true;
+                            writeln!(
+                                io.stdout,
+                                "Successfully installed {} using {}",
+                                package, pm.name
+                            )?;
+                            break;
+                        }
+                    }
+                    Err(e) => writeln!(io.stderr, "Failed to execute {}: {}", pm.name, e)?,
+                }
+            }
+        }
+    }
+
+    if !installed {
+        writeln!(
+            io.stderr,
+            "Failed to install {}. No compatible package manager found or installation failed.",
+            package
+        )?;
+
+        match current_platform {
+            Platform::Windows => {
+                writeln!(
+                    io.stdout,
+                    "You may need to install a package manager first (chocolatey, winget, or scoop)."
+                )?;
+            }
+            Platform::MacOS => {
+                writeln!(
+                    io.stdout,
+                    "You may need to install a package manager first (homebrew or macports)."
+                )?;
+            }
+            Platform::Linux => {
+                writeln!(
+                    io.stdout,
+                    "Your distribution's package manager might not be supported or you may need to run with sudo privileges."
+                )?;
+            }
+            _ => {
+                writeln!(
+                    io.stdout,
+                    "Please install a package manager appropriate for your platform."
+                )?;
+            }
+        }
+    }
+
+    Ok(if installed { 0 } else { 1 })
+}
+
+fn search_packages(
+    package_managers: &[PackageManager],
+    query: &str,
+    current_platform: &Platform,
+    variables: &Variables,
+    io: &mut Io,
+) -> io::Result<i32> {
+    let mut found = false;
+
+    let platform_pms: Vec<&PackageManager> = package_managers
+        .iter()
+        .filter(|pm| pm.platform == *current_platform || pm.platform == Platform::Any)
+        .collect();
+
+    for pm in platform_pms {
+        if (pm.is_available)() {
+            writeln!(io.stdout, "Searching for '{}' using {}...", query, pm.name)?;
+
+            let mut cmd_parts = pm.search_cmd.split_whitespace().collect::<Vec<&str>>();
+            cmd_parts.push(query);
+
+            if let Some(cmd_name) = cmd_parts.first() {
+                let mut cmd = Command::new(cmd_name);
+                signals::restore_defaults(&mut cmd);
+                variables.apply_to(&mut cmd);
+                cmd.args(&cmd_parts[1..])
+                    .stdout(io.stdout.to_stdio()?)
+                    .stderr(io.stderr.to_stdio()?)
+                    .stdin(io.stdin.to_stdio()?);
+
+                match cmd.status() {
+                    Ok(_) => {
+                        found = true;
+                    }
+                    Err(e) => writeln!(io.stderr, "Failed to search with {}: {}", pm.name, e)?,
+                }
+            }
+        }
+    }
+
+    if !found {
+        writeln!(
+            io.stdout,
+            "No compatible package manager found for searching."
+        )?;
+
+        match current_platform {
+            Platform::Windows => {
+                writeln!(
+                    io.stdout,
+                    "You may need to install a package manager first (chocolatey, winget, or scoop)."
+                )?;
+            }
+            Platform::MacOS => {
+                writeln!(
+                    io.stdout,
+                    "You may need to install a package manager first (homebrew or macports)."
+                )?;
+            }
+            Platform::Linux => {
+                writeln!(
+                    io.stdout,
+                    "Your distribution's package manager might not be supported."
+                )?;
+            }
+            _ => {
+                writeln!(
+                    io.stdout,
+                    "Please install a package manager appropriate for your platform."
+                )?;
+            }
+        }
+    }
+
+    Ok(if found { 0 } else { 1 })
+}
+
+fn update_packages(
+    package_managers: &[PackageManager],
+    package: Option<&str>,
+    current_platform: &Platform,
+    variables: &Variables,
+    io: &mut Io,
+) -> io::Result<i32> {
+    let mut updated = false;
+
+    let platform_pms: Vec<&PackageManager> = package_managers
+        .iter()
+        .filter(|pm| pm.platform == *current_platform || pm.platform == Platform::Any)
+        .collect();
+
+    for pm in platform_pms {
+        if (pm.is_available)() {
+            if let Some(pkg) = package {
+                writeln!(io.stdout, "Updating {} using {}...", pkg, pm.name)?;
+
+                let mut cmd_parts = pm.update_cmd.split_whitespace().collect::<Vec<&str>>();
+                cmd_parts.push(pkg);
+
+                if let Some(cmd_name) = cmd_parts.first() {
+                    if (*current_platform == Platform::Linux
+                        || *current_platform == Platform::MacOS)
+                        && ["apt", "dnf", "pacman", "zypper", "port"].contains(&pm.name)
+                    {
+                        let is_root = is_root();
+
+                        if !is_root {
+                            let mut sudo_cmd = Command::new("sudo");
+                            signals::restore_defaults(&mut sudo_cmd);
+                            variables.apply_to(&mut sudo_cmd);
+                            sudo_cmd
+                                .arg(cmd_name)
+                                .args(&cmd_parts[1..])
+                                .stdout(io.stdout.to_stdio()?)
+                                .stdin(io.stdin.to_stdio()?)
+                                .stderr(io.stderr.to_stdio()?);
+
+                            match sudo_cmd.status() {
+                                Ok(status) => {
+                                    if status.success() {
+                                        updated = true;
+                                        writeln!(
+                                            io.stdout,
+                                            "Successfully updated {} using {}",
+                                            pkg, pm.name
+                                        )?;
+                                        break;
+                                    }
+                                }
+                                Err(e) => writeln!(
+                                    io.stderr,
+                                    "Failed to execute sudo {}: {}",
+                                    pm.name, e
+                                )?,
+                            }
+                            continue;
+                        }
+                    }
+
+                    let mut cmd = Command::new(cmd_name);
+
+                    signals::restore_defaults(&mut cmd);
+                    variables.apply_to(&mut cmd);
+                    cmd.args(&cmd_parts[1..])
+                        .stdout(io.stdout.to_stdio()?)
+                        .stdin(io.stdin.to_stdio()?)
+                        .stderr(io.stderr.to_stdio()?);
+
+                    match cmd.status() {
+                        Ok(status) => {
+                            if status.success() {
+                                updated = true;
+                                writeln!(
+                                    io.stdout,
+                                    "Successfully updated {} using {}",
+                                    pkg, pm.name
+                                )?;
+                                break;
+                            }
+                        }
+                        Err(e) => writeln!(io.stderr, "Failed to update with {}: {}", pm.name, e)?,
+                    }
+                }
+            } else {
+                writeln!(io.stdout, "Updating all packages using {}...", pm.name)?;
+
+                let cmd_parts = pm.update_cmd.split_whitespace().collect::<Vec<&str>>();
+
+                if let Some(cmd_name) = cmd_parts.first() {
+                    if (*current_platform == Platform::Linux
+                        || *current_platform == Platform::MacOS)
+                        && ["apt", "dnf", "pacman", "zypper", "port"].contains(&pm.name)
+                    {
+                        let is_root = is_root();
+
+                        if !is_root {
+                            let mut sudo_cmd = Command::new("sudo");
+                            signals::restore_defaults(&mut sudo_cmd);
+                            variables.apply_to(&mut sudo_cmd);
+                            sudo_cmd
+                                .arg(cmd_name)
+                                .args(&cmd_parts[1..])
+                                .stdout(io.stdout.to_stdio()?)
+                                .stdin(io.stdin.to_stdio()?)
+                                .stderr(io.stderr.to_stdio()?);
+
+                            match sudo_cmd.status() {
+                                Ok(_) => {
+                                    updated = true;
+                                }
+                                Err(e) => writeln!(
+                                    io.stderr,
+                                    "Failed to execute sudo {}: {}",
+                                    pm.name, e
+                                )?,
+                            }
+                            continue;
+                        }
+                    }
+                    let mut cmd = Command::new(cmd_name);
+                    signals::restore_defaults(&mut cmd);
+                    variables.apply_to(&mut cmd);
+                    cmd.args(&cmd_parts[1..]);
+                    cmd.stdout(io.stdout.to_stdio()?);
+                    cmd.stdin(io.stdin.to_stdio()?);
+                    cmd.stderr(io.stderr.to_stdio()?);
+
+                    match cmd.status() {
+                        Ok(_) => {
+                            updated = true;
+                        }
+                        Err(e) => writeln!(io.stderr, "Failed to update with {}: {}", pm.name, e)?,
+                    }
+                }
+            }
+        }
+    }
+
+    if !updated {
+        if let Some(pkg) = package {
+            writeln!(
+                io.stderr,
+                "Failed to update {}. No compatible package manager found or update failed.",
+                pkg
+            )?;
+        } else {
+            writeln!(
+                io.stderr,
+                "Failed to update packages. No compatible package manager found or update failed."
+            )?;
+        }
+
+        match current_platform {
+            Platform::Windows => {
+                writeln!(
+                    io.stdout,
+                    "You may need to install a package manager first (chocolatey, winget, or scoop)."
+                )?;
+            }
+            Platform::MacOS => {
+                writeln!(
+                    io.stdout,
+                    "You may need to install a package manager first (homebrew or macports)."
+                )?;
+            }
+            Platform::Linux => {
+                writeln!(
+                    io.stdout,
+                    "Your distribution's package manager might not be supported or you may need to run with sudo privileges."
+                )?;
+            }
+            _ => {
+                writeln!(
+                    io.stdout,
+                    "Please install a package manager appropriate for your platform."
+                )?;
+            }
+        }
+    }
+
+    Ok(if updated { 0 } else { 1 })
+}
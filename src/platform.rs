@@ -0,0 +1,50 @@
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Platform {
+    Windows,
+    Linux,
+    MacOS,
+    Any,
+}
+
+pub fn get_current_platform() -> Platform {
+    if cfg!(target_os = "windows") {
+        Platform::Windows
+    } else if cfg!(target_os = "linux") {
+        Platform::Linux
+    } else if cfg!(target_os = "macos") {
+        Platform::MacOS
+    } else {
+        Platform::Any
+    }
+}
+
+pub fn command_exists(command: &str) -> bool {
+    match get_current_platform() {
+        // On Windows we need to add .exe extension
+        Platform::Windows => Command::new("where")
+            .arg(command)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false),
+        _ => Command::new("which")
+            .arg(command)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false),
+    }
+}
+
+pub fn get_platform_name(platform: &Platform) -> String {
+    match platform {
+        Platform::Windows => "Windows".to_string(),
+        Platform::Linux => "Linux".to_string(),
+        Platform::MacOS => "MacOS".to_string(),
+        Platform::Any => "Any".to_string(),
+    }
+}
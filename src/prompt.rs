@@ -0,0 +1,193 @@
+use std::{env, path::Path};
+
+use colored::Color;
+use is_root::is_root;
+
+use crate::{SHELL_NAME, VERSION, git, shell::Shell, timing};
+
+// The prompts used while PS1 and PS2 are unset
+pub const DEFAULT_PS1: &str = r"\c{cyan}\w\c{yellow}>\c{reset} ";
+pub const DEFAULT_PS2: &str = r"\c{yellow}>\c{reset} ";
+
+// Expands the backslash escapes of a PS1 or PS2 template:
+//   \u user, \h host up to the first dot, \H the whole host name
+//   \w current directory with ~ for home (PROMPT_DIRTRIM=n keeps the last n parts),
+//   \W its last part
+//   \t 24-hour HH:MM:SS, \T 12-hour HH:MM:SS, \A 24-hour HH:MM, \@ 12-hour am/pm,
+//   \d date as in "Tue May 26"
+//   \? status of the last command, \j number of jobs, \! history number
+//   \$ `#` for root and `$` otherwise, \s shell name, \v version
+//   \g git branch with `*` when tracked files changed, nothing outside a repository
+//   \c{color} switches color: the names of colored::Color (red, bright_blue, ...),
+//   on_<color> for the background, bold, dim, italic, underline and reset
+//   \n newline, \e escape character, \\ backslash; \[ and \] are accepted and ignored
+pub fn render(shell: &Shell, template: &str) -> String {
+    let mut prompt = String::new();
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            prompt.push('\\');
+            break;
+        };
+        match escape {
+            'u' => prompt.push_str(&user_name()),
+            'h' => prompt.push_str(host_name().split('.').next().unwrap_or_default()),
+            'H' => prompt.push_str(&host_name()),
+            'w' => prompt.push_str(&working_directory(shell)),
+            'W' => prompt.push_str(&directory_name(shell)),
+            't' | 'T' | 'A' | '@' | 'd' => prompt.push_str(&time(escape)),
+            '?' => prompt.push_str(&shell.last_status.to_string()),
+            'j' => prompt.push_str(&shell.jobs.ids().len().to_string()),
+            '!' => prompt.push_str(&(shell.history.entries().len() + 1).to_string()),
+            '$' => prompt.push(if is_root() { '#' } else { '$' }),
+            's' => prompt.push_str(SHELL_NAME),
+            'v' => prompt.push_str(VERSION),
+            'g' => {
+                if let Some(status) = git::status(&shell.current_dir) {
+                    prompt.push_str(&status.branch);
+                    if status.dirty {
+                        prompt.push('*');
+                    }
+                }
+            }
+            'c' => {
+                let rest = chars.as_str();
+                match rest.strip_prefix('{').and_then(|rest| rest.split_once('}')) {
+                    Some((name, after)) => {
+                        if colored::control::SHOULD_COLORIZE.should_colorize()
+                            && let Some(code) = style_code(name)
+                        {
+                            prompt.push_str(&format!("\x1b[{}m", code));
+                        }
+                        chars = after.chars();
+                    }
+                    None => prompt.push_str("\\c"),
+                }
+            }
+            'n' => prompt.push('\n'),
+            'e' => prompt.push('\x1b'),
+            '\\' => prompt.push('\\'),
+            // Mark non-printing text in bash, the line editor finds escapes by itself
+            '[' | ']' => {}
+            _ => {
+                prompt.push('\\');
+                prompt.push(escape);
+            }
+        }
+    }
+
+    prompt
+}
+
+// The SGR parameters for a \c{...} name, None for a name that is not known
+fn style_code(name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase().replace('_', " ");
+    let code = match name.as_str() {
+        "reset" | "none" | "" => "0".to_string(),
+        "bold" => "1".to_string(),
+        "dim" => "2".to_string(),
+        "italic" => "3".to_string(),
+        "underline" => "4".to_string(),
+        _ => match name.strip_prefix("on ") {
+            Some(background) => background.parse::<Color>().ok()?.to_bg_str().into_owned(),
+            None => name.parse::<Color>().ok()?.to_fg_str().into_owned(),
+        },
+    };
+    Some(code)
+}
+
+fn user_name() -> String {
+    ["USER", "LOGNAME", "USERNAME"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|user| !user.is_empty()))
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length and gethostname writes at most
+    // that many bytes
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return String::new();
+    }
+    let end = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn host_name() -> String {
+    env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+// The current directory with the home directory shown as ~. PROMPT_DIRTRIM=n leaves
+// only the last n directories, the rest become `...`.
+fn working_directory(shell: &Shell) -> String {
+    let (home, rest) = match dirs::home_dir() {
+        Some(home) if shell.current_dir.starts_with(&home) => (
+            true,
+            shell
+                .current_dir
+                .strip_prefix(&home)
+                .unwrap_or(Path::new("")),
+        ),
+        _ => (false, shell.current_dir.as_path()),
+    };
+    let rest = rest.display().to_string();
+
+    let trim = shell
+        .variables
+        .get("PROMPT_DIRTRIM")
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(0);
+    let parts: Vec<&str> = rest.split('/').filter(|part| !part.is_empty()).collect();
+    let rest = if trim > 0 && parts.len() > trim {
+        format!(".../{}", parts[parts.len() - trim..].join("/"))
+    } else {
+        rest
+    };
+
+    match (home, rest.is_empty()) {
+        (true, true) => "~".to_string(),
+        (true, false) => format!("~/{}", rest),
+        (false, _) if rest.starts_with(".../") => format!("/{}", rest),
+        (false, _) => rest,
+    }
+}
+
+fn directory_name(shell: &Shell) -> String {
+    if dirs::home_dir().is_some_and(|home| home == shell.current_dir) {
+        return "~".to_string();
+    }
+    match shell.current_dir.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => shell.current_dir.display().to_string(),
+    }
+}
+
+fn time(escape: char) -> String {
+    let now = timing::local_time(timing::unix_now());
+    let hour12 = match now.hour % 12 {
+        0 => 12,
+        hour => hour,
+    };
+    let am_pm = if now.hour < 12 { "AM" } else { "PM" };
+    match escape {
+        't' => format!("{:02}:{:02}:{:02}", now.hour, now.minute, now.second),
+        'T' => format!("{:02}:{:02}:{:02}", hour12, now.minute, now.second),
+        'A' => format!("{:02}:{:02}", now.hour, now.minute),
+        '@' => format!("{:02}:{:02} {}", hour12, now.minute, am_pm),
+        _ => format!(
+            "{} {} {:02}",
+            timing::WEEKDAYS[now.weekday as usize],
+            timing::MONTHS[now.month as usize - 1],
+            now.day
+        ),
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    fs::{self, Metadata, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{builtins, mv, stdio::Io, timing, variables::Variables};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Prompt {
+    Never,
+    // -I: once before removing more than three files or anything recursively
+    Once,
+    // -i: before every removal
+    Always,
+}
+
+// What became of a path
+#[derive(Clone, Copy, PartialEq)]
+enum Outcome {
+    Removed,
+    // The user answered no to it, or to something inside it
+    Kept,
+    Failed,
+}
+
+struct Options {
+    recursive: bool,
+    // -d removes empty directories
+    directories: bool,
+    force: bool,
+    prompt: Prompt,
+    verbose: bool,
+    preserve_root: bool,
+    dry_run: bool,
+    // Move to the XDG trash instead of deleting
+    trash: bool,
+}
+
+const USAGE: &str = "Usage: rm [-rRdfiIv] [--dry-run] [--trash] [--no-preserve-root] FILE...";
+
+// rm [OPTION]... FILE... removes files, and directories with -r or -d. `/`, the home
+// directory and anything containing the current directory are never removed
+// recursively, and RM_TRASH=1 makes --trash the default.
+pub fn remove(
+    current_dir: &Path,
+    variables: &Variables,
+    args: &[&str],
+    io: &mut Io,
+) -> io::Result<i32> {
+    let mut options = Options {
+        recursive: false,
+        directories: false,
+        force: false,
+        prompt: Prompt::Never,
+        verbose: false,
+        preserve_root: true,
+        dry_run: false,
+        trash: variables
+            .get("RM_TRASH")
+            .is_some_and(|value| !value.is_empty() && value != "0"),
+    };
+    let mut targets = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--" => {
+                targets.extend(args.by_ref());
+                break;
+            }
+            "--recursive" => options.recursive = true,
+            "--dir" => options.directories = true,
+            "--force" => {
+                options.force = true;
+                options.prompt = Prompt::Never;
+            }
+            "--interactive" | "--interactive=always" => {
+                options.force = false;
+                options.prompt = Prompt::Always;
+            }
+            "--interactive=once" => {
+                options.force = false;
+                options.prompt = Prompt::Once;
+            }
+            "--interactive=never" => options.prompt = Prompt::Never,
+            "--verbose" => options.verbose = true,
+            "--preserve-root" => options.preserve_root = true,
+            "--no-preserve-root" => options.preserve_root = false,
+            "--dry-run" => options.dry_run = true,
+            "--trash" => options.trash = true,
+            "--no-trash" => options.trash = false,
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "rm: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'r' | 'R' => options.recursive = true,
+                        'd' => options.directories = true,
+                        'v' => options.verbose = true,
+                        // The last of -f, -i and -I wins
+                        'f' => {
+                            options.force = true;
+                            options.prompt = Prompt::Never;
+                        }
+                        'i' => {
+                            options.force = false;
+                            options.prompt = Prompt::Always;
+                        }
+                        'I' => {
+                            options.force = false;
+                            options.prompt = Prompt::Once;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "rm: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => targets.push(*arg),
+        }
+    }
+
+    if targets.is_empty() {
+        if options.force {
+            return Ok(0);
+        }
+        writeln!(io.stderr, "rm: missing operand")?;
+        writeln!(io.stderr, "{}", USAGE)?;
+        return Ok(2);
+    }
+
+    if options.prompt == Prompt::Once && (targets.len() > 3 || options.recursive) {
+        let question = format!(
+            "rm: remove {} argument{}{}? ",
+            targets.len(),
+            if targets.len() == 1 { "" } else { "s" },
+            if options.recursive {
+                " recursively"
+            } else {
+                ""
+            }
+        );
+        if !builtins::confirm(&question, io)? {
+            return Ok(0);
+        }
+    }
+
+    let mut status = 0;
+    for target in targets {
+        if !remove_target(current_dir, target, &options, io)? {
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+// Removes one operand, false when it could not be removed
+fn remove_target(
+    current_dir: &Path,
+    target: &str,
+    options: &Options,
+    io: &mut Io,
+) -> io::Result<bool> {
+    let Some(path) = builtins::resolve_path(current_dir, target) else {
+        writeln!(io.stderr, "rm: Could not determine home directory")?;
+        return Ok(false);
+    };
+
+    // Path::components drops a trailing `.`, so look at the text
+    let last = target.trim_end_matches('/').rsplit('/').next();
+    if matches!(last, Some("." | "..")) {
+        writeln!(
+            io.stderr,
+            "rm: refusing to remove '.' or '..' directory: skipping '{}'",
+            target
+        )?;
+        return Ok(false);
+    }
+
+    let metadata = match fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if options.force && e.kind() == io::ErrorKind::NotFound => return Ok(true),
+        Err(e) => {
+            writeln!(io.stderr, "rm: cannot remove '{}': {}", target, e)?;
+            return Ok(false);
+        }
+    };
+
+    if metadata.is_dir() {
+        if !options.recursive && !options.directories {
+            writeln!(io.stderr, "rm: cannot remove '{}': Is a directory", target)?;
+            return Ok(false);
+        }
+
+        // Symlinks and `..` are resolved, so `rm -r ~/..` is caught too
+        let real = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if options.preserve_root && real.parent().is_none() {
+            writeln!(
+                io.stderr,
+                "rm: it is dangerous to operate recursively on '{}'",
+                target
+            )?;
+            writeln!(
+                io.stderr,
+                "rm: use --no-preserve-root to override this failsafe"
+            )?;
+            return Ok(false);
+        }
+        if options.preserve_root
+            && dirs::home_dir()
+                .and_then(|home| fs::canonicalize(home).ok())
+                .is_some_and(|home| home == real)
+        {
+            writeln!(
+                io.stderr,
+                "rm: refusing to remove the home directory '{}'",
+                target
+            )?;
+            writeln!(
+                io.stderr,
+                "rm: use --no-preserve-root to override this failsafe"
+            )?;
+            return Ok(false);
+        }
+        let current = fs::canonicalize(current_dir).unwrap_or_else(|_| current_dir.to_path_buf());
+        if current.starts_with(&real) {
+            writeln!(
+                io.stderr,
+                "rm: refusing to remove '{}': it contains the current directory",
+                target
+            )?;
+            return Ok(false);
+        }
+    }
+
+    if options.trash {
+        if options.prompt == Prompt::Always
+            && !builtins::confirm(
+                &format!("rm: move {} '{}' to the trash? ", kind(&metadata), target),
+                io,
+            )?
+        {
+            return Ok(true);
+        }
+        if options.dry_run {
+            writeln!(io.stdout, "would move '{}' to the trash", target)?;
+            return Ok(true);
+        }
+        return match move_to_trash(&path) {
+            Ok(()) => {
+                if options.verbose {
+                    writeln!(io.stdout, "trashed '{}'", target)?;
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                writeln!(
+                    io.stderr,
+                    "rm: cannot move '{}' to the trash: {}",
+                    target, e
+                )?;
+                Ok(false)
+            }
+        };
+    }
+
+    Ok(remove_path(&path, target, &metadata, options, io)? != Outcome::Failed)
+}
+
+// Deletes path, shown as name, going through directories depth first so -i and -v
+// see every file
+fn remove_path(
+    path: &Path,
+    name: &str,
+    metadata: &Metadata,
+    options: &Options,
+    io: &mut Io,
+) -> io::Result<Outcome> {
+    if !metadata.is_dir() {
+        let question = format!("rm: remove {} '{}'? ", kind(metadata), name);
+        if options.prompt == Prompt::Always && !builtins::confirm(&question, io)? {
+            return Ok(Outcome::Kept);
+        }
+        return finish(path, name, false, options, io);
+    }
+
+    // The worst of what happened to the entries: a failure, or one that was kept
+    let mut inside = Outcome::Removed;
+    if options.recursive {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries.collect::<Vec<_>>(),
+            Err(e) => {
+                writeln!(io.stderr, "rm: cannot remove '{}': {}", name, e)?;
+                return Ok(Outcome::Failed);
+            }
+        };
+        if !entries.is_empty()
+            && options.prompt == Prompt::Always
+            && !builtins::confirm(&format!("rm: descend into directory '{}'? ", name), io)?
+        {
+            return Ok(Outcome::Kept);
+        }
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    writeln!(io.stderr, "rm: cannot remove '{}': {}", name, e)?;
+                    inside = Outcome::Failed;
+                    continue;
+                }
+            };
+            let child = entry.path();
+            let child_name = format!(
+                "{}/{}",
+                name.trim_end_matches('/'),
+                entry.file_name().to_string_lossy()
+            );
+            let outcome = match fs::symlink_metadata(&child) {
+                Ok(child_metadata) => {
+                    remove_path(&child, &child_name, &child_metadata, options, io)?
+                }
+                Err(e) => {
+                    writeln!(io.stderr, "rm: cannot remove '{}': {}", child_name, e)?;
+                    Outcome::Failed
+                }
+            };
+            if outcome == Outcome::Failed || inside == Outcome::Removed {
+                inside = outcome;
+            }
+        }
+    }
+
+    // Something inside is still there, removing the directory can only fail. Like GNU
+    // rm, a directory kept because of an answer is left alone quietly.
+    if inside != Outcome::Removed {
+        return Ok(inside);
+    }
+    let question = format!("rm: remove directory '{}'? ", name);
+    if options.prompt == Prompt::Always && !builtins::confirm(&question, io)? {
+        return Ok(Outcome::Kept);
+    }
+    finish(path, name, true, options, io)
+}
+
+// Removes one file or empty directory and reports it, --dry-run only reports
+fn finish(
+    path: &Path,
+    name: &str,
+    directory: bool,
+    options: &Options,
+    io: &mut Io,
+) -> io::Result<Outcome> {
+    if options.dry_run {
+        writeln!(io.stdout, "would remove '{}'", name)?;
+        return Ok(Outcome::Removed);
+    }
+    let (result, action) = match directory {
+        true => (fs::remove_dir(path), "removed directory"),
+        false => (fs::remove_file(path), "removed"),
+    };
+    match result {
+        Ok(()) => {
+            if options.verbose {
+                writeln!(io.stdout, "{} '{}'", action, name)?;
+            }
+            Ok(Outcome::Removed)
+        }
+        Err(e) if options.force && e.kind() == io::ErrorKind::NotFound => Ok(Outcome::Removed),
+        Err(e) => {
+            writeln!(io.stderr, "rm: cannot remove '{}': {}", name, e)?;
+            Ok(Outcome::Failed)
+        }
+    }
+}
+
+fn kind(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symbolic link"
+    } else if metadata.len() == 0 {
+        "regular empty file"
+    } else if file_type.is_file() {
+        "regular file"
+    } else {
+        "file"
+    }
+}
+
+// Moves path into the trash of the data directory (~/.local/share/Trash), with a
+// .trashinfo file so file managers can put it back. See the FreeDesktop.org Trash
+// specification.
+fn move_to_trash(path: &Path) -> io::Result<()> {
+    let trash = dirs::data_dir()
+        .ok_or_else(|| io::Error::other("could not determine the data directory"))?
+        .join("Trash");
+    let files = trash.join("files");
+    let info = trash.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let original = path
+        .parent()
+        .and_then(|parent| fs::canonicalize(parent).ok())
+        .zip(path.file_name())
+        .map(|(parent, name)| parent.join(name))
+        .unwrap_or_else(|| path.to_path_buf());
+    let base = original
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+
+    // Creating the info file first reserves the name, `name.2`, `name.3`... when taken
+    let mut number = 1;
+    let (name, mut info_file) = loop {
+        let name = if number == 1 {
+            base.clone()
+        } else {
+            format!("{}.{}", base, number)
+        };
+        let info_path = info.join(format!("{}.trashinfo", name));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) if !files.join(&name).exists() => break (name, file),
+            Ok(_) => {
+                let _ = fs::remove_file(&info_path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+        number += 1;
+    };
+
+    let now = timing::local_time(timing::unix_now());
+    write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}-{:02}-{:02}T{:02}:{:02}:{:02}\n",
+        encode_path(&original),
+        now.year,
+        now.month,
+        now.day,
+        now.hour,
+        now.minute,
+        now.second
+    )?;
+
+    // Between file systems the trash gets a copy instead
+    let result = mv::move_path(path, &files.join(&name), None);
+    if result.is_err() {
+        let _ = fs::remove_file(info.join(format!("{}.trashinfo", name)));
+    }
+    result
+}
+
+// Paths in .trashinfo files are URL encoded
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn rm(dir: &Path, input: &str, args: &[&str]) -> testing::Run {
+        testing::run(input, |io| remove(dir, &Variables::default(), args, io))
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        let dir = TempDir::new("rm-options");
+        let run = rm(dir.path(), "", &["-rx", "a"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("rm: invalid option -- 'x'\n{}\n", USAGE)
+        );
+        assert_eq!(rm(dir.path(), "", &["--recursiv", "a"]).status, 2);
+
+        let run = rm(dir.path(), "", &[]);
+        assert_eq!(run.status, 2);
+        assert!(run.stderr.starts_with("rm: missing operand\n"));
+        assert_eq!(rm(dir.path(), "", &["-f"]).status, 0);
+    }
+
+    #[test]
+    fn removes_files_and_directories() {
+        let dir = TempDir::new("rm-remove");
+        dir.write("a", "a");
+        dir.write("d/f", "f");
+        fs::create_dir(dir.join("empty")).unwrap();
+
+        let run = rm(dir.path(), "", &["-v", "a"]);
+        assert_eq!((run.status, run.stdout.as_str()), (0, "removed 'a'\n"));
+        assert!(!dir.join("a").exists());
+
+        let run = rm(dir.path(), "", &["d", "empty"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(
+            run.stderr,
+            "rm: cannot remove 'd': Is a directory\nrm: cannot remove 'empty': Is a directory\n"
+        );
+        assert_eq!(rm(dir.path(), "", &["-d", "empty"]).status, 0);
+        assert!(!dir.join("empty").exists());
+
+        let run = rm(dir.path(), "", &["-rv", "d/"]);
+        assert_eq!(run.stdout, "removed 'd/f'\nremoved directory 'd/'\n");
+        assert!(!dir.join("d").exists());
+    }
+
+    #[test]
+    fn missing_files_only_fail_without_force() {
+        let dir = TempDir::new("rm-missing");
+        let run = rm(dir.path(), "", &["gone"]);
+        assert_eq!(run.status, 1);
+        assert!(run.stderr.starts_with("rm: cannot remove 'gone': "));
+        assert_eq!(rm(dir.path(), "", &["-f", "gone"]).status, 0);
+    }
+
+    #[test]
+    fn dry_run_only_reports() {
+        let dir = TempDir::new("rm-dry-run");
+        dir.write("d/f", "f");
+        let run = rm(dir.path(), "", &["-r", "--dry-run", "d"]);
+        assert_eq!(run.stdout, "would remove 'd/f'\nwould remove 'd'\n");
+        assert!(dir.join("d/f").exists());
+    }
+
+    #[test]
+    fn asks_with_interactive() {
+        let dir = TempDir::new("rm-interactive");
+        dir.write("a", "a");
+        dir.write("b", "");
+        let run = rm(dir.path(), "n\ny\n", &["-i", "a", "b"]);
+        assert_eq!(run.status, 0);
+        assert_eq!(
+            run.stderr,
+            "rm: remove regular file 'a'? rm: remove regular empty file 'b'? "
+        );
+        assert!(dir.join("a").exists());
+        assert!(!dir.join("b").exists());
+
+        // The last of -i and -f wins
+        let run = rm(dir.path(), "", &["-if", "a"]);
+        assert_eq!((run.status, run.stderr.as_str()), (0, ""));
+        assert!(!dir.join("a").exists());
+
+        // Keeping a file keeps its directory, without asking about it
+        dir.write("d/f", "f");
+        let run = rm(dir.path(), "y\nn\n", &["-ri", "d"]);
+        assert_eq!(run.status, 0);
+        assert_eq!(
+            run.stderr,
+            "rm: descend into directory 'd'? rm: remove regular file 'd/f'? "
+        );
+        assert!(dir.join("d/f").exists());
+    }
+
+    #[test]
+    fn keeps_dot_dirs_and_the_current_directory() {
+        let dir = TempDir::new("rm-safeguards");
+        dir.write("sub/f", "f");
+        let sub = dir.join("sub");
+
+        let run = rm(&sub, "", &["-rf", ".", "../sub/.."]);
+        assert_eq!(run.status, 1);
+        assert!(
+            run.stderr
+                .contains("refusing to remove '.' or '..' directory: skipping '.'")
+        );
+        assert!(run.stderr.contains("skipping '../sub/..'"));
+
+        let run = rm(&sub, "", &["-rf", dir.path().to_str().unwrap()]);
+        assert_eq!(run.status, 1);
+        assert!(run.stderr.ends_with("it contains the current directory\n"));
+        assert!(sub.join("f").exists());
+    }
+}
@@ -0,0 +1,1387 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    mem,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::Arc,
+    thread,
+    time::Instant,
+};
+
+use crate::{
+    SHELL_NAME,
+    alias::{self, Aliases},
+    builtins, cat, cp,
+    editor::EditMode,
+    expand, grep, head,
+    history::{self, History},
+    jobs::{self, Job, JobTable, LastStage, Process, ProcessState},
+    lexer::RedirectOp,
+    ln, ls, mkdir, mv,
+    parser::{
+        self, AndOr, CaseItem, CompoundCommand, Connector, List, Pipeline, Redirect, SimpleCommand,
+    },
+    pattern,
+    pkg::{self, PackageManager},
+    platform::{self, Platform},
+    rm, signals, sort,
+    stdio::{Input, Io, Output},
+    tail, timing, touch, uniq,
+    variables::{self, Variable, Variables},
+    wc,
+};
+
+// Functions calling themselves without end would run out of stack
+const MAX_FUNCTION_DEPTH: usize = 200;
+
+// Stack for threads that run shell code, as much as the main thread has
+const SUBSHELL_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+// Every command handled by the shell itself instead of being spawned
+pub const BUILTINS: &[&str] = &[
+    "exit", "quit", "help", "cd", "pwd", "ls", "mkdir", "rm", "cp", "mv", "ln", "cat", "echo",
+    "head", "tail", "wc", "grep", "sort", "uniq", "touch", "clear", "pkg", "package", "jobs", "fg",
+    "bg", "wait", "kill", "set", "history", "export", "unset", "env", "local", "return", "break",
+    "continue", "source", ".", "shift", "alias", "unalias", "command",
+];
+
+// How break, continue and return unwind the commands around them
+#[derive(Clone, Copy, PartialEq)]
+enum Flow {
+    // The number of loops still to leave (or to skip to the next iteration of)
+    Break(usize),
+    Continue(usize),
+    // Leave the function or sourced file
+    Return,
+}
+
+#[derive(Clone)]
+pub struct Shell {
+    pub current_dir: PathBuf,
+    pub platform: Platform,
+    pub package_managers: Vec<PackageManager>,
+    pub jobs: JobTable,
+    // Set when the shell owns a terminal and can move jobs between foreground and background
+    pub job_control: bool,
+    // Keymap used by the line editor, chosen with `set -o vi` / `set -o emacs`
+    pub edit_mode: EditMode,
+    // A glob that matches nothing is dropped (nullglob) or fails the command (failglob)
+    // instead of being passed on as it is
+    pub nullglob: bool,
+    pub failglob: bool,
+    pub history: History,
+    pub variables: Variables,
+    // Exit status of the last pipeline, $?
+    pub last_status: i32,
+    // Exit status of the last $(...) in the command being expanded, which is the status
+    // of a command made only of assignments
+    substitution_status: Option<i32>,
+    // Whether commands come from a user at a terminal, failures are only reported then
+    pub interactive: bool,
+    pub exit_requested: bool,
+    // $0, the name of the script being run
+    pub arg0: String,
+    // $1, $2, ... of the script, function or sourced file being run
+    pub positional: Vec<String>,
+    pub functions: BTreeMap<String, Arc<parser::Command>>,
+    pub aliases: Aliases,
+    // For each function being run, the variables it made local and the values they
+    // get back when it returns
+    locals: Vec<Vec<(String, Option<Variable>)>>,
+    // Loops around the running command, break and continue need at least one
+    loop_depth: usize,
+    // Files being sourced, which return can leave as well
+    sourcing: usize,
+    // Set by break, continue and return until the loop or function they end is left
+    flow: Option<Flow>,
+}
+
+// A command ready to run, after expansion
+struct PreparedCommand {
+    // NAME=value words in front of the command, only exported to it
+    assignments: Vec<(String, String)>,
+    argv: Vec<String>,
+    // Run through `command`, which skips functions
+    skip_functions: bool,
+}
+
+// One command of a pipeline, ready to be started
+enum Stage {
+    Simple(PreparedCommand, Io),
+    Compound(parser::Command, Io),
+    // Finished before it started: assignments only, or expansion failed
+    Done(i32),
+}
+
+impl Shell {
+    pub fn new() -> io::Result<Self> {
+        Ok(Shell {
+            current_dir: env::current_dir()?,
+            platform: platform::get_current_platform(),
+            package_managers: pkg::package_managers(),
+            jobs: JobTable::default(),
+            job_control: false,
+            edit_mode: EditMode::Emacs,
+            nullglob: false,
+            failglob: false,
+            history: History::default(),
+            variables: Variables::from_env(),
+            last_status: 0,
+            substitution_status: None,
+            interactive: false,
+            exit_requested: false,
+            arg0: SHELL_NAME.to_string(),
+            positional: Vec::new(),
+            functions: BTreeMap::new(),
+            aliases: Aliases::new(),
+            locals: Vec::new(),
+            loop_depth: 0,
+            sourcing: 0,
+            flow: None,
+        })
+    }
+
+    pub fn enable_job_control(&mut self) {
+        #[cfg(unix)]
+        jobs::sys::init_job_control();
+        self.job_control = true;
+    }
+
+    // Runs a line typed at the prompt
+    pub fn run_list(&mut self, list: &List) {
+        self.execute_list(list, &Io::inherit());
+        // A break or continue outside of a loop has nothing to stop
+        self.flow = None;
+    }
+
+    // Runs a script given as a file or with -c, stopping at the first syntax error
+    pub fn run_script(&mut self, name: &str, content: &str) {
+        if let Err((line, e)) = self.execute_script(content, &Io::inherit()) {
+            eprintln!("{}: line {}: {}", name, line, e);
+            self.last_status = 2;
+        }
+        self.flow = None;
+    }
+
+    // Runs content one complete command at a time, so everything before a syntax
+    // error has already run when it is found. The error comes with its line number.
+    fn execute_script(&mut self, content: &str, io: &Io) -> Result<i32, (usize, String)> {
+        let mut chunk = String::new();
+        let mut line_number = 0;
+
+        for line in content.split_inclusive('\n') {
+            chunk.push_str(line);
+            line_number += 1;
+            if parser::is_incomplete(&chunk) {
+                continue;
+            }
+
+            let list = parser::parse_str(&chunk, &self.aliases).map_err(|e| (line_number, e))?;
+            chunk.clear();
+            self.execute_list(&list, io);
+            if self.exit_requested || self.flow.is_some() {
+                break;
+            }
+        }
+
+        // A command left open at the end of the file
+        if !chunk.is_empty() {
+            let e = parser::parse_str(&chunk, &self.aliases)
+                .err()
+                .unwrap_or_else(|| "syntax error: unexpected end of file".to_string());
+            return Err((line_number, e));
+        }
+        Ok(self.last_status)
+    }
+
+    // A copy of the shell for `( )`, `$( )` and pipeline stages. Its commands stay in
+    // the shell's process group and leave the terminal alone.
+    fn subshell(&self) -> Shell {
+        let mut subshell = self.clone();
+        subshell.job_control = false;
+        subshell.interactive = false;
+        subshell
+    }
+
+    // Runs the and-or lists one after the other and returns the status of the last one
+    fn execute_list(&mut self, list: &List, io: &Io) -> i32 {
+        for and_or in &list.items {
+            if and_or.background {
+                self.execute_background(and_or, io);
+            } else {
+                self.execute_and_or(and_or, io);
+            }
+
+            // Ctrl-C stops the rest of the list too
+            if self.exit_requested || self.flow.is_some() || self.interrupted() {
+                break;
+            }
+        }
+        self.last_status
+    }
+
+    // Whether the last command was killed by Ctrl-C, or it was pressed while a builtin ran
+    fn interrupted(&self) -> bool {
+        self.last_status == 128 + signals::SIGINT || signals::interrupt_pending()
+    }
+
+    fn execute_and_or(&mut self, and_or: &AndOr, io: &Io) -> i32 {
+        // Only a pipeline on its own reports failures, in a chain they are expected
+        let mut status = self.execute_pipeline(&and_or.first, io, and_or.rest.is_empty());
+
+        for (connector, pipeline) in &and_or.rest {
+            if self.exit_requested || self.flow.is_some() {
+                break;
+            }
+            let run = match connector {
+                Connector::And => status == 0,
+                Connector::Or => status != 0,
+            };
+            if run {
+                status = self.execute_pipeline(pipeline, io, false);
+            }
+        }
+        status
+    }
+
+    fn execute_background(&mut self, and_or: &AndOr, io: &Io) {
+        self.last_status = 0;
+        if and_or.rest.is_empty() {
+            self.run_stages(&and_or.first, io, true, false);
+            return;
+        }
+
+        // A whole && / || chain goes to the background as one job run by a subshell
+        let io = match io.try_clone() {
+            Ok(io) => io,
+            Err(e) => {
+                eprintln!("{}: {}", SHELL_NAME, e);
+                self.last_status = 1;
+                return;
+            }
+        };
+        let mut subshell = self.subshell();
+        let chain = and_or.clone();
+        let mut job = Job::new(and_or.to_string(), None);
+        job.threads.push(spawn_subshell(&job, move || {
+            subshell.execute_and_or(&chain, &io)
+        }));
+        job.last_stage = LastStage::Thread;
+
+        let id = self.jobs.add(job);
+        if self.job_control {
+            println!("[{}]", id);
+        }
+    }
+
+    // Runs a pipeline in the foreground, sets $? and returns it. report prints a
+    // message when an external command at its end fails.
+    fn execute_pipeline(&mut self, pipeline: &Pipeline, io: &Io, report: bool) -> i32 {
+        let report = report && self.interactive && !pipeline.negated;
+        let started = pipeline
+            .time
+            .map(|format| (format, Instant::now(), timing::cpu_times()));
+        let status = self.run_stages(pipeline, io, false, report);
+
+        if let Some((format, start, (user, system))) = started {
+            let (user_now, system_now) = timing::cpu_times();
+            let report = timing::report(
+                format,
+                start.elapsed(),
+                user_now.saturating_sub(user),
+                system_now.saturating_sub(system),
+            );
+            if let Ok(mut stderr) = io.stderr.try_clone() {
+                let _ = write!(stderr, "{}", report);
+            }
+        }
+
+        self.last_status = if pipeline.negated {
+            i32::from(status == 0)
+        } else {
+            status
+        };
+        self.last_status
+    }
+
+    // Starts every stage of a pipeline, with io as the streams of the pipeline as a
+    // whole. A background pipeline becomes a job and returns 0 right away.
+    fn run_stages(&mut self, pipeline: &Pipeline, io: &Io, background: bool, report: bool) -> i32 {
+        let commands = &pipeline.commands;
+        if commands.is_empty() {
+            // A bare `time`
+            return 0;
+        }
+        let mut stages = Vec::new();
+
+        if let [command] = commands.as_slice()
+            && !background
+        {
+            let mut io = match io.try_clone() {
+                Ok(io) => io,
+                Err(e) => {
+                    eprintln!("{}: {}", SHELL_NAME, e);
+                    return 1;
+                }
+            };
+
+            let parser::Command::Simple(command) = command else {
+                // A compound command on its own runs directly in the shell, a group
+                // can change its state just like a builtin
+                return self.execute_compound(command, io);
+            };
+            let Some(prepared) = self.prepare_command(command, &mut io) else {
+                return 1;
+            };
+
+            if prepared.argv.is_empty() {
+                // Assignments on their own set shell variables
+                for (name, value) in prepared.assignments {
+                    self.variables.set(&name, value);
+                }
+                return self.substitution_status.unwrap_or(0);
+            }
+
+            if self.is_internal(&prepared) {
+                // A single builtin or function runs directly in the shell so things like cd
+                // take effect. Assignments in front of it only last while it runs.
+                let saved: Vec<_> = prepared
+                    .assignments
+                    .iter()
+                    .map(|(name, _)| (name.clone(), self.variables.get_variable(name).cloned()))
+                    .collect();
+                for (name, value) in &prepared.assignments {
+                    self.variables.export(name, Some(value.clone()));
+                }
+                let status = self.run_internal(&prepared, io);
+                for (name, variable) in saved.into_iter().rev() {
+                    self.variables.restore(&name, variable);
+                }
+                return status;
+            }
+
+            stages.push(Stage::Simple(prepared, io));
+        } else {
+            let streams = io
+                .stdin
+                .try_clone()
+                .and_then(|stdin| Ok((stdin, io.stdout.try_clone()?, io.stderr.try_clone()?)));
+            let (first_stdin, last_stdout, stderr) = match streams {
+                Ok(streams) => streams,
+                Err(e) => {
+                    eprintln!("{}: {}", SHELL_NAME, e);
+                    return 1;
+                }
+            };
+
+            // Connect every pair of neighbouring stages with a pipe
+            let mut readers = Vec::new();
+            let mut writers = Vec::new();
+            for _ in 1..commands.len() {
+                match io::pipe() {
+                    Ok((reader, writer)) => {
+                        readers.push(Some(reader));
+                        writers.push(Some(writer));
+                    }
+                    Err(e) => {
+                        eprintln!("{}: cannot create pipe: {}", SHELL_NAME, e);
+                        return 1;
+                    }
+                }
+            }
+
+            let mut first_stdin = Some(first_stdin);
+            let mut last_stdout = Some(last_stdout);
+            for (i, command) in commands.iter().enumerate() {
+                let stdin = match i.checked_sub(1) {
+                    Some(prev) => Input::Pipe(readers[prev].take().unwrap()),
+                    None => first_stdin.take().unwrap(),
+                };
+                let stdout = match writers.get_mut(i) {
+                    Some(writer) => Output::Pipe(writer.take().unwrap()),
+                    None => last_stdout.take().unwrap(),
+                };
+                let stderr = match stderr.try_clone() {
+                    Ok(stderr) => stderr,
+                    Err(e) => {
+                        eprintln!("{}: {}", SHELL_NAME, e);
+                        return 1;
+                    }
+                };
+                let mut io = Io {
+                    stdin,
+                    stdout,
+                    stderr,
+                };
+
+                let parser::Command::Simple(command) = command else {
+                    stages.push(Stage::Compound(command.clone(), io));
+                    continue;
+                };
+
+                // A stage that fails its expansion or redirections is skipped, dropping
+                // its pipe ends. So is one made only of assignments, which would not
+                // outlive the stage anyway.
+                stages.push(match self.prepare_command(command, &mut io) {
+                    Some(prepared) if prepared.argv.is_empty() => {
+                        Stage::Done(self.substitution_status.unwrap_or(0))
+                    }
+                    Some(prepared) => Stage::Simple(prepared, io),
+                    None => Stage::Done(1),
+                });
+            }
+        }
+
+        // Builtin, function and compound stages run as threads of the shell, so a
+        // foreground pipeline that has any stays in the shell's process group and keeps
+        // the terminal
+        let own_group = self.job_control
+            && (background
+                || stages.iter().all(|stage| match stage {
+                    Stage::Simple(prepared, _) => !self.is_internal(prepared),
+                    Stage::Compound(..) => false,
+                    Stage::Done(_) => true,
+                }));
+
+        let mut job = Job::new(pipeline.to_string(), None);
+        job.interrupt.set_foreground(!background);
+        let last = stages.len() - 1;
+
+        for (i, stage) in stages.into_iter().enumerate() {
+            let last_stage = match stage {
+                Stage::Done(status) => LastStage::Exited(status),
+                Stage::Compound(command, io) => {
+                    // Stages of a pipeline run in a copy of the shell, like a subshell
+                    let mut subshell = self.subshell();
+                    job.threads.push(spawn_subshell(&job, move || {
+                        subshell.execute_compound(&command, io)
+                    }));
+                    LastStage::Thread
+                }
+                Stage::Simple(prepared, io) if self.is_internal(&prepared) => {
+                    let mut subshell = self.subshell();
+                    for (name, value) in &prepared.assignments {
+                        subshell.variables.export(name, Some(value.clone()));
+                    }
+                    job.threads.push(spawn_subshell(&job, move || {
+                        subshell.run_internal(&prepared, io)
+                    }));
+                    LastStage::Thread
+                }
+                Stage::Simple(prepared, io) => {
+                    let pgid = own_group.then(|| job.pgid.unwrap_or(0));
+                    match self.spawn_external(&prepared, io, pgid) {
+                        Ok(child) => {
+                            if own_group {
+                                let pgid = *job.pgid.get_or_insert(child.id());
+                                // Also set it from this side, in case the child has not done it yet
+                                #[cfg(unix)]
+                                jobs::sys::set_process_group(child.id(), pgid);
+                                #[cfg(not(unix))]
+                                let _ = pgid;
+                            }
+                            job.processes.push(Process {
+                                child,
+                                state: ProcessState::Running,
+                            });
+                            LastStage::External
+                        }
+                        Err(status) => LastStage::Exited(status),
+                    }
+                }
+            };
+            if i == last {
+                job.last_stage = last_stage;
+            }
+            // The io (and with it our copies of the pipe ends) is gone by now,
+            // so every stage sees EOF once its writer is done
+        }
+
+        if background {
+            let pid = job.last_pid();
+            let id = self.jobs.add(job);
+            if self.job_control {
+                match pid {
+                    Some(pid) => println!("[{}] {}", id, pid),
+                    None => println!("[{}]", id),
+                }
+            }
+            0
+        } else {
+            self.wait_foreground(job, report)
+        }
+    }
+
+    // Runs a compound command with its redirections applied to io, or defines a function
+    fn execute_compound(&mut self, command: &parser::Command, mut io: Io) -> i32 {
+        let (compound, redirects) = match command {
+            parser::Command::Compound(compound, redirects) => (compound, redirects),
+            parser::Command::Function(name, body) => {
+                self.functions.insert(name.clone(), Arc::clone(body));
+                return 0;
+            }
+            parser::Command::Simple(_) => unreachable!("not a compound command"),
+        };
+
+        if let Err(e) = self.apply_redirects(redirects, &mut io) {
+            eprintln!("{}: {}", SHELL_NAME, e);
+            return 1;
+        }
+
+        match compound {
+            CompoundCommand::Subshell(list) => self.subshell().execute_list(list, &io),
+            CompoundCommand::Group(list) => self.execute_list(list, &io),
+            CompoundCommand::If {
+                branches,
+                otherwise,
+            } => {
+                for (condition, body) in branches {
+                    if self.execute_condition(condition, &io) == 0 {
+                        return self.execute_list(body, &io);
+                    }
+                    if self.exit_requested || self.flow.is_some() {
+                        return self.last_status;
+                    }
+                }
+                match otherwise {
+                    Some(otherwise) => self.execute_list(otherwise, &io),
+                    None => 0,
+                }
+            }
+            CompoundCommand::While {
+                condition,
+                body,
+                until,
+            } => {
+                let mut status = 0;
+                self.loop_depth += 1;
+                loop {
+                    let succeeded = self.execute_condition(condition, &io) == 0;
+                    if self.loop_finished() || succeeded == *until {
+                        break;
+                    }
+                    status = self.execute_list(body, &io);
+                    if self.loop_finished() {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            CompoundCommand::For { name, words, body } => {
+                let items = match words {
+                    Some(words) => match expand::expand_words(self, words) {
+                        Ok(items) => items,
+                        Err(e) => {
+                            eprintln!("{}: {}", SHELL_NAME, e);
+                            return 1;
+                        }
+                    },
+                    None => self.positional.clone(),
+                };
+
+                let mut status = 0;
+                self.loop_depth += 1;
+                for item in items {
+                    self.variables.set(name, item);
+                    status = self.execute_list(body, &io);
+                    if self.loop_finished() {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            CompoundCommand::Case { word, items } => self.execute_case(word, items, &io),
+        }
+    }
+
+    // Runs the condition of an if, while or until. A condition that fails is not an
+    // error, so it is never reported.
+    fn execute_condition(&mut self, condition: &List, io: &Io) -> i32 {
+        let interactive = mem::replace(&mut self.interactive, false);
+        let status = self.execute_list(condition, io);
+        self.interactive = interactive;
+        status
+    }
+
+    // Checked after each part of a loop, handles break and continue. Returns whether
+    // the loop is over.
+    fn loop_finished(&mut self) -> bool {
+        match self.flow {
+            Some(Flow::Break(count)) => {
+                // break 2 leaves this loop and the one around it
+                self.flow = (count > 1).then(|| Flow::Break(count - 1));
+                true
+            }
+            Some(Flow::Continue(count)) if count > 1 => {
+                self.flow = Some(Flow::Continue(count - 1));
+                true
+            }
+            Some(Flow::Continue(_)) => {
+                self.flow = None;
+                false
+            }
+            Some(Flow::Return) => true,
+            None => self.exit_requested || self.interrupted(),
+        }
+    }
+
+    // Runs the body of the first item with a pattern matching the word
+    fn execute_case(&mut self, word: &str, items: &[CaseItem], io: &Io) -> i32 {
+        let word = match expand::expand_word(self, word) {
+            Ok(word) => word,
+            Err(e) => {
+                eprintln!("{}: {}", SHELL_NAME, e);
+                return 1;
+            }
+        };
+        let chars: Vec<char> = word.chars().collect();
+
+        for item in items {
+            for raw in &item.patterns {
+                let pattern = match expand::expand_pattern(self, raw) {
+                    Ok(pattern) => pattern,
+                    Err(e) => {
+                        eprintln!("{}: {}", SHELL_NAME, e);
+                        return 1;
+                    }
+                };
+                if pattern::matches(&pattern, &chars) {
+                    if item.body.items.is_empty() {
+                        return 0;
+                    }
+                    return self.execute_list(&item.body, io);
+                }
+            }
+        }
+        0
+    }
+
+    fn is_internal(&self, command: &PreparedCommand) -> bool {
+        let name = &command.argv[0];
+        (self.functions.contains_key(name) && !command.skip_functions) || is_builtin(name)
+    }
+
+    // Functions come first, so one can take the place of a builtin
+    fn run_internal(&mut self, command: &PreparedCommand, io: Io) -> i32 {
+        let argv = &command.argv;
+        match self.functions.get(&argv[0]).cloned() {
+            Some(body) if !command.skip_functions => self.call_function(&body, argv, io),
+            _ => self.run_builtin(argv, io),
+        }
+    }
+
+    // Runs a function with its arguments as the positional parameters. Variables it made
+    // local get their old values back when it returns.
+    fn call_function(&mut self, body: &parser::Command, argv: &[String], mut io: Io) -> i32 {
+        if self.locals.len() >= MAX_FUNCTION_DEPTH {
+            let _ = writeln!(
+                io.stderr,
+                "{}: maximum function nesting level exceeded ({})",
+                argv[0], MAX_FUNCTION_DEPTH
+            );
+            // The function that made the call returns right away as well
+            self.flow = Some(Flow::Return);
+            return 1;
+        }
+
+        let positional = mem::replace(&mut self.positional, argv[1..].to_vec());
+        // Loops around the call cannot be left from inside the function
+        let loop_depth = mem::take(&mut self.loop_depth);
+        self.locals.push(Vec::new());
+
+        let status = self.execute_compound(body, io);
+        if self.flow == Some(Flow::Return) {
+            self.flow = None;
+        }
+
+        for (name, variable) in self.locals.pop().unwrap_or_default().into_iter().rev() {
+            self.variables.restore(&name, variable);
+        }
+        self.loop_depth = loop_depth;
+        self.positional = positional;
+        status
+    }
+
+    // Waits for a job that owns the terminal, keeping it in the job table if it gets
+    // stopped. Returns its exit status.
+    fn wait_foreground(&mut self, mut job: Job, report: bool) -> i32 {
+        #[cfg(unix)]
+        if let Some(pgid) = job.pgid {
+            jobs::sys::give_terminal_to(pgid);
+        }
+
+        job.wait();
+
+        #[cfg(unix)]
+        if job.pgid.is_some() {
+            jobs::sys::take_terminal_back();
+        }
+
+        if job.is_stopped() {
+            job.interrupt.set_foreground(false);
+            let id = self.jobs.add(job);
+            println!();
+            let _ = self.jobs.print(id, &mut io::stdout());
+            return 128 + signals::SIGTSTP;
+        }
+
+        // Like other shells, only the last stage decides the outcome
+        if report && let Some(status) = job.status() {
+            report_status(Ok(status));
+        }
+        job.exit_code()
+    }
+
+    // Runs the commands of a `$(...)` or `` `...` `` in a subshell and returns what
+    // they print without its trailing newlines. Their status becomes $? right away.
+    pub fn capture_output(&mut self, input: &str) -> Result<String, String> {
+        let list = parser::parse_str(input, &self.aliases)?;
+
+        let (mut reader, writer) = io::pipe().map_err(|e| e.to_string())?;
+        // Read while the commands run, they would block on a full pipe otherwise
+        let output = thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = reader.read_to_end(&mut output);
+            output
+        });
+
+        let io = Io {
+            stdout: Output::Pipe(writer),
+            ..Io::inherit()
+        };
+        let status = self.subshell().execute_list(&list, &io);
+        self.last_status = status;
+        self.substitution_status = Some(status);
+        // Our end of the pipe has to go before the reader can see EOF
+        drop(io);
+
+        let output = output.join().unwrap_or_default();
+        Ok(String::from_utf8_lossy(&output)
+            .trim_end_matches('\n')
+            .to_string())
+    }
+
+    // Expands the words of a command and applies its redirections to io.
+    // Returns None when either fails.
+    fn prepare_command(&mut self, command: &SimpleCommand, io: &mut Io) -> Option<PreparedCommand> {
+        match self.expand_command(command, io) {
+            Ok(prepared) => Some(prepared),
+            Err(e) => {
+                eprintln!("{}: {}", SHELL_NAME, e);
+                None
+            }
+        }
+    }
+
+    fn expand_command(
+        &mut self,
+        command: &SimpleCommand,
+        io: &mut Io,
+    ) -> Result<PreparedCommand, String> {
+        self.substitution_status = None;
+        // Leading NAME=value words are assignments rather than the command
+        let raw_assignments: Vec<(&str, &str)> = command
+            .words
+            .iter()
+            .map_while(|word| variables::split_assignment(word))
+            .collect();
+
+        let mut assignments = Vec::new();
+        for (name, value) in &raw_assignments {
+            assignments.push((name.to_string(), expand::expand_word(self, value)?));
+        }
+        let mut argv = expand::expand_words(self, &command.words[raw_assignments.len()..])?;
+        self.apply_redirects(&command.redirects, io)?;
+
+        // `command name args` runs name as a builtin or program even if a function has
+        // that name. Aliases are already out of the way, only the first word has them.
+        let mut skip_functions = false;
+        while argv.len() > 1 && argv[0] == "command" && !argv[1].starts_with('-') {
+            argv.remove(0);
+            skip_functions = true;
+        }
+
+        Ok(PreparedCommand {
+            assignments,
+            argv,
+            skip_functions,
+        })
+    }
+
+    fn apply_redirects(&mut self, redirects: &[Redirect], io: &mut Io) -> Result<(), String> {
+        for redirect in redirects {
+            match redirect.op {
+                RedirectOp::Input => {
+                    let file = self.open_redirect_file(&redirect.target, RedirectOp::Input)?;
+                    set_input(io, redirect.fd.unwrap_or(0), Input::File(file))?;
+                }
+                RedirectOp::Output | RedirectOp::Append => {
+                    let file = self.open_redirect_file(&redirect.target, redirect.op)?;
+                    set_output(io, redirect.fd.unwrap_or(1), Output::File(file))?;
+                }
+                RedirectOp::OutputAll | RedirectOp::AppendAll => {
+                    let file = self.open_redirect_file(&redirect.target, redirect.op)?;
+                    let copy = file.try_clone().map_err(|e| e.to_string())?;
+                    io.stdout = Output::File(file);
+                    io.stderr = Output::File(copy);
+                }
+                RedirectOp::DupOutput => {
+                    let target = expand::expand_word(self, &redirect.target)?;
+                    match target.parse::<u32>() {
+                        Ok(source) => {
+                            let output = match source {
+                                1 => io.stdout.try_clone(),
+                                2 => io.stderr.try_clone(),
+                                _ => return Err(format!("{}: Bad file descriptor", source)),
+                            };
+                            let output = output.map_err(|e| e.to_string())?;
+                            set_output(io, redirect.fd.unwrap_or(1), output)?;
+                        }
+                        // `>&file` is the old spelling of `&>file`
+                        Err(_) if redirect.fd.is_none() => {
+                            let file = self.open_file(&target, RedirectOp::Output)?;
+                            let copy = file.try_clone().map_err(|e| e.to_string())?;
+                            io.stdout = Output::File(file);
+                            io.stderr = Output::File(copy);
+                        }
+                        Err(_) => return Err(format!("{}: ambiguous redirect", target)),
+                    }
+                }
+                RedirectOp::DupInput => {
+                    // Only stdin can be read from, so `<&0` is the only valid form
+                    let target = expand::expand_word(self, &redirect.target)?;
+                    if target != "0" || redirect.fd.unwrap_or(0) != 0 {
+                        return Err(format!("{}: Bad file descriptor", target));
+                    }
+                }
+                RedirectOp::HereDoc | RedirectOp::HereString => {
+                    let content = if redirect.op == RedirectOp::HereDoc {
+                        expand::expand_heredoc(self, &redirect.target)?
+                    } else {
+                        format!("{}\n", expand::expand_word(self, &redirect.target)?)
+                    };
+                    let input = Input::from_string(content).map_err(|e| e.to_string())?;
+                    set_input(io, redirect.fd.unwrap_or(0), input)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open_redirect_file(&mut self, raw: &str, op: RedirectOp) -> Result<File, String> {
+        let name = expand::expand_word(self, raw)?;
+        self.open_file(&name, op)
+    }
+
+    fn open_file(&self, name: &str, op: RedirectOp) -> Result<File, String> {
+        let path = builtins::resolve_path(&self.current_dir, name)
+            .ok_or_else(|| "Could not determine home directory".to_string())?;
+
+        let mut options = OpenOptions::new();
+        match op {
+            RedirectOp::Input => options.read(true),
+            RedirectOp::Append | RedirectOp::AppendAll => options.append(true).create(true),
+            _ => options.write(true).create(true).truncate(true),
+        };
+
+        options.open(&path).map_err(|e| format!("{}: {}", name, e))
+    }
+
+    fn run_builtin(&mut self, argv: &[String], mut io: Io) -> i32 {
+        let command = argv[0].as_str();
+        let args: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+        let args = args.as_slice();
+        let io = &mut io;
+
+        let result = match command {
+            "exit" | "quit" => self.exit(args, io),
+            "help" => builtins::display_help(io),
+            "cd" => self.change_directory(args, io),
+            "pwd" => builtins::print_working_directory(&self.current_dir, io),
+            "ls" => ls::list_directory(&self.current_dir, &self.variables, args, io),
+            "mkdir" => mkdir::make_directory(&self.current_dir, args, io),
+            "rm" => rm::remove(&self.current_dir, &self.variables, args, io),
+            "cat" => cat::concatenate(&self.current_dir, args, io),
+            "head" => head::head(&self.current_dir, args, io),
+            "tail" => tail::tail(&self.current_dir, args, io),
+            "wc" => wc::word_count(&self.current_dir, args, io),
+            "grep" => grep::grep(&self.current_dir, args, io),
+            "sort" => sort::sort(&self.current_dir, args, io),
+            "uniq" => uniq::uniq(&self.current_dir, args, io),
+            "echo" => builtins::echo(args, io),
+            "touch" => touch::touch(&self.current_dir, args, io),
+            "cp" => cp::copy(&self.current_dir, args, io),
+            "mv" => mv::move_files(&self.current_dir, args, io),
+            "ln" => ln::link(&self.current_dir, args, io),
+            "clear" => builtins::clear_screen(io),
+            "jobs" => jobs::list_jobs(&mut self.jobs, args, io),
+            "fg" => self.foreground_job(args, io),
+            "bg" => jobs::background_job(&mut self.jobs, args, io),
+            "wait" => jobs::wait_jobs(&mut self.jobs, args, io),
+            "kill" => jobs::kill(&mut self.jobs, args, io),
+            "set" => self.set_options(args, io),
+            "export" => variables::export_variables(&mut self.variables, args, io),
+            "unset" => variables::unset_variables(&mut self.variables, args, io),
+            "env" => self.run_env(args, io),
+            "local" => self.make_local(args, io),
+            "return" => self.return_from(args, io),
+            "break" | "continue" => self.loop_control(command, args, io),
+            "source" | "." => self.source(command, args, io),
+            "shift" => self.shift(args, io),
+            "alias" => alias::define_aliases(&mut self.aliases, args, io),
+            "unalias" => alias::remove_aliases(&mut self.aliases, args, io),
+            "command" => match args.first() {
+                // Only options are left, expand_command took off the name
+                None => Ok(0),
+                Some(option) => {
+                    writeln!(io.stderr, "command: {}: invalid option", option).map(|_| 2)
+                }
+            },
+            "history" => {
+                history::handle_history_command(&mut self.history, &self.current_dir, args, io)
+            }
+            "pkg" | "package" => pkg::handle_package_command(
+                &self.package_managers,
+                args,
+                &self.platform,
+                &self.variables,
+                io,
+            ),
+            _ => unreachable!("not a builtin: {}", command),
+        };
+
+        match result {
+            Ok(status) => status,
+            // The reading end of the pipe went away (e.g. `ls | head -1`), not worth a message
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => 1,
+            Err(e) => {
+                let _ = writeln!(io.stderr, "{}: {}", command, e);
+                1
+            }
+        }
+    }
+
+    // exit [n] leaves the shell with status n, or with the status of the last command
+    fn exit(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        self.exit_requested = true;
+        match args.first() {
+            None => Ok(self.last_status),
+            Some(arg) => match arg.parse::<i32>() {
+                Ok(status) => Ok(status & 0xff),
+                Err(_) => {
+                    writeln!(io.stderr, "exit: {}: numeric argument required", arg)?;
+                    Ok(2)
+                }
+            },
+        }
+    }
+
+    // local NAME[=value]... gives the running function its own copy of each variable.
+    // Without a value it starts out unset.
+    fn make_local(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        let Some(frame) = self.locals.last_mut() else {
+            writeln!(io.stderr, "local: can only be used in a function")?;
+            return Ok(1);
+        };
+
+        let mut status = 0;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (*arg, None),
+            };
+            if !variables::is_valid_name(name) {
+                writeln!(io.stderr, "local: `{}': not a valid identifier", arg)?;
+                status = 1;
+                continue;
+            }
+
+            // Only the value from before the first `local` is put back
+            if !frame.iter().any(|(saved, _)| saved == name) {
+                frame.push((name.to_string(), self.variables.get_variable(name).cloned()));
+            }
+            self.variables.unset(name);
+            if let Some(value) = value {
+                self.variables.set(name, value);
+            }
+        }
+        Ok(status)
+    }
+
+    // return [n] leaves a function or sourced file, by default with the last status
+    fn return_from(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        if self.locals.is_empty() && self.sourcing == 0 {
+            writeln!(
+                io.stderr,
+                "return: can only `return' from a function or sourced script"
+            )?;
+            return Ok(1);
+        }
+
+        let status = match args.first() {
+            None => self.last_status,
+            Some(arg) => match arg.parse::<i32>() {
+                Ok(status) => status & 0xff,
+                Err(_) => {
+                    writeln!(io.stderr, "return: {}: numeric argument required", arg)?;
+                    2
+                }
+            },
+        };
+        self.flow = Some(Flow::Return);
+        Ok(status)
+    }
+
+    // shift [n] drops the first n positional parameters, $2 becomes $1 and so on
+    fn shift(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        let count = match args.first() {
+            None => 1,
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) => count,
+                Err(_) => {
+                    writeln!(io.stderr, "shift: {}: numeric argument required", arg)?;
+                    return Ok(2);
+                }
+            },
+        };
+
+        if count > self.positional.len() {
+            writeln!(io.stderr, "shift: {}: shift count out of range", count)?;
+            return Ok(1);
+        }
+        self.positional.drain(..count);
+        Ok(0)
+    }
+
+    // break [n] leaves the n innermost loops, continue [n] goes on with the next
+    // iteration of the n-th one
+    fn loop_control(&mut self, command: &str, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        let count = match args.first() {
+            None => 1,
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) if count > 0 => count,
+                _ => {
+                    writeln!(io.stderr, "{}: {}: loop count out of range", command, arg)?;
+                    return Ok(1);
+                }
+            },
+        };
+
+        if self.loop_depth == 0 {
+            writeln!(
+                io.stderr,
+                "{}: only meaningful in a `for', `while', or `until' loop",
+                command
+            )?;
+            return Ok(0);
+        }
+
+        let count = count.min(self.loop_depth);
+        self.flow = Some(if command == "break" {
+            Flow::Break(count)
+        } else {
+            Flow::Continue(count)
+        });
+        Ok(0)
+    }
+
+    // source FILE [ARGS...] (or `. FILE`) runs the commands of a file in this shell.
+    // Arguments become the positional parameters while it runs.
+    fn source(&mut self, command: &str, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        let Some((file, args)) = args.split_first() else {
+            writeln!(io.stderr, "{}: filename argument required", command)?;
+            return Ok(2);
+        };
+
+        let Some(path) = builtins::resolve_path(&self.current_dir, file) else {
+            writeln!(io.stderr, "Could not determine home directory")?;
+            return Ok(1);
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                writeln!(io.stderr, "{}: {}: {}", command, file, e)?;
+                return Ok(1);
+            }
+        };
+        let positional = (!args.is_empty()).then(|| {
+            let args = args.iter().map(|arg| arg.to_string()).collect();
+            mem::replace(&mut self.positional, args)
+        });
+        self.sourcing += 1;
+
+        let status = match self.execute_script(&content, io) {
+            Ok(status) => status,
+            Err((line, e)) => {
+                writeln!(io.stderr, "{}: {}: line {}: {}", command, file, line, e)?;
+                2
+            }
+        };
+        if self.flow == Some(Flow::Return) {
+            self.flow = None;
+        }
+
+        self.sourcing -= 1;
+        if let Some(positional) = positional {
+            self.positional = positional;
+        }
+        Ok(status)
+    }
+
+    // Keeps PWD and OLDPWD in step with the current directory
+    fn change_directory(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        let previous = self.current_dir.clone();
+        let status = builtins::change_directory(&mut self.current_dir, args, io)?;
+
+        if self.current_dir != previous {
+            let previous = previous.to_string_lossy().into_owned();
+            let current = self.current_dir.to_string_lossy().into_owned();
+            self.variables.set("OLDPWD", previous);
+            self.variables.set("PWD", current);
+        }
+        Ok(status)
+    }
+
+    // env lists the environment, or runs a command with NAME=value pairs added to it.
+    // -i starts from an empty environment and -u NAME leaves a variable out.
+    fn run_env(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        let mut environment: BTreeMap<&str, &str> = self.variables.exported().collect();
+        let mut rest = args;
+
+        while let Some((arg, tail)) = rest.split_first() {
+            if matches!(*arg, "-i" | "-") {
+                environment.clear();
+            } else if *arg == "-u"
+                && let Some((name, tail)) = tail.split_first()
+            {
+                environment.remove(name);
+                rest = tail;
+                continue;
+            } else if let Some((name, value)) = arg.split_once('=') {
+                environment.insert(name, value);
+            } else {
+                break;
+            }
+            rest = tail;
+        }
+
+        let Some((program, program_args)) = rest.split_first() else {
+            for (name, value) in &environment {
+                writeln!(io.stdout, "{}={}", name, value)?;
+            }
+            return Ok(0);
+        };
+
+        let mut cmd = Command::new(program);
+        cmd.args(program_args);
+        signals::restore_defaults(&mut cmd);
+        cmd.env_clear()
+            .envs(&environment)
+            .current_dir(&self.current_dir)
+            .stdin(io.stdin.to_stdio()?)
+            .stdout(io.stdout.to_stdio()?)
+            .stderr(io.stderr.to_stdio()?);
+
+        match cmd.status() {
+            Ok(status) => Ok(jobs::exit_code(&status)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                writeln!(io.stderr, "env: '{}': No such file or directory", program)?;
+                Ok(127)
+            }
+            Err(e) => {
+                writeln!(io.stderr, "env: '{}': {}", program, e)?;
+                Ok(126)
+            }
+        }
+    }
+
+    fn foreground_job(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        let spec = args.first().copied().unwrap_or("%+");
+        let Some(mut job) = self.jobs.find(spec).and_then(|id| self.jobs.remove(id)) else {
+            writeln!(io.stderr, "fg: {}: no such job", spec)?;
+            return Ok(1);
+        };
+
+        writeln!(io.stdout, "{}", job.command)?;
+        job.interrupt.set_foreground(true);
+        job.resume();
+        Ok(self.wait_foreground(job, self.interactive))
+    }
+
+    // `set -o name` turns an option on, `set +o name` turns it off, `set -o` lists them.
+    // Without arguments it lists every variable.
+    fn set_options(&mut self, args: &[&str], io: &mut Io) -> io::Result<i32> {
+        match args {
+            [] => return variables::print_variables(&self.variables, io),
+            ["-o"] | ["+o"] => {
+                for (name, on) in self.options() {
+                    if args.first() == Some(&"+o") {
+                        writeln!(io.stdout, "set {}o {}", if on { "-" } else { "+" }, name)?;
+                    } else {
+                        writeln!(io.stdout, "{:<15}{}", name, if on { "on" } else { "off" })?;
+                    }
+                }
+            }
+            [flag @ ("-o" | "+o"), name] => {
+                if !self.set_option(name, *flag == "-o") {
+                    writeln!(io.stderr, "set: {}: invalid option name", name)?;
+                    return Ok(1);
+                }
+            }
+            _ => {
+                writeln!(io.stderr, "set: usage: set [-o|+o] [option]")?;
+                return Ok(2);
+            }
+        }
+        Ok(0)
+    }
+
+    fn options(&self) -> [(&'static str, bool); 4] {
+        let vi = self.edit_mode == EditMode::Vi;
+        [
+            ("emacs", !vi),
+            ("failglob", self.failglob),
+            ("nullglob", self.nullglob),
+            ("vi", vi),
+        ]
+    }
+
+    // Returns false for an unknown option name
+    fn set_option(&mut self, name: &str, on: bool) -> bool {
+        match name {
+            "vi" | "emacs" => {
+                self.edit_mode = if (name == "vi") == on {
+                    EditMode::Vi
+                } else {
+                    EditMode::Emacs
+                };
+            }
+            "failglob" => self.failglob = on,
+            "nullglob" => self.nullglob = on,
+            _ => return false,
+        }
+        true
+    }
+
+    // pgid is the process group to put the child in when job control is on,
+    // 0 starts a new group led by the child. Fails with the exit status for $?.
+    fn spawn_external(
+        &self,
+        prepared: &PreparedCommand,
+        io: Io,
+        pgid: Option<u32>,
+    ) -> Result<Child, i32> {
+        let argv = &prepared.argv;
+        let mut cmd = if self.platform == Platform::Windows {
+            // Go through cmd so its own commands (dir, type, ...) keep working
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C").args(argv);
+            cmd
+        } else {
+            let mut cmd = Command::new(&argv[0]);
+            cmd.args(&argv[1..]);
+            cmd
+        };
+
+        #[cfg(unix)]
+        if self.job_control
+            && let Some(pgid) = pgid
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(pgid as i32);
+        }
+        #[cfg(not(unix))]
+        let _ = pgid;
+
+        signals::restore_defaults(&mut cmd);
+        self.variables.apply_to(&mut cmd);
+        cmd.envs(
+            prepared
+                .assignments
+                .iter()
+                .map(|(name, value)| (name, value)),
+        );
+
+        cmd.current_dir(&self.current_dir)
+            .stdin(Stdio::from(io.stdin))
+            .stdout(Stdio::from(io.stdout))
+            .stderr(Stdio::from(io.stderr));
+
+        match cmd.spawn() {
+            Ok(child) => Ok(child),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("{}: {}: command not found", SHELL_NAME, argv[0]);
+                Err(127)
+            }
+            Err(e) => {
+                eprintln!("Failed to execute command: {}", e);
+                Err(126)
+            }
+        }
+    }
+}
+
+fn set_input(io: &mut Io, fd: u32, input: Input) -> Result<(), String> {
+    match fd {
+        0 => io.stdin = input,
+        _ => return Err(format!("{}: Bad file descriptor", fd)),
+    }
+    Ok(())
+}
+
+fn set_output(io: &mut Io, fd: u32, output: Output) -> Result<(), String> {
+    match fd {
+        1 => io.stdout = output,
+        2 => io.stderr = output,
+        _ => return Err(format!("{}: Bad file descriptor", fd)),
+    }
+    Ok(())
+}
+
+// Runs builtins, functions and compound commands of a pipeline or background job
+fn spawn_subshell<F>(job: &Job, run: F) -> thread::JoinHandle<i32>
+where
+    F: FnOnce() -> i32 + Send + 'static,
+{
+    let interrupt = Arc::clone(&job.interrupt);
+    thread::Builder::new()
+        .stack_size(SUBSHELL_STACK_SIZE)
+        .spawn(move || {
+            interrupt.enter();
+            run()
+        })
+        .expect("failed to spawn thread")
+}
+
+pub fn is_builtin(command: &str) -> bool {
+    BUILTINS.contains(&command)
+}
+
+fn report_status(status: io::Result<ExitStatus>) {
+    match status {
+        Ok(exit_status) => {
+            if !exit_status.success() {
+                if let Some(code) = exit_status.code() {
+                    println!("Command exited with non-zero status code: {}", code);
+                } else if let Some(signal) = signals::termination_signal(&exit_status) {
+                    println!(
+                        "Command terminated by signal {} ({})",
+                        signal,
+                        signals::signal_name(signal)
+                    );
+                } else {
+                    println!("Command terminated by signal");
+                }
+            }
+        }
+        Err(e) => {
+            println!("Failed to execute command: {}", e);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    cell::RefCell,
+    process::{Command, ExitStatus},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+// Set by the SIGINT handler, checked by whoever is waiting on input
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // The job whose builtins run on this thread, None on the shell's own thread
+    static JOB: RefCell<Option<Arc<JobInterrupt>>> = const { RefCell::new(None) };
+}
+
+// Interrupts the builtins of one job, which run as threads that no signal can reach.
+// kill sets it, and Ctrl-C counts only while the job is in the foreground, so it does
+// not stop the builtins of background jobs too. A job started on the thread of another
+// one is part of it.
+#[derive(Default)]
+pub struct JobInterrupt {
+    killed: AtomicBool,
+    foreground: AtomicBool,
+    parent: Option<Arc<JobInterrupt>>,
+}
+
+impl JobInterrupt {
+    // For a new job started on this thread
+    pub fn new() -> Arc<JobInterrupt> {
+        Arc::new(JobInterrupt {
+            parent: JOB.with_borrow(Clone::clone),
+            ..JobInterrupt::default()
+        })
+    }
+
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn set_foreground(&self, foreground: bool) {
+        self.foreground.store(foreground, Ordering::SeqCst);
+    }
+
+    fn is_pending(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+            || (self.foreground.load(Ordering::SeqCst)
+                && match &self.parent {
+                    Some(parent) => parent.is_pending(),
+                    None => INTERRUPTED.load(Ordering::SeqCst),
+                })
+    }
+
+    // Makes interrupt_pending on this thread look at this job
+    pub fn enter(self: Arc<Self>) {
+        JOB.set(Some(self));
+    }
+}
+
+#[cfg(unix)]
+pub const SIGTERM: i32 = libc::SIGTERM;
+#[cfg(unix)]
+pub const SIGHUP: i32 = libc::SIGHUP;
+#[cfg(unix)]
+pub const SIGINT: i32 = libc::SIGINT;
+#[cfg(unix)]
+pub const SIGTSTP: i32 = libc::SIGTSTP;
+
+#[cfg(unix)]
+pub const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("WINCH", libc::SIGWINCH),
+];
+
+// Processes can only be killed outright here, the signal itself is ignored
+#[cfg(not(unix))]
+pub const SIGTERM: i32 = 15;
+#[cfg(not(unix))]
+pub const SIGHUP: i32 = 1;
+#[cfg(not(unix))]
+pub const SIGINT: i32 = 2;
+#[cfg(not(unix))]
+pub const SIGTSTP: i32 = 20;
+
+#[cfg(not(unix))]
+pub const SIGNALS: &[(&str, i32)] = &[("KILL", 9), ("TERM", 15)];
+
+// Accepts a number or a name with or without the SIG prefix (9, KILL, SIGKILL)
+pub fn signal_number(name: &str) -> Option<i32> {
+    if let Ok(number) = name.parse() {
+        return Some(number);
+    }
+    let name = name.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS
+        .iter()
+        .find(|(signal, _)| *signal == name)
+        .map(|(_, number)| *number)
+}
+
+// "SIGINT" for 2, "SIG42" for signals without a name
+pub fn signal_name(number: i32) -> String {
+    match SIGNALS.iter().find(|(_, signal)| *signal == number) {
+        Some((name, _)) => format!("SIG{}", name),
+        None => format!("SIG{}", number),
+    }
+}
+
+// The signal that killed a process, if any
+pub fn termination_signal(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+// Returns whether Ctrl-C was pressed since the last call
+pub fn take_interrupt() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+// Like take_interrupt, but leaves the flag for the prompt to clear, so loops can
+// check it on every iteration. On the thread of a job it tells whether that job was
+// interrupted.
+pub fn interrupt_pending() -> bool {
+    JOB.with_borrow(|job| match job {
+        Some(job) => job.is_pending(),
+        None => INTERRUPTED.load(Ordering::SeqCst),
+    })
+}
+
+// Whether a signal ends a process by default, rather than stopping or continuing it
+// or being ignored
+#[cfg(unix)]
+pub fn terminates(signal: i32) -> bool {
+    !matches!(
+        signal,
+        0 | libc::SIGCHLD
+            | libc::SIGCONT
+            | libc::SIGSTOP
+            | libc::SIGTSTP
+            | libc::SIGTTIN
+            | libc::SIGTTOU
+            | libc::SIGURG
+            | libc::SIGWINCH
+    )
+}
+
+#[cfg(not(unix))]
+pub fn terminates(signal: i32) -> bool {
+    signal != 0
+}
+
+// Keeps Ctrl-C and Ctrl-\ from killing the interactive shell itself
+pub fn install_handlers() {
+    #[cfg(unix)]
+    {
+        extern "C" fn on_interrupt(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+
+        // SAFETY: the handler only touches an atomic. SA_RESTART is left out on purpose
+        // so a blocking read returns EINTR and the prompt can be redrawn.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_interrupt as *const () as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+
+            libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+        }
+    }
+}
+
+// Children start with the default dispositions the shell changed for itself
+pub fn restore_defaults(cmd: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        // SAFETY: signal() is async-signal-safe, nothing else runs between fork and exec
+        unsafe {
+            cmd.pre_exec(|| {
+                for signal in [
+                    libc::SIGINT,
+                    libc::SIGQUIT,
+                    libc::SIGTSTP,
+                    libc::SIGTTIN,
+                    libc::SIGTTOU,
+                ] {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
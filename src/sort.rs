@@ -0,0 +1,484 @@
+use std::{
+    cmp::Ordering,
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{builtins, signals, stdio::Io};
+
+// How the text of a key is compared
+#[derive(Default, Clone, Copy)]
+struct Order {
+    numeric: bool,
+    reverse: bool,
+    fold_case: bool,
+    skip_blanks: bool,
+}
+
+impl Order {
+    fn is_set(&self) -> bool {
+        self.numeric || self.reverse || self.fold_case || self.skip_blanks
+    }
+}
+
+// -k START[,END]: fields and characters count from 1, an end character of 0 is the end
+// of the field and no end field is the end of the line
+struct Key {
+    start_field: usize,
+    start_char: usize,
+    end: Option<(usize, usize)>,
+    order: Order,
+}
+
+const USAGE: &str = "Usage: sort [-nrfbsu] [-k START[,END]]... [-t SEP] [-o FILE] [FILE]...";
+
+// sort [OPTION]... [FILE]... prints the lines of the files, or of stdin without files or
+// for `-`, in order. Lines are compared byte by byte, or by the -k keys in turn, with lines
+// that tie compared whole unless -s is given.
+pub fn sort(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut order = Order::default();
+    let mut keys = Vec::new();
+    let mut separator = None;
+    let mut unique = false;
+    let mut stable = false;
+    let mut output = None;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (option, value) = match *arg {
+            "--" => {
+                files.extend(args.by_ref().copied());
+                break;
+            }
+            "--numeric-sort" => {
+                order.numeric = true;
+                continue;
+            }
+            "--reverse" => {
+                order.reverse = true;
+                continue;
+            }
+            "--ignore-case" => {
+                order.fold_case = true;
+                continue;
+            }
+            "--ignore-leading-blanks" => {
+                order.skip_blanks = true;
+                continue;
+            }
+            "--unique" => {
+                unique = true;
+                continue;
+            }
+            "--stable" => {
+                stable = true;
+                continue;
+            }
+            _ if arg.starts_with("--key=") => ('k', Some(&arg["--key=".len()..])),
+            _ if arg.starts_with("--field-separator=") => {
+                ('t', Some(&arg["--field-separator=".len()..]))
+            }
+            _ if arg.starts_with("--output=") => ('o', Some(&arg["--output=".len()..])),
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "sort: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                let mut option = None;
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'n' => order.numeric = true,
+                        'r' => order.reverse = true,
+                        'f' => order.fold_case = true,
+                        'b' => order.skip_blanks = true,
+                        'u' => unique = true,
+                        's' => stable = true,
+                        'k' | 't' | 'o' => {
+                            let rest = &arg[2 + i..];
+                            option = Some((flag, Some(rest).filter(|rest| !rest.is_empty())));
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "sort: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                match option {
+                    Some(option) => option,
+                    None => continue,
+                }
+            }
+            _ => {
+                files.push(*arg);
+                continue;
+            }
+        };
+
+        let Some(value) = value.or_else(|| args.next().copied()) else {
+            writeln!(
+                io.stderr,
+                "sort: option requires an argument -- '{}'",
+                option
+            )?;
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        };
+        match option {
+            'k' => match parse_key(value) {
+                Some(key) => keys.push(key),
+                None => {
+                    writeln!(io.stderr, "sort: invalid key '{}'", value)?;
+                    return Ok(2);
+                }
+            },
+            't' => {
+                let [byte] = value.as_bytes() else {
+                    writeln!(io.stderr, "sort: multi-character tab '{}'", value)?;
+                    return Ok(2);
+                };
+                separator = Some(*byte);
+            }
+            _ => output = Some(value),
+        }
+    }
+
+    // Keys without options of their own take the global ones
+    for key in &mut keys {
+        if !key.order.is_set() {
+            key.order = order;
+        }
+    }
+
+    if files.is_empty() {
+        files.push("-");
+    }
+    // Everything is read before the output is opened, so `sort -o f f` works
+    let mut text = Vec::new();
+    for name in &files {
+        let result = builtins::open_input(current_dir, name, io)
+            .and_then(|input| read_all(input, &mut text));
+        match result {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(128 + signals::SIGINT),
+            Err(e) => {
+                writeln!(io.stderr, "sort: cannot read: {}: {}", name, e)?;
+                return Ok(2);
+            }
+        }
+        if !text.is_empty() && !text.ends_with(b"\n") {
+            text.push(b'\n');
+        }
+    }
+    let mut lines: Vec<&[u8]> = text.split_inclusive(|byte| *byte == b'\n').collect();
+
+    let compare_keys = |a: &[u8], b: &[u8]| -> Ordering {
+        let a = a.strip_suffix(b"\n").unwrap_or(a);
+        let b = b.strip_suffix(b"\n").unwrap_or(b);
+        if keys.is_empty() {
+            return compare(a, b, order);
+        }
+        keys.iter()
+            .map(|key| {
+                let a = key_text(a, key, separator);
+                let b = key_text(b, key, separator);
+                compare(a, b, key.order)
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    };
+    lines.sort_by(|a, b| {
+        compare_keys(a, b).then_with(|| match stable || unique {
+            true => Ordering::Equal,
+            false if order.reverse => b.cmp(a),
+            false => a.cmp(b),
+        })
+    });
+    // -u keeps the first of each run of lines with equal keys
+    if unique {
+        lines.dedup_by(|line, kept| compare_keys(kept, line).is_eq());
+    }
+
+    match output {
+        Some(name) => {
+            let result = builtins::resolve_path(current_dir, name)
+                .ok_or_else(|| io::Error::other("Could not determine home directory"))
+                .and_then(|path| fs::write(path, lines.concat()));
+            if let Err(e) = result {
+                writeln!(io.stderr, "sort: cannot create '{}': {}", name, e)?;
+                return Ok(2);
+            }
+        }
+        None => {
+            for line in lines {
+                io.stdout.write_all(line)?;
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+// Appends an input to text a chunk at a time, stopping on Ctrl-C
+fn read_all(mut input: Box<dyn Read + Send>, text: &mut Vec<u8>) -> io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        if signals::interrupt_pending() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        match input.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => text.extend_from_slice(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// F[.C][OPTS][,F[.C][OPTS]]
+fn parse_key(text: &str) -> Option<Key> {
+    let (start, end) = match text.split_once(',') {
+        Some((start, end)) => (start, Some(end)),
+        None => (text, None),
+    };
+
+    let mut order = Order::default();
+    let mut position = |part: &str| -> Option<(usize, usize)> {
+        let digits_end = |text: &str| {
+            text.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(text.len())
+        };
+        let field_end = digits_end(part);
+        let field = part[..field_end].parse().ok()?;
+        let mut rest = &part[field_end..];
+        let mut char = 0;
+        if let Some(after) = rest.strip_prefix('.') {
+            let char_end = digits_end(after);
+            char = after[..char_end].parse().ok()?;
+            rest = &after[char_end..];
+        }
+        for flag in rest.chars() {
+            match flag {
+                'n' => order.numeric = true,
+                'r' => order.reverse = true,
+                'f' => order.fold_case = true,
+                'b' => order.skip_blanks = true,
+                _ => return None,
+            }
+        }
+        Some((field, char))
+    };
+
+    let (start_field, start_char) = position(start)?;
+    let end = match end {
+        Some(end) => Some(position(end)?),
+        None => None,
+    };
+    if start_field == 0 || end.is_some_and(|(field, _)| field == 0) {
+        return None;
+    }
+    Some(Key {
+        start_field,
+        // A missing start character is the first one
+        start_char: start_char.max(1),
+        end,
+        order,
+    })
+}
+
+// Where each field of a line begins and ends. Without a separator a field is a run of
+// blanks followed by a run of anything else, so the blanks belong to the field they precede.
+fn fields(line: &[u8], separator: Option<u8>) -> Vec<(usize, usize)> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    match separator {
+        Some(separator) => {
+            for (i, byte) in line.iter().enumerate() {
+                if *byte == separator {
+                    fields.push((start, i));
+                    start = i + 1;
+                }
+            }
+            fields.push((start, line.len()));
+        }
+        None => loop {
+            let mut end = start;
+            while end < line.len() && is_blank(line[end]) {
+                end += 1;
+            }
+            while end < line.len() && !is_blank(line[end]) {
+                end += 1;
+            }
+            fields.push((start, end));
+            if end >= line.len() {
+                break;
+            }
+            start = end;
+        },
+    }
+    fields
+}
+
+// The part of a line a key covers
+fn key_text<'a>(line: &'a [u8], key: &Key, separator: Option<u8>) -> &'a [u8] {
+    let fields = fields(line, separator);
+    let skip = |mut at: usize, end: usize| {
+        while key.order.skip_blanks && at < end && is_blank(line[at]) {
+            at += 1;
+        }
+        at
+    };
+
+    let begin = match fields.get(key.start_field - 1) {
+        Some((start, end)) => (skip(*start, *end) + key.start_char - 1).min(line.len()),
+        None => line.len(),
+    };
+    let end = match key.end {
+        None => line.len(),
+        Some((field, _)) if field > fields.len() => line.len(),
+        Some((field, 0)) => fields[field - 1].1,
+        Some((field, char)) => {
+            let (start, end) = fields[field - 1];
+            (skip(start, end) + char).min(line.len())
+        }
+    };
+    &line[begin..end.max(begin)]
+}
+
+fn compare(a: &[u8], b: &[u8], order: Order) -> Ordering {
+    let (a, b) = match order.skip_blanks {
+        true => (a.trim_ascii_start(), b.trim_ascii_start()),
+        false => (a, b),
+    };
+    let ordering = if order.numeric {
+        number(a).total_cmp(&number(b))
+    } else if order.fold_case {
+        a.iter()
+            .map(u8::to_ascii_lowercase)
+            .cmp(b.iter().map(u8::to_ascii_lowercase))
+    } else {
+        a.cmp(b)
+    };
+    match order.reverse {
+        true => ordering.reverse(),
+        false => ordering,
+    }
+}
+
+// The number a key starts with, after any blanks. Text that is not a number counts as 0.
+fn number(text: &[u8]) -> f64 {
+    let text = text.trim_ascii_start();
+    let mut end = usize::from(text.first() == Some(&b'-'));
+    while end < text.len() && text[end].is_ascii_digit() {
+        end += 1;
+    }
+    if text.get(end) == Some(&b'.') {
+        end += 1;
+        while end < text.len() && text[end].is_ascii_digit() {
+            end += 1;
+        }
+    }
+    std::str::from_utf8(&text[..end])
+        .ok()
+        .and_then(|number| number.parse().ok())
+        .unwrap_or(0.0)
+}
+
+fn is_blank(byte: u8) -> bool {
+    byte == b' ' || byte == b'\t'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn sort_lines(input: &str, args: &[&str]) -> String {
+        let run = testing::run(input, |io| sort(Path::new("."), args, io));
+        assert_eq!((run.status, run.stderr.as_str()), (0, ""));
+        run.stdout
+    }
+
+    #[test]
+    fn parses_keys() {
+        let key = parse_key("2").unwrap();
+        assert_eq!((key.start_field, key.start_char, key.end), (2, 1, None));
+        let key = parse_key("1.3nr,2.0").unwrap();
+        assert_eq!((key.start_field, key.start_char), (1, 3));
+        assert_eq!(key.end, Some((2, 0)));
+        assert!(key.order.numeric && key.order.reverse && !key.order.fold_case);
+
+        assert!(parse_key("0").is_none());
+        assert!(parse_key("1,0").is_none());
+        assert!(parse_key("1x").is_none());
+        assert!(parse_key("").is_none());
+    }
+
+    #[test]
+    fn finds_the_text_of_keys() {
+        let text = |line: &str, key: &str, separator: Option<u8>| {
+            let key = parse_key(key).unwrap();
+            String::from_utf8(key_text(line.as_bytes(), &key, separator).to_vec()).unwrap()
+        };
+        // Without a separator the blanks before a field are part of it
+        assert_eq!(text("a  b c", "2,2", None), "  b");
+        assert_eq!(text("a  b c", "2b,2", None), "b");
+        assert_eq!(text("a  b c", "2", None), "  b c");
+        assert_eq!(text("a:b:c", "2,2", Some(b':')), "b");
+        assert_eq!(text("a:bcd:e", "2.2,2.3", Some(b':')), "cd");
+        assert_eq!(text("a", "3", None), "");
+    }
+
+    #[test]
+    fn compares_keys() {
+        let order = |flags: &str| parse_key(&format!("1{}", flags)).unwrap().order;
+        assert_eq!(compare(b"10", b"9", order("")), Ordering::Less);
+        assert_eq!(compare(b"10", b"9", order("n")), Ordering::Greater);
+        assert_eq!(compare(b"-2.5", b"x", order("n")), Ordering::Less);
+        assert_eq!(compare(b"B", b"a", order("")), Ordering::Less);
+        assert_eq!(compare(b"B", b"a", order("f")), Ordering::Greater);
+        assert_eq!(compare(b"a", b"b", order("r")), Ordering::Greater);
+        assert_eq!(compare(b"  b", b"a", order("b")), Ordering::Greater);
+
+        assert_eq!(number(b"  42abc"), 42.0);
+        assert_eq!(number(b"-1.5"), -1.5);
+        assert_eq!(number(b"abc"), 0.0);
+    }
+
+    #[test]
+    fn sorts_lines() {
+        let input = "b 10\na 9\nc 9\na 9\n";
+        assert_eq!(sort_lines(input, &[]), "a 9\na 9\nb 10\nc 9\n");
+        assert_eq!(sort_lines(input, &["-r"]), "c 9\nb 10\na 9\na 9\n");
+        assert_eq!(sort_lines(input, &["-u"]), "a 9\nb 10\nc 9\n");
+        // Lines with equal keys fall back to the whole line, unless -s keeps them as they were
+        assert_eq!(sort_lines(input, &["-k2n"]), "a 9\na 9\nc 9\nb 10\n");
+        assert_eq!(
+            sort_lines(input, &["-s", "-k2,2n"]),
+            "a 9\nc 9\na 9\nb 10\n"
+        );
+        assert_eq!(sort_lines("x:2\ny:1\n", &["-t:", "-k2"]), "y:1\nx:2\n");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let run = testing::run("", |io| sort(Path::new("."), &["-k0"], io));
+        assert_eq!(
+            (run.status, run.stderr.as_str()),
+            (2, "sort: invalid key '0'\n")
+        );
+        let run = testing::run("", |io| sort(Path::new("."), &["-t", "ab"], io));
+        assert_eq!(run.status, 2);
+        assert_eq!(run.stderr, "sort: multi-character tab 'ab'\n");
+        let run = testing::run("", |io| sort(Path::new("."), &["-x"], io));
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("sort: invalid option -- 'x'\n{}\n", USAGE)
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    fs::File,
+    io::{self, IsTerminal, PipeReader, PipeWriter, Read, Write},
+    process::Stdio,
+    thread,
+};
+
+use crate::signals;
+
+// Where a command reads its input from
+pub enum Input {
+    Inherit,
+    Pipe(PipeReader),
+    File(File),
+}
+
+// Where a command writes its output to
+pub enum Output {
+    Stdout,
+    Stderr,
+    Pipe(PipeWriter),
+    File(File),
+}
+
+// The standard streams of a single command, builtin or external
+pub struct Io {
+    pub stdin: Input,
+    pub stdout: Output,
+    pub stderr: Output,
+}
+
+impl Io {
+    pub fn inherit() -> Self {
+        Io {
+            stdin: Input::Inherit,
+            stdout: Output::Stdout,
+            stderr: Output::Stderr,
+        }
+    }
+
+    // Another set of handles to the same streams, for each command of a list or group
+    pub fn try_clone(&self) -> io::Result<Io> {
+        Ok(Io {
+            stdin: self.stdin.try_clone()?,
+            stdout: self.stdout.try_clone()?,
+            stderr: self.stderr.try_clone()?,
+        })
+    }
+}
+
+impl Input {
+    // Feeds a string (here-document, here-string) to the command through a pipe
+    pub fn from_string(content: String) -> io::Result<Input> {
+        let (reader, mut writer) = io::pipe()?;
+        thread::spawn(move || {
+            let _ = writer.write_all(content.as_bytes());
+        });
+        Ok(Input::Pipe(reader))
+    }
+
+    pub fn try_clone(&self) -> io::Result<Input> {
+        match self {
+            Input::Inherit => Ok(Input::Inherit),
+            Input::Pipe(reader) => Ok(Input::Pipe(reader.try_clone()?)),
+            Input::File(file) => Ok(Input::File(file.try_clone()?)),
+        }
+    }
+
+    pub fn to_stdio(&self) -> io::Result<Stdio> {
+        Ok(Stdio::from(self.try_clone()?))
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Input::Inherit => io::stdin().is_terminal(),
+            Input::Pipe(_) | Input::File(_) => false,
+        }
+    }
+}
+
+impl From<Input> for Stdio {
+    fn from(input: Input) -> Self {
+        match input {
+            Input::Inherit => Stdio::inherit(),
+            Input::Pipe(reader) => Stdio::from(reader),
+            Input::File(file) => Stdio::from(file),
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Inherit => loop {
+                match io::stdin().read(buf) {
+                    // Ctrl-C while a builtin reads from the terminal ends its input
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                        if signals::take_interrupt() {
+                            return Ok(0);
+                        }
+                    }
+                    result => return result,
+                }
+            },
+            Input::Pipe(reader) => reader.read(buf),
+            Input::File(file) => file.read(buf),
+        }
+    }
+}
+
+impl Output {
+    pub fn try_clone(&self) -> io::Result<Output> {
+        match self {
+            Output::Stdout => Ok(Output::Stdout),
+            Output::Stderr => Ok(Output::Stderr),
+            Output::Pipe(writer) => Ok(Output::Pipe(writer.try_clone()?)),
+            Output::File(file) => Ok(Output::File(file.try_clone()?)),
+        }
+    }
+
+    pub fn to_stdio(&self) -> io::Result<Stdio> {
+        Ok(Stdio::from(self.try_clone()?))
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Output::Stdout => io::stdout().is_terminal(),
+            Output::Stderr => io::stderr().is_terminal(),
+            Output::Pipe(_) | Output::File(_) => false,
+        }
+    }
+}
+
+impl From<Output> for Stdio {
+    fn from(output: Output) -> Self {
+        match output {
+            Output::Stdout => Stdio::from(io::stdout()),
+            Output::Stderr => Stdio::from(io::stderr()),
+            Output::Pipe(writer) => Stdio::from(writer),
+            Output::File(file) => Stdio::from(file),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout => io::stdout().write(buf),
+            Output::Stderr => io::stderr().write(buf),
+            Output::Pipe(writer) => writer.write(buf),
+            Output::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout => io::stdout().flush(),
+            Output::Stderr => io::stderr().flush(),
+            Output::Pipe(writer) => writer.flush(),
+            Output::File(file) => file.flush(),
+        }
+    }
+}
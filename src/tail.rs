@@ -0,0 +1,406 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{builtins, signals, stdio::Io};
+
+// How much of each input tail prints
+#[derive(Clone, Copy)]
+enum Count {
+    Lines(u64),
+    Bytes(u64),
+    // -n +N and -c +N: everything from line or byte N on
+    FromLine(u64),
+    FromByte(u64),
+}
+
+// A file that -f keeps reading as it grows
+struct Followed {
+    name: String,
+    file: File,
+    position: u64,
+}
+
+const USAGE: &str = "Usage: tail [-n LINES | -c BYTES] [-f] [-s SECONDS] [-qv] [FILE]...";
+
+// tail [OPTION]... [FILE]... prints the last 10 lines of each file, or of stdin without
+// files or for `-`. With -f it then waits for the files to grow and prints what is
+// appended until Ctrl-C.
+pub fn tail(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut count = Count::Lines(10);
+    let mut follow = false;
+    let mut interval = Duration::from_secs(1);
+    let mut headers = None;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (option, value) = match *arg {
+            "--" => {
+                files.extend(args.by_ref().copied());
+                break;
+            }
+            "--follow" => {
+                follow = true;
+                continue;
+            }
+            "--quiet" | "--silent" => {
+                headers = Some(false);
+                continue;
+            }
+            "--verbose" => {
+                headers = Some(true);
+                continue;
+            }
+            _ if arg.starts_with("--lines=") => ('n', Some(&arg["--lines=".len()..])),
+            _ if arg.starts_with("--bytes=") => ('c', Some(&arg["--bytes=".len()..])),
+            _ if arg.starts_with("--sleep-interval=") => {
+                ('s', Some(&arg["--sleep-interval=".len()..]))
+            }
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "tail: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            // The old forms: tail -5 and tail +5
+            _ if arg.len() > 1
+                && (arg.starts_with('-') || arg.starts_with('+'))
+                && arg[1..].bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                ('n', Some(*arg))
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                let mut option = None;
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'f' => follow = true,
+                        'q' => headers = Some(false),
+                        'v' => headers = Some(true),
+                        'n' | 'c' | 's' => {
+                            let rest = &arg[2 + i..];
+                            option = Some((flag, Some(rest).filter(|rest| !rest.is_empty())));
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "tail: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                match option {
+                    Some(option) => option,
+                    None => continue,
+                }
+            }
+            _ => {
+                files.push(*arg);
+                continue;
+            }
+        };
+
+        let Some(value) = value.or_else(|| args.next().copied()) else {
+            writeln!(
+                io.stderr,
+                "tail: option requires an argument -- '{}'",
+                option
+            )?;
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        };
+        if option == 's' {
+            match value.parse::<f64>() {
+                Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => {
+                    interval = Duration::from_secs_f64(seconds);
+                }
+                _ => {
+                    writeln!(io.stderr, "tail: invalid number of seconds: '{}'", value)?;
+                    return Ok(1);
+                }
+            }
+            continue;
+        }
+        let (from_start, digits) = match value.strip_prefix('+') {
+            Some(digits) => (true, digits),
+            None => (false, value.strip_prefix('-').unwrap_or(value)),
+        };
+        let Ok(number) = digits.parse::<u64>() else {
+            let what = if option == 'n' { "lines" } else { "bytes" };
+            writeln!(io.stderr, "tail: invalid number of {}: '{}'", what, value)?;
+            return Ok(1);
+        };
+        count = match (option, from_start) {
+            ('n', false) => Count::Lines(number),
+            ('n', true) => Count::FromLine(number),
+            (_, false) => Count::Bytes(number),
+            (_, true) => Count::FromByte(number),
+        };
+    }
+
+    if files.is_empty() {
+        files.push("-");
+    }
+    let headers = headers.unwrap_or(files.len() > 1);
+
+    let mut status = 0;
+    let mut followed = Vec::new();
+    for (i, name) in files.iter().enumerate() {
+        let header = match headers {
+            true => {
+                let shown = if *name == "-" { "standard input" } else { name };
+                let separator = if i > 0 { "\n" } else { "" };
+                format!("{}==> {} <==\n", separator, shown)
+            }
+            false => String::new(),
+        };
+
+        let result = if *name == "-" {
+            // Stdin can only be read to its end, there is nothing to follow
+            let mut stdin = io.stdin.try_clone()?;
+            write!(io.stdout, "{}", header)?;
+            print_tail(&mut stdin, count, io)
+        } else {
+            match builtins::resolve_path(current_dir, name)
+                .ok_or_else(|| io::Error::other("Could not determine home directory"))
+                .and_then(File::open)
+            {
+                Ok(mut file) => {
+                    write!(io.stdout, "{}", header)?;
+                    let result = print_file_tail(&mut file, count, io);
+                    if follow && result.is_ok() {
+                        let position = file.stream_position()?;
+                        followed.push(Followed {
+                            name: name.to_string(),
+                            file,
+                            position,
+                        });
+                    }
+                    result
+                }
+                Err(e) => {
+                    writeln!(io.stderr, "tail: cannot open '{}' for reading: {}", name, e)?;
+                    status = 1;
+                    continue;
+                }
+            }
+        };
+        if let Err(e) = result {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return Err(e);
+            }
+            writeln!(io.stderr, "tail: error reading '{}': {}", name, e)?;
+            status = 1;
+        }
+    }
+    io.stdout.flush()?;
+
+    if followed.is_empty() {
+        return Ok(status);
+    }
+    follow_files(&mut followed, headers, interval, io)
+}
+
+// Prints the end of a file, starting from the right place when it can seek there
+fn print_file_tail(file: &mut File, count: Count, io: &mut Io) -> io::Result<()> {
+    let length = file.metadata()?.len();
+    let start = match count {
+        _ if !file.metadata()?.is_file() => None,
+        Count::Lines(lines) => Some(last_lines_start(file, length, lines)?),
+        Count::Bytes(bytes) => Some(length.saturating_sub(bytes)),
+        Count::FromByte(byte) => Some(byte.saturating_sub(1).min(length)),
+        Count::FromLine(_) => None,
+    };
+    match start {
+        Some(start) => {
+            file.seek(SeekFrom::Start(start))?;
+            io::copy(file, &mut io.stdout)?;
+            Ok(())
+        }
+        None => print_tail(file, count, io),
+    }
+}
+
+// Where the last lines of a file begin, found by reading backwards from its end
+fn last_lines_start(file: &mut File, length: u64, lines: u64) -> io::Result<u64> {
+    if lines == 0 {
+        return Ok(length);
+    }
+    let mut buffer = vec![0; 64 * 1024];
+    let mut end = length;
+    let mut newlines = 0;
+    // A newline at the very end finishes the last line, it does not start another
+    let mut skip_last = true;
+
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+
+        for (i, byte) in chunk.iter().enumerate().rev() {
+            if *byte != b'\n' {
+                skip_last = false;
+                continue;
+            }
+            if skip_last {
+                skip_last = false;
+                continue;
+            }
+            newlines += 1;
+            if newlines == lines {
+                return Ok(start + i as u64 + 1);
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+// Prints the end of something that can only be read from the start
+fn print_tail(input: &mut impl Read, count: Count, io: &mut Io) -> io::Result<()> {
+    let mut reader = BufReader::new(input);
+    match count {
+        Count::Lines(lines) => {
+            let mut last = VecDeque::new();
+            loop {
+                let mut line = Vec::new();
+                if reader.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                last.push_back(line);
+                if last.len() as u64 > lines {
+                    last.pop_front();
+                }
+            }
+            for line in last {
+                io.stdout.write_all(&line)?;
+            }
+        }
+        Count::Bytes(bytes) => {
+            let mut last = Vec::new();
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                last.extend_from_slice(&buffer[..read]);
+                let extra = last.len().saturating_sub(bytes as usize);
+                last.drain(..extra);
+            }
+            io.stdout.write_all(&last)?;
+        }
+        Count::FromLine(line) => {
+            let mut skipped = Vec::new();
+            for _ in 1..line {
+                skipped.clear();
+                if reader.read_until(b'\n', &mut skipped)? == 0 {
+                    return Ok(());
+                }
+            }
+            io::copy(&mut reader, &mut io.stdout)?;
+        }
+        Count::FromByte(byte) => {
+            io::copy(
+                &mut reader.by_ref().take(byte.saturating_sub(1)),
+                &mut io::sink(),
+            )?;
+            io::copy(&mut reader, &mut io.stdout)?;
+        }
+    }
+    Ok(())
+}
+
+// -f: checks the files every interval and prints what was added, starting over when a
+// file got shorter. Ends with Ctrl-C.
+fn follow_files(
+    followed: &mut [Followed],
+    headers: bool,
+    interval: Duration,
+    io: &mut Io,
+) -> io::Result<i32> {
+    // The header is repeated whenever the output switches to another file
+    let mut last_shown = followed.len() - 1;
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        // Short naps, so Ctrl-C does not wait for the whole interval
+        let started = Instant::now();
+        loop {
+            if signals::interrupt_pending() {
+                return Ok(128 + signals::SIGINT);
+            }
+            let left = interval.saturating_sub(started.elapsed());
+            if left.is_zero() {
+                break;
+            }
+            thread::sleep(left.min(Duration::from_millis(50)));
+        }
+
+        for (i, entry) in followed.iter_mut().enumerate() {
+            let length = match entry.file.metadata() {
+                Ok(metadata) => metadata.len(),
+                Err(_) => continue,
+            };
+            if length < entry.position {
+                writeln!(io.stderr, "tail: {}: file truncated", entry.name)?;
+                entry.position = entry.file.seek(SeekFrom::Start(0))?;
+            }
+            loop {
+                let read = entry.file.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                if headers && last_shown != i {
+                    writeln!(io.stdout, "\n==> {} <==", entry.name)?;
+                    last_shown = i;
+                }
+                io.stdout.write_all(&buffer[..read])?;
+                entry.position += read as u64;
+            }
+        }
+        io.stdout.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    #[test]
+    fn prints_the_last_lines_or_bytes() {
+        let dir = TempDir::new("tail-last");
+        let input: String = (1..=12).map(|i| format!("{}\n", i)).collect();
+        dir.write("numbers", &input);
+        dir.write("short", "a\nb");
+        // The same answers whether the input can be read backwards or not
+        let tail_of = |args: &[&str]| {
+            let from_stdin = testing::run(&input, |io| tail(dir.path(), args, io));
+            let mut args = args.to_vec();
+            args.push("numbers");
+            let from_file = testing::run("", |io| tail(dir.path(), &args, io));
+            assert_eq!(from_stdin.stdout, from_file.stdout);
+            assert_eq!((from_file.status, from_file.stderr.as_str()), (0, ""));
+            from_file.stdout
+        };
+
+        assert_eq!(tail_of(&[]), input[input.find("3").unwrap()..]);
+        assert_eq!(tail_of(&["-n", "2"]), "11\n12\n");
+        assert_eq!(tail_of(&["-3"]), "10\n11\n12\n");
+        assert_eq!(tail_of(&["-n", "+11"]), "11\n12\n");
+        assert_eq!(tail_of(&["-c4"]), "\n12\n");
+        assert_eq!(tail_of(&["-c", "+25"]), "12\n");
+
+        let run = testing::run("", |io| tail(dir.path(), &["-n1", "short"], io));
+        assert_eq!(run.stdout, "b");
+        let run = testing::run("", |io| tail(dir.path(), &["-c", "x"], io));
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stderr, "tail: invalid number of bytes: 'x'\n");
+    }
+}
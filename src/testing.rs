@@ -0,0 +1,82 @@
+// Helpers for the unit tests of the builtins, which need real files to work on and
+// a way to see what they print
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::stdio::{Input, Io, Output};
+
+// A directory of its own under the temporary directory, removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("mini-shell-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    // Creates a file, and the directories above it
+    pub fn write(&self, name: &str, content: &str) {
+        let path = self.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.join(name)).unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// What a builtin printed and the status it returned
+pub struct Run {
+    pub status: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Runs a builtin with input as its stdin, collecting what it writes
+pub fn run(input: &str, builtin: impl FnOnce(&mut Io) -> io::Result<i32>) -> Run {
+    let collect = |mut reader: io::PipeReader| {
+        thread::spawn(move || {
+            let mut text = String::new();
+            reader.read_to_string(&mut text).unwrap();
+            text
+        })
+    };
+    let (stdout, stdout_writer) = io::pipe().unwrap();
+    let (stderr, stderr_writer) = io::pipe().unwrap();
+    let (stdout, stderr) = (collect(stdout), collect(stderr));
+
+    let mut io = Io {
+        stdin: Input::from_string(input.to_string()).unwrap(),
+        stdout: Output::Pipe(stdout_writer),
+        stderr: Output::Pipe(stderr_writer),
+    };
+    let status = builtin(&mut io).unwrap();
+    drop(io);
+
+    Run {
+        status,
+        stdout: stdout.join().unwrap(),
+        stderr: stderr.join().unwrap(),
+    }
+}
@@ -0,0 +1,219 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::parser::TimeFormat;
+
+// CPU time used so far by the shell (builtins and subshell threads included) and by
+// the child processes it has waited for, as (user, system)
+#[cfg(unix)]
+pub fn cpu_times() -> (Duration, Duration) {
+    fn usage(who: libc::c_int) -> (Duration, Duration) {
+        // SAFETY: getrusage only writes to the struct it is given
+        let usage = unsafe {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            libc::getrusage(who, &mut usage);
+            usage
+        };
+        let duration = |time: libc::timeval| {
+            Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+        };
+        (duration(usage.ru_utime), duration(usage.ru_stime))
+    }
+
+    let (own_user, own_system) = usage(libc::RUSAGE_SELF);
+    let (child_user, child_system) = usage(libc::RUSAGE_CHILDREN);
+    (own_user + child_user, own_system + child_system)
+}
+
+#[cfg(not(unix))]
+pub fn cpu_times() -> (Duration, Duration) {
+    (Duration::ZERO, Duration::ZERO)
+}
+
+// What `time` prints once the pipeline is done
+pub fn report(format: TimeFormat, real: Duration, user: Duration, system: Duration) -> String {
+    let times = [("real", real), ("user", user), ("sys", system)];
+    match format {
+        TimeFormat::Bash => {
+            let mut report = String::from("\n");
+            for (name, time) in times {
+                let seconds = time.as_secs_f64();
+                let minutes = (seconds / 60.0).floor();
+                report.push_str(&format!(
+                    "{}\t{}m{:.3}s\n",
+                    name,
+                    minutes,
+                    seconds - minutes * 60.0
+                ));
+            }
+            report
+        }
+        TimeFormat::Posix => times
+            .iter()
+            .map(|(name, time)| format!("{} {:.2}\n", name, time.as_secs_f64()))
+            .collect(),
+    }
+}
+
+// Short form for the "took ..." line: 850ms, 12.3s, 4m 5s, 1h 2m
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h {}m", seconds / 3600, seconds / 60 % 60)
+    } else if seconds >= 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else if seconds >= 1 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+pub const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+pub const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Broken down local time
+pub struct Time {
+    pub year: i64,
+    // 1 to 12
+    pub month: u32,
+    pub day: u32,
+    // 0 is Sunday
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+// Seconds since the Unix epoch
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+#[cfg(unix)]
+pub fn local_time(seconds: i64) -> Time {
+    let seconds = seconds as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm it is given
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&seconds, &mut tm);
+        tm
+    };
+    Time {
+        year: i64::from(tm.tm_year) + 1900,
+        month: tm.tm_mon as u32 + 1,
+        day: tm.tm_mday as u32,
+        weekday: tm.tm_wday as u32,
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+    }
+}
+
+// Without the C library's time zone database times are in UTC
+#[cfg(not(unix))]
+pub fn local_time(seconds: i64) -> Time {
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400) as u32;
+
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's civil_from_days)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    Time {
+        year,
+        month,
+        day,
+        weekday: (days + 4).rem_euclid(7) as u32,
+        hour: time / 3600,
+        minute: time / 60 % 60,
+        second: time % 60,
+    }
+}
+
+// Seconds since the Unix epoch for a local date and time. Out of range fields carry
+// over like mktime does, so month 13 is January of the next year. None when a field or
+// the result does not fit.
+#[cfg(unix)]
+pub fn local_timestamp(
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+) -> Option<i64> {
+    let field = |value: i64| libc::c_int::try_from(value).ok();
+    // SAFETY: tm is plain integers (and a null zone name), all zeros is a valid value
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = field(year.checked_sub(1900)?)?;
+    tm.tm_mon = field(month.checked_sub(1)?)?;
+    tm.tm_mday = field(day)?;
+    tm.tm_hour = field(hour)?;
+    tm.tm_min = field(minute)?;
+    tm.tm_sec = field(second)?;
+    // Let the time zone database decide whether daylight saving time applies
+    tm.tm_isdst = -1;
+    // SAFETY: mktime only reads and normalizes the tm it is given
+    let timestamp = unsafe { libc::mktime(&mut tm) };
+    // -1 is also one second before the epoch, which leaves the year at 1969
+    match timestamp == -1 && tm.tm_year != 69 {
+        true => None,
+        false => Some(timestamp as i64),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn local_timestamp(
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+) -> Option<i64> {
+    // Years beyond what 32-bit fields hold, as with mktime
+    let field = |value: i64| i32::try_from(value).ok().map(i64::from);
+    let (year, month, day) = (field(year)?, field(month)?, field(day)?);
+    let (hour, minute, second) = (field(hour)?, field(minute)?, field(second)?);
+    let year = year + (month - 1).div_euclid(12);
+    let month = (month - 1).rem_euclid(12) + 1;
+
+    // A civil date to days since 1970-01-01 (Howard Hinnant's days_from_civil)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_millis(250)), "250ms");
+        assert_eq!(format_duration(Duration::from_millis(5300)), "5.3s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 5s");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 125)),
+            "3h 2m"
+        );
+    }
+}
@@ -0,0 +1,513 @@
+use std::{
+    fs::{self, File, FileTimes, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{builtins, stdio::Io, timing};
+
+// Where the new timestamps come from
+enum Source {
+    Now,
+    // -d or -t
+    Date(SystemTime),
+    // -r copies both times of another file
+    Reference(SystemTime, SystemTime),
+}
+
+const USAGE: &str = "Usage: touch [-acm] [-d DATE | -t STAMP | -r FILE] FILE...";
+
+// touch [OPTION]... FILE... sets the access and modification times of the files, creating
+// the ones that do not exist unless -c is given
+pub fn touch(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut access = false;
+    let mut modification = false;
+    let mut no_create = false;
+    let mut source = Source::Now;
+    let mut sources = 0;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // Options that take a value: (-d, "2024-01-01") from `-d 2024-01-01`, `-d2024-01-01`
+        // or `--date=2024-01-01`
+        let (option, value) = match *arg {
+            "--" => {
+                files.extend(args.by_ref());
+                break;
+            }
+            "--no-create" => {
+                no_create = true;
+                continue;
+            }
+            "--time=access" | "--time=atime" | "--time=use" => {
+                access = true;
+                continue;
+            }
+            "--time=modify" | "--time=mtime" => {
+                modification = true;
+                continue;
+            }
+            _ if arg.starts_with("--") => match arg.split_once('=') {
+                Some(("--date", value)) => ('d', Some(value.to_string())),
+                Some(("--reference", value)) => ('r', Some(value.to_string())),
+                None if matches!(*arg, "--date" | "--reference") => {
+                    (arg.as_bytes()[2] as char, None)
+                }
+                _ => {
+                    writeln!(io.stderr, "touch: unrecognized option '{}'", arg)?;
+                    writeln!(io.stderr, "{}", USAGE)?;
+                    return Ok(2);
+                }
+            },
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                let mut option = None;
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'a' => access = true,
+                        'm' => modification = true,
+                        'c' => no_create = true,
+                        // Accepted for compatibility
+                        'f' => {}
+                        'd' | 'r' | 't' => {
+                            let rest = &arg[2 + i..];
+                            option = Some((flag, (!rest.is_empty()).then(|| rest.to_string())));
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "touch: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                match option {
+                    Some(option) => option,
+                    None => continue,
+                }
+            }
+            _ => {
+                files.push(*arg);
+                continue;
+            }
+        };
+
+        let Some(value) = value.or_else(|| args.next().map(|value| value.to_string())) else {
+            writeln!(
+                io.stderr,
+                "touch: option requires an argument -- '{}'",
+                option
+            )?;
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        };
+        sources += 1;
+        source = match option {
+            'd' => match parse_date(&value) {
+                Some(time) => Source::Date(time),
+                None => {
+                    writeln!(io.stderr, "touch: invalid date format '{}'", value)?;
+                    return Ok(1);
+                }
+            },
+            't' => match parse_stamp(&value) {
+                Some(time) => Source::Date(time),
+                None => {
+                    writeln!(io.stderr, "touch: invalid date format '{}'", value)?;
+                    return Ok(1);
+                }
+            },
+            _ => {
+                let reference = builtins::resolve_path(current_dir, &value).unwrap_or_default();
+                match fs::metadata(&reference).and_then(|m| Ok((m.accessed()?, m.modified()?))) {
+                    Ok((accessed, modified)) => Source::Reference(accessed, modified),
+                    Err(e) => {
+                        writeln!(
+                            io.stderr,
+                            "touch: failed to get attributes of '{}': {}",
+                            value, e
+                        )?;
+                        return Ok(1);
+                    }
+                }
+            }
+        };
+    }
+
+    if sources > 1 {
+        writeln!(
+            io.stderr,
+            "touch: cannot specify times from more than one source"
+        )?;
+        return Ok(2);
+    }
+    if files.is_empty() {
+        writeln!(io.stderr, "touch: missing file operand")?;
+        writeln!(io.stderr, "{}", USAGE)?;
+        return Ok(2);
+    }
+    // Neither -a nor -m changes both
+    if !access && !modification {
+        access = true;
+        modification = true;
+    }
+
+    let now = SystemTime::now();
+    let (accessed, modified) = match source {
+        Source::Now => (now, now),
+        Source::Date(time) => (time, time),
+        Source::Reference(accessed, modified) => (accessed, modified),
+    };
+    let mut times = FileTimes::new();
+    if access {
+        times = times.set_accessed(accessed);
+    }
+    if modification {
+        times = times.set_modified(modified);
+    }
+
+    let mut status = 0;
+    for name in files {
+        let Some(path) = builtins::resolve_path(current_dir, name) else {
+            writeln!(io.stderr, "touch: Could not determine home directory")?;
+            status = 1;
+            continue;
+        };
+
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if no_create {
+                    continue;
+                }
+                match OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(&path)
+                {
+                    Ok(file) => file,
+                    Err(e) => {
+                        writeln!(io.stderr, "touch: cannot touch '{}': {}", name, e)?;
+                        status = 1;
+                        continue;
+                    }
+                }
+            }
+            // Not readable, a write-only handle can set the times as well
+            Err(e) => match OpenOptions::new().write(true).open(&path) {
+                Ok(file) => file,
+                Err(_) => {
+                    writeln!(io.stderr, "touch: cannot touch '{}': {}", name, e)?;
+                    status = 1;
+                    continue;
+                }
+            },
+        };
+
+        if let Err(e) = file.set_times(times) {
+            writeln!(io.stderr, "touch: setting times of '{}': {}", name, e)?;
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+// None when the time is too far off for the platform to hold
+fn system_time(seconds: i64) -> Option<SystemTime> {
+    match u64::try_from(seconds) {
+        Ok(seconds) => UNIX_EPOCH.checked_add(Duration::from_secs(seconds)),
+        Err(_) => UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs())),
+    }
+}
+
+// -t [[CC]YY]MMDDhhmm[.ss], the year defaults to the current one and two-digit years
+// 69 to 99 are in the 1900s
+fn parse_stamp(stamp: &str) -> Option<SystemTime> {
+    let (digits, second) = match stamp.split_once('.') {
+        Some((digits, second)) if second.len() == 2 => (digits, second.parse::<i64>().ok()?),
+        Some(_) => return None,
+        None => (stamp, 0),
+    };
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |at: usize| digits[at..at + 2].parse::<i64>().ok();
+
+    let (year, rest) = match digits.len() {
+        8 => (timing::local_time(timing::unix_now()).year, 0),
+        10 => match field(0)? {
+            year @ 69..=99 => (1900 + year, 2),
+            year => (2000 + year, 2),
+        },
+        12 => (digits[..4].parse().ok()?, 4),
+        _ => return None,
+    };
+    let (month, day, hour, minute) = (
+        field(rest)?,
+        field(rest + 2)?,
+        field(rest + 4)?,
+        field(rest + 6)?,
+    );
+    valid_date(month, day).then_some(())?;
+    valid_time(hour, minute, second).then_some(())?;
+    system_time(timing::local_timestamp(
+        year, month, day, hour, minute, second,
+    )?)
+}
+
+fn valid_date(month: i64, day: i64) -> bool {
+    (1..=12).contains(&month) && (1..=31).contains(&day)
+}
+
+fn valid_time(hour: i64, minute: i64, second: i64) -> bool {
+    (0..24).contains(&hour) && (0..60).contains(&minute) && (0..=60).contains(&second)
+}
+
+// The date strings -d understands, a combination of:
+//   now, today, yesterday, tomorrow
+//   a date 2024-05-26, optionally with a time as 2024-05-26T13:45
+//   a time of day 13:45 or 13:45:30
+//   relative amounts like `2 hours`, `-3 days`, `+1 week`, `1 month ago`
+// or @SECONDS since the epoch. A date alone means midnight, a time alone today.
+fn parse_date(text: &str) -> Option<SystemTime> {
+    let text = text.trim().to_lowercase();
+    if let Some(seconds) = text.strip_prefix('@') {
+        return system_time(seconds.parse().ok()?);
+    }
+
+    let mut date = None;
+    let mut time = None;
+    let mut seconds: i64 = 0;
+    let mut months: i64 = 0;
+    // The last relative amount as (seconds, months), for `ago`
+    let mut last = None;
+
+    let mut words = text.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        match word {
+            "now" | "today" => {}
+            "yesterday" => seconds = seconds.checked_sub(86400)?,
+            "tomorrow" => seconds = seconds.checked_add(86400)?,
+            "ago" => {
+                let (relative_seconds, relative_months): (i64, i64) = last.take()?;
+                seconds = seconds.checked_sub(relative_seconds.checked_mul(2)?)?;
+                months = months.checked_sub(relative_months.checked_mul(2)?)?;
+            }
+            _ if word.contains('-') && word.as_bytes()[0].is_ascii_digit() => {
+                let (day, clock) = match word.split_once('t') {
+                    Some((day, clock)) => (day, Some(clock)),
+                    None => (word, None),
+                };
+                let mut fields = day.split('-').map(|field| field.parse::<i64>().ok());
+                let (year, month, day) = (fields.next()??, fields.next()??, fields.next()??);
+                if fields.next().is_some() || !valid_date(month, day) {
+                    return None;
+                }
+                date = Some((year, month, day));
+                if let Some(clock) = clock {
+                    time = Some(parse_clock(clock)?);
+                }
+            }
+            _ if word.contains(':') => time = Some(parse_clock(word)?),
+            _ => {
+                // A number and then a unit, or a unit alone for one of it
+                let (amount, unit) = match word.parse::<i64>() {
+                    Ok(amount) => (amount, words.next()?),
+                    Err(_) => (1, word),
+                };
+                let (seconds_per_unit, months_per_unit) =
+                    match unit.strip_suffix('s').unwrap_or(unit) {
+                        "sec" | "second" => (1, 0),
+                        "min" | "minute" => (60, 0),
+                        "hour" => (3600, 0),
+                        "day" => (86400, 0),
+                        "week" => (7 * 86400, 0),
+                        "fortnight" => (14 * 86400, 0),
+                        "month" => (0, 1),
+                        "year" => (0, 12),
+                        _ => return None,
+                    };
+                // Amounts too large to add up are not a date
+                let relative_seconds = amount.checked_mul(seconds_per_unit)?;
+                let relative_months = amount.checked_mul(months_per_unit)?;
+                seconds = seconds.checked_add(relative_seconds)?;
+                months = months.checked_add(relative_months)?;
+                last = Some((relative_seconds, relative_months));
+            }
+        }
+    }
+
+    let now = timing::local_time(timing::unix_now());
+    let (year, month, day) = date.unwrap_or((now.year, i64::from(now.month), i64::from(now.day)));
+    let (hour, minute, second) = match (date, time) {
+        (_, Some(time)) => time,
+        (Some(_), None) => (0, 0, 0),
+        (None, None) => (
+            i64::from(now.hour),
+            i64::from(now.minute),
+            i64::from(now.second),
+        ),
+    };
+    let month = month.checked_add(months)?;
+    let timestamp = timing::local_timestamp(year, month, day, hour, minute, second)?;
+    system_time(timestamp.checked_add(seconds)?)
+}
+
+// HH:MM or HH:MM:SS
+fn parse_clock(clock: &str) -> Option<(i64, i64, i64)> {
+    let mut fields = clock.split(':').map(|field| field.parse::<i64>().ok());
+    let (hour, minute) = (fields.next()??, fields.next()??);
+    let second = fields.next().unwrap_or(Some(0))?;
+    if fields.next().is_some() || !valid_time(hour, minute, second) {
+        return None;
+    }
+    Some((hour, minute, second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    fn date(text: &str) -> SystemTime {
+        parse_date(text).unwrap_or_else(|| panic!("{:?} should parse", text))
+    }
+
+    #[test]
+    fn parses_stamps() {
+        assert_eq!(
+            parse_stamp("202405261345.30"),
+            Some(date("2024-05-26 13:45:30"))
+        );
+        assert_eq!(parse_stamp("0405261345"), Some(date("2004-05-26 13:45")));
+        assert_eq!(parse_stamp("7001011200"), Some(date("1970-01-01 12:00")));
+        for stamp in [
+            "",
+            "2405261345.3",
+            "24x5261345",
+            "202413261345",
+            "202405262460",
+            "2024052613",
+            "2024052613450",
+        ] {
+            assert_eq!(parse_stamp(stamp), None, "{:?}", stamp);
+        }
+    }
+
+    #[test]
+    fn parses_dates_and_times() {
+        assert_eq!(date("@0"), UNIX_EPOCH);
+        assert_eq!(date("@-86400"), UNIX_EPOCH - Duration::from_secs(86400));
+        assert_eq!(date("2024-05-26T13:45"), date("2024-05-26 13:45:00"));
+        assert_eq!(date(" 2024-05-26 "), date("2024-05-26t00:00"));
+    }
+
+    #[test]
+    fn adds_relative_amounts() {
+        assert_eq!(
+            date("2024-05-26t10:00 2 hours ago"),
+            date("2024-05-26 08:00")
+        );
+        assert_eq!(date("2024-05-26 +1 week"), date("2024-06-02"));
+        assert_eq!(date("2024-05-26 -3 days"), date("2024-05-23"));
+        assert_eq!(date("2024-05-26 fortnight"), date("2024-06-09"));
+        assert_eq!(date("2024-05-26 tomorrow"), date("2024-05-27"));
+        assert_eq!(
+            date("2024-05-26 90 min 30 secs"),
+            date("2024-05-26 01:30:30")
+        );
+        assert_eq!(date("2024-05-26 1 year ago"), date("2023-05-26"));
+        // Months carry over like mktime does
+        assert_eq!(date("2024-01-31 1 month"), date("2024-03-02"));
+    }
+
+    #[test]
+    fn rejects_bad_dates() {
+        for text in [
+            "2024-13-01",
+            "2024-05-32",
+            "2024-05",
+            "2024-05-26t25:00",
+            "12:60",
+            "1:2:3:4",
+            "3 parsecs",
+            "2 hours ago ago",
+            "ago",
+            "5",
+            "@x",
+        ] {
+            assert_eq!(parse_date(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_dates_out_of_range() {
+        for text in [
+            "9999999999999999 weeks",
+            "9223372036854775807 seconds",
+            "9223372036854775807 seconds 1 second",
+            "-9223372036854775807 seconds yesterday",
+            "4611686018427387904 seconds ago",
+            "9223372036854775807 years",
+            "9999999999 months",
+            "9999999999-01-01",
+        ] {
+            assert_eq!(parse_date(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn sets_the_chosen_times() {
+        let dir = TempDir::new("touch");
+        let run = testing::run("", |io| touch(dir.path(), &["-d", "@86400", "a"], io));
+        assert_eq!(run.status, 0);
+        let metadata = fs::metadata(dir.join("a")).unwrap();
+        assert_eq!(
+            metadata.modified().unwrap(),
+            UNIX_EPOCH + Duration::from_secs(86400)
+        );
+
+        // -m leaves the access time alone, -r copies the times of another file
+        let run = testing::run("", |io| {
+            touch(dir.path(), &["-m", "-t", "197001030000", "a"], io)
+        });
+        assert_eq!(run.status, 0);
+        let run = testing::run("", |io| touch(dir.path(), &["-r", "a", "b"], io));
+        assert_eq!(run.status, 0);
+        let (a, b) = (
+            fs::metadata(dir.join("a")).unwrap(),
+            fs::metadata(dir.join("b")).unwrap(),
+        );
+        assert_eq!(b.modified().unwrap(), a.modified().unwrap());
+        assert_eq!(a.modified().unwrap(), date("1970-01-03"));
+
+        let run = testing::run("", |io| touch(dir.path(), &["-c", "missing"], io));
+        assert_eq!(run.status, 0);
+        assert!(!dir.join("missing").exists());
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let dir = TempDir::new("touch-errors");
+        let touch = |args: &[&str]| testing::run("", |io| touch(dir.path(), args, io));
+
+        let run = touch(&["-d", "someday", "a"]);
+        assert_eq!(
+            (run.status, run.stderr.as_str()),
+            (1, "touch: invalid date format 'someday'\n")
+        );
+        let run = touch(&["-d", "9999999999999999 weeks", "a"]);
+        assert_eq!(
+            (run.status, run.stderr.as_str()),
+            (1, "touch: invalid date format '9999999999999999 weeks'\n")
+        );
+        assert_eq!(touch(&["-d"]).status, 2);
+        assert_eq!(touch(&["-x", "a"]).status, 2);
+        assert_eq!(touch(&["-d", "@0", "-t", "202401010000", "a"]).status, 2);
+        assert_eq!(touch(&["-a"]).status, 2);
+        assert!(!dir.join("a").exists());
+    }
+}
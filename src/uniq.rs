@@ -0,0 +1,288 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Write},
+    path::Path,
+};
+
+use crate::{builtins, signals, stdio::Io};
+
+// Which part of a line is compared
+struct Compare {
+    skip_fields: usize,
+    skip_chars: usize,
+    // -w: at most this many characters after the skipped ones
+    width: Option<usize>,
+    ignore_case: bool,
+}
+
+impl Compare {
+    fn part<'a>(&self, line: &'a [u8]) -> &'a [u8] {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let mut at = 0;
+        for _ in 0..self.skip_fields {
+            while at < line.len() && is_blank(line[at]) {
+                at += 1;
+            }
+            while at < line.len() && !is_blank(line[at]) {
+                at += 1;
+            }
+        }
+        let start = (at + self.skip_chars).min(line.len());
+        let end = match self.width {
+            Some(width) => (start + width).min(line.len()),
+            None => line.len(),
+        };
+        &line[start..end]
+    }
+
+    fn same(&self, a: &[u8], b: &[u8]) -> bool {
+        let (a, b) = (self.part(a), self.part(b));
+        match self.ignore_case {
+            true => a.eq_ignore_ascii_case(b),
+            false => a == b,
+        }
+    }
+}
+
+const USAGE: &str = "Usage: uniq [-cdui] [-f FIELDS] [-s CHARS] [-w CHARS] [INPUT [OUTPUT]]";
+
+// uniq [OPTION]... [INPUT [OUTPUT]] prints the lines of INPUT, or of stdin, with runs of
+// equal adjacent lines folded into one. -c prefixes how often each line was repeated,
+// -d prints only the repeated lines and -u only the ones that were not.
+pub fn uniq(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut compare = Compare {
+        skip_fields: 0,
+        skip_chars: 0,
+        width: None,
+        ignore_case: false,
+    };
+    let mut count = false;
+    let mut repeated = false;
+    let mut unique = false;
+    let mut operands = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (option, value) = match *arg {
+            "--" => {
+                operands.extend(args.by_ref().copied());
+                break;
+            }
+            "--count" => {
+                count = true;
+                continue;
+            }
+            "--repeated" => {
+                repeated = true;
+                continue;
+            }
+            "--unique" => {
+                unique = true;
+                continue;
+            }
+            "--ignore-case" => {
+                compare.ignore_case = true;
+                continue;
+            }
+            _ if arg.starts_with("--skip-fields=") => ('f', Some(&arg["--skip-fields=".len()..])),
+            _ if arg.starts_with("--skip-chars=") => ('s', Some(&arg["--skip-chars=".len()..])),
+            _ if arg.starts_with("--check-chars=") => ('w', Some(&arg["--check-chars=".len()..])),
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "uniq: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                let mut option = None;
+                for (i, flag) in arg[1..].char_indices() {
+                    match flag {
+                        'c' => count = true,
+                        'd' => repeated = true,
+                        'u' => unique = true,
+                        'i' => compare.ignore_case = true,
+                        'f' | 's' | 'w' => {
+                            let rest = &arg[2 + i..];
+                            option = Some((flag, Some(rest).filter(|rest| !rest.is_empty())));
+                            break;
+                        }
+                        _ => {
+                            writeln!(io.stderr, "uniq: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+                match option {
+                    Some(option) => option,
+                    None => continue,
+                }
+            }
+            _ => {
+                operands.push(*arg);
+                continue;
+            }
+        };
+
+        let Some(value) = value.or_else(|| args.next().copied()) else {
+            writeln!(
+                io.stderr,
+                "uniq: option requires an argument -- '{}'",
+                option
+            )?;
+            writeln!(io.stderr, "{}", USAGE)?;
+            return Ok(2);
+        };
+        let Ok(number) = value.parse::<usize>() else {
+            let what = match option {
+                'f' => "fields to skip",
+                's' => "bytes to skip",
+                _ => "bytes to compare",
+            };
+            writeln!(io.stderr, "uniq: {}: invalid number of {}", value, what)?;
+            return Ok(1);
+        };
+        match option {
+            'f' => compare.skip_fields = number,
+            's' => compare.skip_chars = number,
+            _ => compare.width = Some(number),
+        }
+    }
+
+    if operands.len() > 2 {
+        writeln!(io.stderr, "uniq: extra operand '{}'", operands[2])?;
+        writeln!(io.stderr, "{}", USAGE)?;
+        return Ok(2);
+    }
+    let input_name = operands.first().copied().unwrap_or("-");
+    let input = match builtins::open_input(current_dir, input_name, io) {
+        Ok(input) => input,
+        Err(e) => {
+            writeln!(io.stderr, "uniq: {}: {}", input_name, e)?;
+            return Ok(1);
+        }
+    };
+    let mut output: Box<dyn Write> = match operands.get(1) {
+        Some(name) if *name != "-" => {
+            match builtins::resolve_path(current_dir, name)
+                .ok_or_else(|| io::Error::other("Could not determine home directory"))
+                .and_then(File::create)
+            {
+                Ok(file) => Box::new(io::BufWriter::new(file)),
+                Err(e) => {
+                    writeln!(io.stderr, "uniq: {}: {}", name, e)?;
+                    return Ok(1);
+                }
+            }
+        }
+        _ => Box::new(io.stdout.try_clone()?),
+    };
+
+    // Prints a run of equal lines by its first one
+    let mut print = |line: &[u8], times: u64| -> io::Result<()> {
+        if (repeated && times == 1) || (unique && times > 1) {
+            return Ok(());
+        }
+        if count {
+            write!(output, "{:>7} ", times)?;
+        }
+        output.write_all(line)?;
+        if !line.ends_with(b"\n") {
+            output.write_all(b"\n")?;
+        }
+        Ok(())
+    };
+
+    let mut reader = BufReader::new(input);
+    let mut first = Vec::new();
+    let mut times = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = match builtins::read_line(&mut reader, &mut line) {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                output.flush()?;
+                return Ok(128 + signals::SIGINT);
+            }
+            Err(e) => {
+                writeln!(io.stderr, "uniq: {}: {}", input_name, e)?;
+                return Ok(1);
+            }
+        };
+        if read == 0 {
+            break;
+        }
+        if times > 0 && compare.same(&first, &line) {
+            times += 1;
+            continue;
+        }
+        if times > 0 {
+            print(&first, times)?;
+        }
+        std::mem::swap(&mut first, &mut line);
+        times = 1;
+    }
+    if times > 0 {
+        print(&first, times)?;
+    }
+    output.flush()?;
+
+    Ok(0)
+}
+
+fn is_blank(byte: u8) -> bool {
+    byte == b' ' || byte == b'\t'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn uniq_lines(input: &str, args: &[&str]) -> String {
+        let run = testing::run(input, |io| uniq(Path::new("."), args, io));
+        assert_eq!((run.status, run.stderr.as_str()), (0, ""));
+        run.stdout
+    }
+
+    #[test]
+    fn counts_runs_of_lines() {
+        let input = "a\na\nb\na\nc\nc\nc";
+        assert_eq!(uniq_lines(input, &[]), "a\nb\na\nc\n");
+        assert_eq!(
+            uniq_lines(input, &["-c"]),
+            "      2 a\n      1 b\n      1 a\n      3 c\n"
+        );
+        assert_eq!(uniq_lines(input, &["-d"]), "a\nc\n");
+        assert_eq!(uniq_lines(input, &["-u"]), "b\na\n");
+        assert_eq!(uniq_lines(input, &["-cd"]), "      2 a\n      3 c\n");
+        assert_eq!(uniq_lines("", &["-c"]), "");
+    }
+
+    #[test]
+    fn compares_parts_of_lines() {
+        assert_eq!(uniq_lines("A\na\nb\n", &["-i"]), "A\nb\n");
+        assert_eq!(uniq_lines("1 x\n2 x\n3 y\n", &["-f", "1"]), "1 x\n3 y\n");
+        assert_eq!(uniq_lines("1x\n2x\n", &["-s1"]), "1x\n");
+        assert_eq!(uniq_lines("ab\nac\nbc\n", &["-w", "1"]), "ab\nbc\n");
+
+        let compare = Compare {
+            skip_fields: 1,
+            skip_chars: 1,
+            width: Some(2),
+            ignore_case: false,
+        };
+        assert_eq!(compare.part(b"a  bcde\n"), b" b");
+        assert_eq!(compare.part(b"a"), b"");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let run = testing::run("", |io| uniq(Path::new("."), &["-f", "x"], io));
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stderr, "uniq: x: invalid number of fields to skip\n");
+        let run = testing::run("", |io| uniq(Path::new("."), &["a", "b", "c"], io));
+        assert_eq!(run.status, 2);
+        assert_eq!(run.stderr, format!("uniq: extra operand 'c'\n{}\n", USAGE));
+    }
+}
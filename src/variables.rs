@@ -0,0 +1,205 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, Write},
+    process::Command,
+};
+
+use crate::stdio::Io;
+
+#[derive(Clone)]
+pub struct Variable {
+    pub value: String,
+    // Exported variables make up the environment of every child process
+    pub exported: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct Variables {
+    variables: BTreeMap<String, Variable>,
+}
+
+impl Variables {
+    // Starts out with the shell's own environment, all of it exported
+    pub fn from_env() -> Self {
+        let variables = env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .map(|(name, value)| {
+                (
+                    name,
+                    Variable {
+                        value,
+                        exported: true,
+                    },
+                )
+            })
+            .collect();
+        Variables { variables }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(|v| v.value.as_str())
+    }
+
+    pub fn get_variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.get(name)
+    }
+
+    // Sets a value, an exported variable stays exported
+    pub fn set(&mut self, name: &str, value: String) {
+        match self.variables.get_mut(name) {
+            Some(variable) => variable.value = value,
+            None => {
+                self.variables.insert(
+                    name.to_string(),
+                    Variable {
+                        value,
+                        exported: false,
+                    },
+                );
+            }
+        }
+    }
+
+    // Marks a variable for export, giving it a value first when there is one.
+    // Exporting a name that was never set creates it empty.
+    pub fn export(&mut self, name: &str, value: Option<String>) {
+        let variable = self
+            .variables
+            .entry(name.to_string())
+            .or_insert_with(|| Variable {
+                value: String::new(),
+                exported: true,
+            });
+        variable.exported = true;
+        if let Some(value) = value {
+            variable.value = value;
+        }
+    }
+
+    pub fn unexport(&mut self, name: &str) {
+        if let Some(variable) = self.variables.get_mut(name) {
+            variable.exported = false;
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.variables.remove(name);
+    }
+
+    // Puts back a variable saved with get_variable, None unsets it
+    pub fn restore(&mut self, name: &str, variable: Option<Variable>) {
+        match variable {
+            Some(variable) => {
+                self.variables.insert(name.to_string(), variable);
+            }
+            None => self.unset(name),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Variable)> {
+        self.variables.iter().map(|(name, v)| (name.as_str(), v))
+    }
+
+    pub fn exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.iter()
+            .filter(|(_, v)| v.exported)
+            .map(|(name, v)| (name, v.value.as_str()))
+    }
+
+    // Gives a child exactly the exported variables as its environment
+    pub fn apply_to(&self, cmd: &mut Command) {
+        cmd.env_clear().envs(self.exported());
+    }
+}
+
+// Names start with a letter or underscore, followed by letters, digits and underscores
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Splits `NAME=value`, None when the part before `=` is not a valid name
+pub fn split_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, value) = word.split_once('=')?;
+    is_valid_name(name).then_some((name, value))
+}
+
+// Quotes a value so it can be read back by the shell, as `set` and `export -p` print them
+pub fn quote_value(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:,+@%=".contains(c))
+    {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+// export NAME[=value]... marks variables for the environment of commands, -n takes the
+// mark away again. With no names (or -p) the exported variables are listed.
+pub fn export_variables(variables: &mut Variables, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let (unexport, names) = match args {
+        ["-n", names @ ..] => (true, names),
+        ["-p", names @ ..] => (false, names),
+        _ => (false, args),
+    };
+
+    if names.is_empty() && !unexport {
+        for (name, value) in variables.exported() {
+            writeln!(io.stdout, "export {}={}", name, quote_value(value))?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in names {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (*arg, None),
+        };
+        if !is_valid_name(name) {
+            writeln!(io.stderr, "export: `{}': not a valid identifier", arg)?;
+            status = 1;
+            continue;
+        }
+
+        if unexport {
+            if let Some(value) = value {
+                variables.set(name, value);
+            }
+            variables.unexport(name);
+        } else {
+            variables.export(name, value);
+        }
+    }
+
+    Ok(status)
+}
+
+pub fn unset_variables(variables: &mut Variables, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let names = args.strip_prefix(&["-v"]).unwrap_or(args);
+    let mut status = 0;
+    for name in names {
+        if is_valid_name(name) {
+            variables.unset(name);
+        } else {
+            writeln!(io.stderr, "unset: `{}': not a valid identifier", name)?;
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+// Lists every variable the way `set` does, quoted so the output can be read back in
+pub fn print_variables(variables: &Variables, io: &mut Io) -> io::Result<i32> {
+    for (name, variable) in variables.iter() {
+        writeln!(io.stdout, "{}={}", name, quote_value(&variable.value))?;
+    }
+    Ok(0)
+}
@@ -0,0 +1,221 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{builtins, signals, stdio::Io};
+
+#[derive(Default, Clone, Copy)]
+struct Counts {
+    lines: u64,
+    words: u64,
+    chars: u64,
+    bytes: u64,
+}
+
+// Which columns to print, in this order
+struct Columns {
+    lines: bool,
+    words: bool,
+    chars: bool,
+    bytes: bool,
+}
+
+const USAGE: &str = "Usage: wc [-lwmc] [FILE]...";
+
+// wc [OPTION]... [FILE]... counts lines, words and bytes (-m characters) of each file, or
+// of stdin without files or for `-`, with a total for several files
+pub fn word_count(current_dir: &Path, args: &[&str], io: &mut Io) -> io::Result<i32> {
+    let mut columns = Columns {
+        lines: false,
+        words: false,
+        chars: false,
+        bytes: false,
+    };
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--" => {
+                files.extend(args.by_ref().copied());
+                break;
+            }
+            "--lines" => columns.lines = true,
+            "--words" => columns.words = true,
+            "--chars" => columns.chars = true,
+            "--bytes" => columns.bytes = true,
+            _ if arg.starts_with("--") => {
+                writeln!(io.stderr, "wc: unrecognized option '{}'", arg)?;
+                writeln!(io.stderr, "{}", USAGE)?;
+                return Ok(2);
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'l' => columns.lines = true,
+                        'w' => columns.words = true,
+                        'm' => columns.chars = true,
+                        'c' => columns.bytes = true,
+                        _ => {
+                            writeln!(io.stderr, "wc: invalid option -- '{}'", flag)?;
+                            writeln!(io.stderr, "{}", USAGE)?;
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => files.push(*arg),
+        }
+    }
+
+    if !columns.lines && !columns.words && !columns.chars && !columns.bytes {
+        columns.lines = true;
+        columns.words = true;
+        columns.bytes = true;
+    }
+    let names_given = !files.is_empty();
+    if files.is_empty() {
+        files.push("-");
+    }
+
+    let mut status = 0;
+    let mut rows = Vec::new();
+    let mut total = Counts::default();
+    for name in &files {
+        let counts = builtins::open_input(current_dir, name, io).and_then(count);
+        match counts {
+            Ok(counts) => {
+                total.lines += counts.lines;
+                total.words += counts.words;
+                total.chars += counts.chars;
+                total.bytes += counts.bytes;
+                rows.push((counts, names_given.then_some(*name)));
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(128 + signals::SIGINT),
+            Err(e) => {
+                writeln!(io.stderr, "wc: {}: {}", name, e)?;
+                status = 1;
+            }
+        }
+    }
+    if files.len() > 1 {
+        rows.push((total, Some("total")));
+    }
+
+    // Columns line up on the widest number, with room for a typical count from stdin
+    let selected = [columns.lines, columns.words, columns.chars, columns.bytes];
+    let single = selected.iter().filter(|on| **on).count() == 1;
+    let widest = rows
+        .iter()
+        .map(|(counts, _)| {
+            counts
+                .bytes
+                .max(counts.lines)
+                .max(counts.words)
+                .max(counts.chars)
+        })
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len();
+    let width = match (single && rows.len() == 1, names_given) {
+        (true, _) => 0,
+        (false, false) => widest.max(7),
+        (false, true) => widest,
+    };
+
+    for (counts, name) in rows {
+        let values = [counts.lines, counts.words, counts.chars, counts.bytes];
+        let line: Vec<String> = values
+            .iter()
+            .zip(selected)
+            .filter(|(_, on)| *on)
+            .map(|(value, _)| format!("{:>width$}", value, width = width))
+            .collect();
+        match name {
+            Some(name) => writeln!(io.stdout, "{} {}", line.join(" "), name)?,
+            None => writeln!(io.stdout, "{}", line.join(" "))?,
+        }
+    }
+
+    Ok(status)
+}
+
+// Counts an input a chunk at a time. Words are runs of anything but whitespace and
+// characters are UTF-8 sequences, counted by the bytes that start one.
+fn count(mut input: Box<dyn Read + Send>) -> io::Result<Counts> {
+    let mut counts = Counts::default();
+    let mut in_word = false;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        if signals::interrupt_pending() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let read = match input.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        counts.bytes += read as u64;
+        for &byte in &buffer[..read] {
+            if byte == b'\n' {
+                counts.lines += 1;
+            }
+            if byte & 0xc0 != 0x80 {
+                counts.chars += 1;
+            }
+            if byte.is_ascii_whitespace() || byte == 0x0b {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                counts.words += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDir};
+
+    #[test]
+    fn counts_lines_words_and_characters() {
+        let counts = |text: &str| {
+            let counts = count(Box::new(io::Cursor::new(text.as_bytes().to_vec()))).unwrap();
+            (counts.lines, counts.words, counts.chars, counts.bytes)
+        };
+        assert_eq!(counts(""), (0, 0, 0, 0));
+        assert_eq!(counts("one two\n three"), (1, 3, 14, 14));
+        assert_eq!(counts("a\tb\x0bc\n\n"), (2, 3, 7, 7));
+        assert_eq!(counts("héllo wörld\n"), (1, 2, 12, 14));
+    }
+
+    #[test]
+    fn prints_columns_and_totals() {
+        let dir = TempDir::new("wc-columns");
+        dir.write("a", "one two\nthree\n");
+        dir.write("b", "four\n");
+        let wc =
+            |input: &str, args: &[&str]| testing::run(input, |io| word_count(dir.path(), args, io));
+
+        assert_eq!(wc("x y\n", &[]).stdout, "      1       2       4\n");
+        assert_eq!(wc("x y\n", &["-l"]).stdout, "1\n");
+        // With files the width comes from the largest count, bytes included
+        assert_eq!(wc("", &["-lw", "a"]).stdout, " 2  3 a\n");
+        let run = wc("", &["-l", "a", "b", "missing"]);
+        assert_eq!(run.status, 1);
+        assert_eq!(run.stdout, " 2 a\n 1 b\n 3 total\n");
+        assert!(run.stderr.starts_with("wc: missing: "));
+
+        let run = wc("", &["-x"]);
+        assert_eq!(run.status, 2);
+        assert_eq!(
+            run.stderr,
+            format!("wc: invalid option -- 'x'\n{}\n", USAGE)
+        );
+    }
+}